@@ -18,6 +18,8 @@ pub struct Apu {
     noise: Noise,
     mixer: Mixer,
     frame_counter: FrameCounter,
+    /// frame counter interrupt flag (drives the IRQ line while set)
+    frame_irq: bool,
 }
 
 impl Default for Apu {
@@ -34,6 +36,7 @@ impl Apu {
             noise: Noise::new(),
             mixer: Mixer::new(),
             frame_counter: FrameCounter::new(),
+            frame_irq: false,
         }
     }
 
@@ -64,12 +67,24 @@ impl Apu {
                     FrameCounterMode::Step4
                 };
                 self.frame_counter.flag_irq_inhibit = (data & 0x40) != 0;
+                if self.frame_counter.flag_irq_inhibit {
+                    self.frame_irq = false;
+                }
             }
             _ => {}
         }
     }
 
-    pub fn cpu_read(&self, addr: u16) -> u8 {
+    pub fn cpu_read(&mut self, addr: u16) -> u8 {
+        let data = self.cpu_read_ro(addr);
+        if addr == 0x4015 {
+            // reading status acknowledges the frame interrupt
+            self.frame_irq = false;
+        }
+        data
+    }
+
+    pub fn cpu_read_ro(&self, addr: u16) -> u8 {
         match addr {
             0x4015 => {
                 let mut status = 0;
@@ -77,14 +92,23 @@ impl Apu {
                 status |= (self.pulse[1].get_lc_enable() as u8) << 1;
                 status |= (self.triangle.get_lc_enable() as u8) << 2;
                 status |= (self.noise.get_lc_enable() as u8) << 3;
+                status |= (self.frame_irq as u8) << 6;
                 status
             }
             _ => 0x00,
         }
     }
 
+    /// level of the APU IRQ output
+    pub fn irq_state(&self) -> bool {
+        self.frame_irq
+    }
+
     pub fn clock(&mut self) {
         let clock_events = self.frame_counter.clock();
+        if clock_events.irq {
+            self.frame_irq = true;
+        }
         if clock_events.cpu_cycle {
             self.triangle.clock_cpu();
         }
@@ -0,0 +1,676 @@
+//! Small two-pass 6502 assembler (ca65-like syntax) for testing CPU behavior
+//! without a toolchain.
+//!
+//! ```text
+//! ; comment
+//! count = 10              ; constant
+//!         .org $8000
+//! start:  ldx #count
+//! loop:   dex
+//!         bne loop
+//!         jmp (vector)
+//!         lda a:$0010,x   ; force absolute addressing
+//! vector: .word start
+//! text:   .byte "hi", $0d, <start, >start
+//! ```
+//!
+//! Expressions: numbers (`$ff`, `%1010`, `42`, `'c'`), labels, constants,
+//! `*` (current address), `+ - * / & | ^ << >>`, unary `- ~ < >` (low /
+//! high byte) and parentheses.
+
+use crate::cpu::instr::{AddrMode, INSTR_LOOKUP};
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+
+/// Assembled program
+#[derive(Debug, Default)]
+pub struct Program {
+    /// contiguous blocks of code / data (a new block starts at each `.org`)
+    pub segments: Vec<Segment>,
+    /// code and data labels (constants not included)
+    pub labels: BTreeMap<String, u16>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u16,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    /// source line (1-based)
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl Error for AsmError {}
+
+/// Assemble `src`, starting at address `org` (until the first `.org`)
+pub fn assemble(src: &str, org: u16) -> Result<Program, AsmError> {
+    let lines = src
+        .lines()
+        .enumerate()
+        .map(|(n, l)| parse_line(l).map_err(|msg| AsmError { line: n + 1, msg }))
+        .collect::<Result<Vec<Line>, AsmError>>()?;
+
+    let mut asm = Assembler {
+        symbols: HashMap::new(),
+        labels: BTreeMap::new(),
+        modes: vec![None; lines.len()],
+        final_pass: false,
+    };
+    asm.pass(&lines, org)?;
+    asm.final_pass = true;
+    let segments = asm.pass(&lines, org)?;
+
+    Ok(Program {
+        segments,
+        labels: asm.labels,
+    })
+}
+
+/// Operand syntax
+#[derive(Debug, PartialEq)]
+enum Operand {
+    None,
+    /// `A`
+    Accumulator,
+    /// `#expr`
+    Immediate(String),
+    /// `expr`, `expr,X`, `expr,Y` with optional `z:` / `a:` size prefix
+    Direct(String, Index, Option<Size>),
+    /// `(expr)`
+    Indirect(String),
+    /// `(expr,X)`
+    IndirectX(String),
+    /// `(expr),Y`
+    IndirectY(String),
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Index {
+    None,
+    X,
+    Y,
+}
+
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Size {
+    ZeroPage,
+    Absolute,
+}
+
+#[derive(Debug, PartialEq)]
+enum Statement {
+    None,
+    Org(String),
+    Byte(Vec<String>),
+    Word(Vec<String>),
+    Constant(String, String),
+    Instr(String, Operand),
+}
+
+struct Line {
+    label: Option<String>,
+    statement: Statement,
+}
+
+fn parse_line(line: &str) -> Result<Line, String> {
+    let mut rest = strip_comment(line).trim();
+
+    let mut label = None;
+    if let Some((name, after)) = rest.split_once(':')
+        && is_identifier(name)
+    {
+        label = Some(name.to_string());
+        rest = after.trim();
+    }
+
+    let (word, args) = match rest.split_once(char::is_whitespace) {
+        Some((w, a)) => (w, a.trim()),
+        None => (rest, ""),
+    };
+
+    let statement = if word.is_empty() {
+        Statement::None
+    } else if let Some((name, value)) = rest.split_once('=')
+        && is_identifier(name.trim())
+    {
+        Statement::Constant(name.trim().to_string(), value.trim().to_string())
+    } else if word.starts_with('.') {
+        match word.to_ascii_lowercase().as_str() {
+            ".org" => Statement::Org(args.to_string()),
+            ".byte" | ".byt" => Statement::Byte(split_args(args)),
+            ".word" | ".addr" => Statement::Word(split_args(args)),
+            _ => return Err(format!("unknown directive {word}")),
+        }
+    } else if word.len() == 3 && word.chars().all(|c| c.is_ascii_alphabetic()) {
+        Statement::Instr(word.to_ascii_uppercase(), parse_operand(args)?)
+    } else {
+        return Err(format!("syntax error at '{word}'"));
+    };
+
+    Ok(Line { label, statement })
+}
+
+fn parse_operand(s: &str) -> Result<Operand, String> {
+    let s = s.trim();
+    let upper = s.to_ascii_uppercase();
+    let compact: String = upper.chars().filter(|c| !c.is_whitespace()).collect();
+
+    if s.is_empty() {
+        Ok(Operand::None)
+    } else if compact == "A" {
+        Ok(Operand::Accumulator)
+    } else if let Some(expr) = s.strip_prefix('#') {
+        Ok(Operand::Immediate(expr.trim().to_string()))
+    } else if s.starts_with('(') && compact.ends_with(",X)") {
+        let end = s.to_ascii_uppercase().rfind('X').unwrap_or(0);
+        let inner = s[1..end].trim().trim_end_matches(',');
+        Ok(Operand::IndirectX(inner.trim().to_string()))
+    } else if s.starts_with('(') && compact.ends_with("),Y") {
+        let end = s.rfind(')').unwrap_or(0);
+        Ok(Operand::IndirectY(s[1..end].trim().to_string()))
+    } else if s.starts_with('(') && s.ends_with(')') && matching_paren(s) == Some(s.len() - 1) {
+        Ok(Operand::Indirect(s[1..s.len() - 1].trim().to_string()))
+    } else {
+        let (expr, index) = if compact.ends_with(",X") {
+            (&s[..s.rfind(',').unwrap_or(0)], Index::X)
+        } else if compact.ends_with(",Y") {
+            (&s[..s.rfind(',').unwrap_or(0)], Index::Y)
+        } else {
+            (s, Index::None)
+        };
+        let expr = expr.trim();
+        let (expr, size) = match expr.get(..2).map(|p| p.to_ascii_lowercase()) {
+            Some(p) if p == "z:" => (&expr[2..], Some(Size::ZeroPage)),
+            Some(p) if p == "a:" => (&expr[2..], Some(Size::Absolute)),
+            _ => (expr, None),
+        };
+        Ok(Operand::Direct(expr.trim().to_string(), index, size))
+    }
+}
+
+/// position of the parenthesis closing the one at the start of `s`
+fn matching_paren(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (';', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// split directive arguments at commas outside of quotes
+fn split_args(s: &str) -> Vec<String> {
+    let mut args = vec![];
+    let mut quote = None;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match (c, quote) {
+            ('"' | '\'', None) => quote = Some(c),
+            (c, Some(q)) if c == q => quote = None,
+            (',', None) => {
+                args.push(s[start..i].trim().to_string());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !s[start..].trim().is_empty() {
+        args.push(s[start..].trim().to_string());
+    }
+    args
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '@')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '@')
+}
+
+/// opcode for mnemonic and addressing mode, documented opcodes preferred
+fn opcode(name: &str, mode: AddrMode) -> Option<u8> {
+    let candidates = || {
+        INSTR_LOOKUP
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| i.name == name && i.addr_mode == mode)
+    };
+    candidates()
+        .find(|(_, i)| !i.unofficial)
+        .or_else(|| candidates().next())
+        .map(|(opcode, _)| opcode as u8)
+}
+
+fn operand_len(mode: AddrMode) -> u16 {
+    match mode {
+        AddrMode::Imp => 0,
+        AddrMode::Abs | AddrMode::Abx | AddrMode::Aby | AddrMode::Ind => 2,
+        _ => 1,
+    }
+}
+
+struct Assembler {
+    /// labels and constants
+    symbols: HashMap<String, i64>,
+    labels: BTreeMap<String, u16>,
+    /// addressing mode chosen in the first pass, by line (instruction
+    /// sizes must not change between the passes)
+    modes: Vec<Option<AddrMode>>,
+    final_pass: bool,
+}
+
+impl Assembler {
+    fn pass(&mut self, lines: &[Line], org: u16) -> Result<Vec<Segment>, AsmError> {
+        let mut segments = vec![Segment {
+            addr: org,
+            data: vec![],
+        }];
+        let mut pc = org as i64;
+
+        for (n, line) in lines.iter().enumerate() {
+            let err = |msg: String| AsmError { line: n + 1, msg };
+
+            if let Some(label) = &line.label {
+                if !self.final_pass && self.symbols.contains_key(label) {
+                    return Err(err(format!("duplicate label '{label}'")));
+                }
+                self.symbols.insert(label.clone(), pc);
+                self.labels.insert(label.clone(), pc as u16);
+            }
+
+            let mut out = vec![];
+            match &line.statement {
+                Statement::None => {}
+                Statement::Org(expr) => {
+                    let addr = self
+                        .eval(expr, pc)
+                        .map_err(err)?
+                        .ok_or_else(|| err(String::from(".org needs a known address")))?;
+                    pc = check_range(addr, 0, 0xffff).map_err(err)?;
+                    segments.push(Segment {
+                        addr: pc as u16,
+                        data: vec![],
+                    });
+                }
+                Statement::Constant(name, expr) => {
+                    if let Some(value) = self.eval(expr, pc).map_err(err)? {
+                        if !self.final_pass && self.symbols.contains_key(name) {
+                            return Err(err(format!("duplicate symbol '{name}'")));
+                        }
+                        self.symbols.insert(name.clone(), value);
+                    }
+                }
+                Statement::Byte(args) => {
+                    for arg in args {
+                        if let Some(s) = arg.strip_prefix('"').and_then(|a| a.strip_suffix('"')) {
+                            out.extend(s.bytes());
+                        } else {
+                            let value = self.eval(arg, pc).map_err(err)?.unwrap_or(0);
+                            out.push(check_range(value, -128, 0xff).map_err(err)? as u8);
+                        }
+                    }
+                }
+                Statement::Word(args) => {
+                    for arg in args {
+                        let value = self.eval(arg, pc).map_err(err)?.unwrap_or(0);
+                        let value = check_range(value, -0x8000, 0xffff).map_err(err)? as u16;
+                        out.extend(value.to_le_bytes());
+                    }
+                }
+                Statement::Instr(name, operand) => {
+                    out = self.instr(n, name, operand, pc).map_err(err)?;
+                }
+            }
+
+            pc += out.len() as i64;
+            if pc > 0x10000 {
+                return Err(err(String::from("program exceeds $ffff")));
+            }
+            segments.last_mut().unwrap().data.extend(out);
+        }
+
+        segments.retain(|s| !s.data.is_empty());
+        Ok(segments)
+    }
+
+    fn instr(
+        &mut self,
+        n: usize,
+        name: &str,
+        operand: &Operand,
+        pc: i64,
+    ) -> Result<Vec<u8>, String> {
+        let has = |mode| opcode(name, mode).is_some();
+        let (expr, mode) = match operand {
+            Operand::None | Operand::Accumulator => {
+                // BRK without operand: opcode only
+                if !has(AddrMode::Imp) && has(AddrMode::Imm) && *operand == Operand::None {
+                    return Ok(vec![opcode(name, AddrMode::Imm).unwrap_or(0)]);
+                }
+                (None, AddrMode::Imp)
+            }
+            Operand::Immediate(e) => (Some(e), AddrMode::Imm),
+            Operand::Indirect(e) => (Some(e), AddrMode::Ind),
+            Operand::IndirectX(e) => (Some(e), AddrMode::Izx),
+            Operand::IndirectY(e) => (Some(e), AddrMode::Izy),
+            Operand::Direct(e, Index::None, _) if has(AddrMode::Rel) => (Some(e), AddrMode::Rel),
+            Operand::Direct(e, index, size) => {
+                let (zp, abs) = match index {
+                    Index::None => (AddrMode::Zp0, AddrMode::Abs),
+                    Index::X => (AddrMode::Zpx, AddrMode::Abx),
+                    Index::Y => (AddrMode::Zpy, AddrMode::Aby),
+                };
+                let mode = match self.modes[n] {
+                    Some(mode) => mode,
+                    None => {
+                        let value = self.eval(e, pc)?;
+                        let fits_zp = matches!(value, Some(v) if (0..0x100).contains(&v));
+                        match size {
+                            Some(Size::ZeroPage) => zp,
+                            Some(Size::Absolute) => abs,
+                            None if fits_zp && has(zp) => zp,
+                            None if has(abs) => abs,
+                            None => zp,
+                        }
+                    }
+                };
+                (Some(e), mode)
+            }
+        };
+        self.modes[n] = Some(mode);
+
+        let opcode = opcode(name, mode)
+            .ok_or_else(|| format!("{name} does not support this addressing mode"))?;
+        let mut out = vec![opcode];
+
+        // undefined symbols are errors in the final pass only
+        let value = match expr {
+            Some(e) => self.eval(e, pc)?.unwrap_or(0),
+            None => 0,
+        };
+
+        match mode {
+            AddrMode::Imp => {}
+            AddrMode::Rel if self.final_pass => {
+                let offset = value - (pc + 2);
+                let offset = check_range(offset, -128, 127)
+                    .map_err(|_| String::from("branch out of range"))?;
+                out.push(offset as u8);
+            }
+            AddrMode::Rel => out.push(0),
+            AddrMode::Imm => out.push(check_range(value, -128, 0xff)? as u8),
+            mode if operand_len(mode) == 1 => out.push(check_range(value, 0, 0xff)? as u8),
+            _ => out.extend((check_range(value, 0, 0xffff)? as u16).to_le_bytes()),
+        }
+        Ok(out)
+    }
+
+    /// evaluate an expression, `None` if it uses an undefined symbol
+    /// (allowed in the first pass)
+    fn eval(&self, expr: &str, pc: i64) -> Result<Option<i64>, String> {
+        let mut parser = ExprParser {
+            chars: expr.chars().collect(),
+            pos: 0,
+            symbols: &self.symbols,
+            pc,
+        };
+        let value = parser.expr(0)?;
+        parser.skip_space();
+        if parser.pos < parser.chars.len() {
+            return Err(format!("invalid expression '{expr}'"));
+        }
+        match value {
+            None if self.final_pass => Err(format!("undefined symbol in '{expr}'")),
+            value => Ok(value),
+        }
+    }
+}
+
+fn check_range(value: i64, min: i64, max: i64) -> Result<i64, String> {
+    if (min..=max).contains(&value) {
+        Ok(value & 0xffff)
+    } else {
+        Err(format!("value {value} out of range"))
+    }
+}
+
+/// precedence climbing expression parser
+struct ExprParser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    symbols: &'a HashMap<String, i64>,
+    pc: i64,
+}
+
+impl ExprParser<'_> {
+    fn skip_space(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_space();
+        self.chars.get(self.pos).copied()
+    }
+
+    /// binary operator at the current position: (length, precedence)
+    fn binary_op(&mut self) -> Option<(&'static str, u8)> {
+        let c = self.peek()?;
+        let next = self.chars.get(self.pos + 1).copied();
+        match (c, next) {
+            ('<', Some('<')) => Some(("<<", 4)),
+            ('>', Some('>')) => Some((">>", 4)),
+            ('|', _) => Some(("|", 1)),
+            ('^', _) => Some(("^", 2)),
+            ('&', _) => Some(("&", 3)),
+            ('+', _) => Some(("+", 5)),
+            ('-', _) => Some(("-", 5)),
+            ('*', _) => Some(("*", 6)),
+            ('/', _) => Some(("/", 6)),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, min_prec: u8) -> Result<Option<i64>, String> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec)) = self.binary_op() {
+            if prec < min_prec {
+                break;
+            }
+            self.pos += op.len();
+            let rhs = self.expr(prec + 1)?;
+            lhs = match (lhs, rhs) {
+                (Some(a), Some(b)) => Some(match op {
+                    "|" => a | b,
+                    "^" => a ^ b,
+                    "&" => a & b,
+                    "<<" => a << (b & 0x3f),
+                    ">>" => a >> (b & 0x3f),
+                    "+" => a + b,
+                    "-" => a - b,
+                    "*" => a * b,
+                    _ if b == 0 => return Err(String::from("division by zero")),
+                    _ => a / b,
+                }),
+                _ => None,
+            };
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Option<i64>, String> {
+        let c = self.peek().ok_or_else(|| String::from("missing operand"))?;
+        match c {
+            '-' | '~' | '<' | '>' => {
+                self.pos += 1;
+                let value = self.unary()?;
+                Ok(value.map(|v| match c {
+                    '-' => -v,
+                    '~' => !v & 0xffff,
+                    '<' => v & 0xff,
+                    _ => (v >> 8) & 0xff,
+                }))
+            }
+            '(' => {
+                self.pos += 1;
+                let value = self.expr(0)?;
+                if self.peek() != Some(')') {
+                    return Err(String::from("missing ')'"));
+                }
+                self.pos += 1;
+                Ok(value)
+            }
+            '*' => {
+                self.pos += 1;
+                Ok(Some(self.pc))
+            }
+            '\'' => {
+                let value = self.chars.get(self.pos + 1).copied();
+                if self.chars.get(self.pos + 2) != Some(&'\'') {
+                    return Err(String::from("invalid character literal"));
+                }
+                self.pos += 3;
+                Ok(value.map(|c| c as i64))
+            }
+            '$' | '%' | '0'..='9' => {
+                let (radix, start) = match c {
+                    '$' => (16, self.pos + 1),
+                    '%' => (2, self.pos + 1),
+                    _ => (10, self.pos),
+                };
+                let end = self.token_end(start);
+                let digits: String = self.chars[start..end].iter().collect();
+                self.pos = end;
+                i64::from_str_radix(&digits, radix)
+                    .map(Some)
+                    .map_err(|_| format!("invalid number '{c}{digits}'"))
+            }
+            _ => {
+                let end = self.token_end(self.pos);
+                let name: String = self.chars[self.pos..end].iter().collect();
+                if !is_identifier(&name) {
+                    return Err(format!("unexpected '{c}'"));
+                }
+                self.pos = end;
+                Ok(self.symbols.get(&name).copied())
+            }
+        }
+    }
+
+    fn token_end(&self, start: usize) -> usize {
+        let mut end = start;
+        while self
+            .chars
+            .get(end)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '@')
+        {
+            end += 1;
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble() {
+        let src = "
+            count = 3
+            ptr = $10
+                    .org $8000
+            start:  ldx #count      ; forward and backward references
+            loop:   dex
+                    bne loop
+                    lda ptr,x
+                    lda a:ptr,x
+                    sta (ptr),y
+                    lda (ptr, X)
+                    asl a
+                    brk
+                    jmp (vector)
+                    jsr end
+                    lda #<end + 1
+                    lda #>end
+            vector: .word start, *
+            text:   .byte \"a;b\", 'c', -1, count * 2
+            end:    rts
+        ";
+        let program = assemble(src, 0x0000).unwrap();
+        assert_eq!(
+            program.segments,
+            [Segment {
+                addr: 0x8000,
+                data: vec![
+                    0xa2, 0x03, // LDX #3
+                    0xca, // DEX
+                    0xd0, 0xfd, // BNE loop
+                    0xb5, 0x10, // LDA $10,X
+                    0xbd, 0x10, 0x00, // LDA a:$0010,X
+                    0x91, 0x10, // STA ($10),Y
+                    0xa1, 0x10, // LDA ($10,X)
+                    0x0a, // ASL A
+                    0x00, // BRK
+                    0x6c, 0x1a, 0x80, // JMP (vector)
+                    0x20, 0x24, 0x80, // JSR end
+                    0xa9, 0x25, // LDA #<end + 1
+                    0xa9, 0x80, // LDA #>end
+                    0x00, 0x80, 0x1a, 0x80, // .word
+                    0x61, 0x3b, 0x62, 0x63, 0xff, 0x06, // .byte
+                    0x60, // RTS
+                ],
+            }]
+        );
+        assert_eq!(program.labels["end"], 0x8024);
+        assert!(!program.labels.contains_key("count"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = |src: &str| assemble(src, 0x8000).unwrap_err();
+        assert_eq!(err("nop\nlda undefined").line, 2);
+        assert_eq!(
+            err("bne far\n.org $9000\nfar: rts").msg,
+            "branch out of range"
+        );
+        assert_eq!(err("a: nop\na: nop").msg, "duplicate label 'a'");
+        assert_eq!(
+            err("jmp ($1234),y").msg,
+            "JMP does not support this addressing mode"
+        );
+        assert_eq!(err("lda #$100").msg, "value 256 out of range");
+    }
+}
@@ -1,8 +1,14 @@
-use nessuno::cpu::{Disassembly, Flag};
+use clap::Parser;
+use nessuno::asm;
+use nessuno::cpu::{DisasmEntry, Disassembly, Flag};
 use nessuno::screen::backend::{Frame, ScreenBackend};
 use nessuno::screen::textwriter::{TextScreenParams, TextWriter};
 use nessuno::screen::{Screen, ScreenParams};
-use nessuno::system_debug_cpu::SystemDebugCpu;
+use nessuno::symbols::Symbols;
+use nessuno::system_debug_cpu::{Machine, Stop, SystemDebugCpu};
+use std::fs;
+use std::io;
+use std::io::Write;
 use winit::keyboard::KeyCode;
 use winit_input_helper::WinitInputHelper;
 
@@ -15,6 +21,48 @@ const OFF_COLOR: [u8; 4] = [0xbf, 0x00, 0x00, 0xff];
 const ON_COLOR: [u8; 4] = [0x00, 0xbf, 0x00, 0xff];
 const HL_COLOR: [u8; 4] = [0xbf, 0xbf, 0xff, 0xff];
 
+/// program loaded without a file argument: multiply 10 by 3
+const DEMO_PROGRAM: &str = "
+        ldx #10
+        stx a:$0000
+        ldx #3
+        stx a:$0001
+        ldy a:$0000
+        lda #0
+        clc
+loop:   adc a:$0001
+        dey
+        bne loop
+        sta a:$0002
+        nop
+        nop
+        nop
+";
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    /// program to load: 6502 assembly (.s / .asm) or raw binary
+    file: Option<String>,
+    /// load address (hex), also set as reset vector
+    #[clap(short, long, value_name = "ADDR", value_parser = parse_addr, default_value = "8000")]
+    addr: u16,
+    /// start address (hex) instead of the reset vector
+    #[clap(long, value_name = "ADDR", value_parser = parse_addr)]
+    start: Option<u16>,
+    /// memory map (RAM / ROM regions, mirrors, character output and exit
+    /// ports) from FILE, default: 64 KiB RAM
+    #[clap(short, long, value_name = "FILE")]
+    machine: Option<String>,
+    /// run without a window until the program writes the exit port, print
+    /// its character output and exit with its status
+    #[clap(long)]
+    headless: bool,
+    /// headless: stop after N CPU cycles
+    #[clap(long, value_name = "N")]
+    max_cycles: Option<u64>,
+}
+
 struct DebugCpu {
     system: SystemDebugCpu,
     disasm: Disassembly,
@@ -135,6 +183,25 @@ impl DebugCpu {
                 &BG_COLOR,
             );
         }
+        let jam = if self.system.cpu.jammed() {
+            "JAM"
+        } else {
+            "   "
+        };
+        self.text_writer
+            .write(frame, pos_x + 25, pos_y, jam, &OFF_COLOR, &BG_COLOR);
+        let exit = match self.system.exit_status() {
+            Some(status) => format!("EXIT ${status:02x}"),
+            None => String::new(),
+        };
+        self.text_writer.write(
+            frame,
+            pos_x + 29,
+            pos_y,
+            &format!("{exit:8}"),
+            &OFF_COLOR,
+            &BG_COLOR,
+        );
     }
 
     fn print_disasm(&self, frame: &mut [u8], addr: u16, pos_x: i32, pos_y: i32, range: usize) {
@@ -145,7 +212,7 @@ impl DebugCpu {
             pos_y + range as i32,
             &format!(
                 "{:30}",
-                &self.disasm.get(&addr).unwrap_or(&String::from(""))
+                self.disasm.get(&addr).map(disasm_line).unwrap_or_default()
             ),
             &HL_COLOR,
             &BG_COLOR,
@@ -154,11 +221,10 @@ impl DebugCpu {
         // forward
         let mut it_forward = self.disasm.range(addr..).skip(1);
         for i in 0..range {
-            let line = if let Some((_, v)) = it_forward.next() {
-                v
-            } else {
-                ""
-            };
+            let line = it_forward
+                .next()
+                .map(|(_, e)| disasm_line(e))
+                .unwrap_or_default();
             self.text_writer.write(
                 frame,
                 pos_x,
@@ -172,11 +238,10 @@ impl DebugCpu {
         // backward
         let mut it_backward = self.disasm.range(..addr);
         for i in 0..range {
-            let line = if let Some((_, v)) = it_backward.next_back() {
-                v
-            } else {
-                ""
-            };
+            let line = it_backward
+                .next_back()
+                .map(|(_, e)| disasm_line(e))
+                .unwrap_or_default();
             self.text_writer.write(
                 frame,
                 pos_x,
@@ -230,6 +295,7 @@ impl ScreenBackend for DebugCpu {
                 }
                 _ => {}
             }
+            print_output(&self.system.take_output());
             self.action = None;
             self.paint = true;
         } else {
@@ -251,13 +317,7 @@ impl ScreenBackend for DebugCpu {
 }
 
 impl DebugCpu {
-    fn new() -> DebugCpu {
-        let mut system = SystemDebugCpu::new();
-        system.load_from_str(
-            "A2 0A 8E 00 00 A2 03 8E 01 00 AC 00 00 A9 00 18 6D 01 00 88 D0 FA 8D 02 00 EA EA EA",
-            0x8000,
-        );
-        system.set_reset_vector(0x8000);
+    fn new(system: SystemDebugCpu) -> DebugCpu {
         let disasm = system.cpu_disassemble(0x0000, 0xffff);
 
         DebugCpu {
@@ -276,16 +336,115 @@ impl DebugCpu {
     }
 }
 
-fn main() {
+fn disasm_line(entry: &DisasmEntry) -> String {
+    match &entry.label {
+        Some(label) => format!("${:04x}: {label}: {entry}", entry.addr),
+        None => format!("${:04x}: {entry}", entry.addr),
+    }
+}
+
+/// Load an assembly source or a raw binary at `addr`. The reset vector
+/// points to `addr`, unless the program sets it.
+fn load_program(system: &mut SystemDebugCpu, file: Option<&str>, addr: u16) -> io::Result<()> {
+    system.set_reset_vector(addr);
+
+    let source = match file {
+        None => String::from(DEMO_PROGRAM),
+        Some(f) if f.ends_with(".s") || f.ends_with(".asm") => fs::read_to_string(f)?,
+        Some(f) => {
+            let data = fs::read(f)?;
+            if addr as usize + data.len() > 0x10000 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "binary exceeds $ffff",
+                ));
+            }
+            system.load(&data, addr);
+            return Ok(());
+        }
+    };
+
+    let program =
+        asm::assemble(&source, addr).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    for segment in &program.segments {
+        system.load(&segment.data, segment.addr);
+    }
+    let mut symbols = Symbols::new();
+    for (name, &label_addr) in &program.labels {
+        symbols.add_label(label_addr, name);
+    }
+    system.set_symbols(symbols);
+    Ok(())
+}
+
+fn print_output(bytes: &[u8]) {
+    let mut stdout = io::stdout().lock();
+    // output is best effort, e.g. with a closed pipe
+    let _ = stdout.write_all(bytes).and_then(|_| stdout.flush());
+}
+
+/// Run until the program stops, exit with its status (1 if stopped
+/// otherwise)
+fn run_headless(mut system: SystemDebugCpu, max_cycles: Option<u64>) -> ! {
+    let stop = system.run(max_cycles, print_output);
+    let cycles = system.cycles();
+    let status = match stop {
+        Stop::Exit(status) => {
+            eprintln!("Exit status {status} after {cycles} cycles");
+            status as i32
+        }
+        Stop::Jam => {
+            eprintln!("CPU jammed at ${:04x} after {cycles} cycles", system.cpu.pc);
+            1
+        }
+        Stop::Trap(pc) => {
+            eprintln!("Trap at ${pc:04x} after {cycles} cycles");
+            1
+        }
+        Stop::Limit => {
+            eprintln!("Stopped at ${:04x} after {cycles} cycles", system.cpu.pc);
+            1
+        }
+    };
+    std::process::exit(status);
+}
+
+fn parse_addr(s: &str) -> Result<u16, String> {
+    let hex = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u16::from_str_radix(hex, 16).map_err(|e| e.to_string())
+}
+
+fn main() -> Result<(), io::Error> {
+    let args = Args::parse();
+
+    let mut system = match &args.machine {
+        Some(file) => SystemDebugCpu::with_machine(&Machine::load(file)?),
+        None => SystemDebugCpu::new(),
+    };
+    load_program(&mut system, args.file.as_deref(), args.addr)?;
+    system.cpu_reset();
+    if let Some(start) = args.start {
+        system.cpu.pc = start;
+    }
+
+    if args.headless {
+        run_headless(system, args.max_cycles);
+    }
+
     let screen = Screen::new(
         ScreenParams {
             width: SCREEN_WIDTH,
             height: SCREEN_HEIGHT,
             title: "debug_cpu",
-            backend: Box::new(DebugCpu::new()),
+            backend: Box::new(DebugCpu::new(system)),
         },
         false,
     );
 
     screen.run();
+
+    Ok(())
 }
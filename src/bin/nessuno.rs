@@ -1,17 +1,23 @@
 use clap::Parser;
 use crossbeam_channel::{Sender, bounded};
 use nessuno::audio;
+use nessuno::breakpoint::{Breakpoint, BreakpointHit};
 use nessuno::cartridge::Cartridge;
-use nessuno::cpu::Flag;
+use nessuno::cpu::{DisasmEntry, Flag};
+use nessuno::expr::Expr;
 use nessuno::input::{InputGilrs, InputKeyboard};
+use nessuno::mapper::registry;
 use nessuno::ppu::SetPixel;
 use nessuno::ppu::palette::PALETTE_MAGNUM_FBX;
+use nessuno::profile::Profiler;
 use nessuno::romdb;
 use nessuno::save::SaveState;
 use nessuno::screen::backend::{Frame, ScreenBackend};
 use nessuno::screen::textwriter::{TextScreenParams, TextWriter};
 use nessuno::screen::{Screen, ScreenParams};
+use nessuno::symbols::Symbols;
 use nessuno::system::{System, TvStandard};
+use nessuno::trace::{TraceConditions, Tracer};
 use std::io;
 use winit::keyboard::KeyCode;
 use winit_input_helper::WinitInputHelper;
@@ -46,6 +52,64 @@ struct Args {
     reset: bool,
     #[clap(short, long)]
     pal: bool,
+    /// emulate bus conflicts for UxROM / CNROM / AxROM images without a NES
+    /// 2.0 submapper
+    #[clap(long)]
+    bus_conflicts: bool,
+    /// log executed CPU instructions to FILE (nestest.log layout)
+    #[clap(long, value_name = "FILE")]
+    trace: Option<String>,
+    /// start tracing at this PC (hex)
+    #[clap(long, value_name = "ADDR", value_parser = parse_addr)]
+    trace_start: Option<u16>,
+    /// stop tracing at this PC (hex)
+    #[clap(long, value_name = "ADDR", value_parser = parse_addr)]
+    trace_stop: Option<u16>,
+    /// stop tracing after N instructions
+    #[clap(long, value_name = "N")]
+    trace_limit: Option<u64>,
+    /// only trace instructions for which EXPR is true, e.g. "scanline == 0"
+    #[clap(long, value_name = "EXPR")]
+    trace_if: Option<Expr>,
+    /// profile CPU execution, write a report (cycles per subroutine and
+    /// instruction) to FILE on exit
+    #[clap(long, value_name = "FILE")]
+    profile: Option<String>,
+    /// profile CPU execution, write folded stacks (for flamegraph tools) to
+    /// FILE on exit
+    #[clap(long, value_name = "FILE")]
+    profile_folded: Option<String>,
+    /// log PRG / CHR ROM usage (code, data, rendered graphics) in FCEUX .cdl
+    /// format to FILE on exit, continuing an existing log
+    #[clap(long, value_name = "FILE")]
+    cdl: Option<String>,
+    /// load debug symbols (ca65 .dbg, FCEUX .nl, Mesen .mlb), can be repeated
+    #[clap(long, value_name = "FILE")]
+    symbols: Vec<String>,
+    /// debug mode: stop at breakpoint SPEC ("exec c000", "read 4016",
+    /// "write 2000-2007", "ppuwrite 3f00-3f1f", "nmi", "irq", "scanline 241",
+    /// optionally followed by "if EXPR"; "if EXPR" alone: before any
+    /// instruction), can be repeated
+    #[clap(long = "break", value_name = "SPEC")]
+    breakpoints: Vec<Breakpoint>,
+    /// debug mode: show the value of EXPR, e.g. "[$0300] + x", can be repeated
+    #[clap(long = "watch", value_name = "EXPR")]
+    watches: Vec<Expr>,
+}
+
+struct TraceParams {
+    file: String,
+    conditions: TraceConditions,
+    /// trace from the start (debug mode: toggled with L)
+    enabled: bool,
+}
+
+/// profiler / code data log output files, written on exit
+#[derive(Default)]
+struct DebugOutput {
+    report: Option<String>,
+    folded: Option<String>,
+    cdl: Option<String>,
 }
 
 struct VideoRenderParams {
@@ -74,6 +138,8 @@ struct Nessuno {
     input_keyboard: InputKeyboard,
 
     save: SaveState,
+    trace: TraceParams,
+    output: DebugOutput,
 
     run: bool,
     t_residual: f64,
@@ -81,6 +147,8 @@ struct Nessuno {
     display_oam: bool,
     palette_selected: usize,
     paint: bool,
+    last_break: Option<BreakpointHit>,
+    watches: Vec<Expr>,
 
     frame_duration: f64,
 }
@@ -160,6 +228,13 @@ impl Nessuno {
                 &BG_COLOR,
             );
         }
+        let jam = if self.system.cpu.jammed() {
+            "JAM"
+        } else {
+            "   "
+        };
+        self.text_writer
+            .write(frame, pos_x + 25, pos_y, jam, &OFF_COLOR, &BG_COLOR);
     }
 
     fn print_oam(&self, frame: &mut [u8], pos_x: i32, pos_y: i32, num_entries: usize) {
@@ -175,6 +250,107 @@ impl Nessuno {
         }
     }
 
+    fn print_source(&self, frame: &mut [u8], addr: u16, pos_x: i32, pos_y: i32) {
+        let (pos, text) = match self.system.source_line(addr) {
+            Some(l) => (
+                printable(&format!("{}:{}", l.file, l.line)),
+                printable(l.text.as_deref().unwrap_or_default()),
+            ),
+            None => (String::new(), String::new()),
+        };
+        self.text_writer.write(
+            frame,
+            pos_x,
+            pos_y,
+            &format!("{pos:30.30}\n{text:30.30}"),
+            &FG_COLOR,
+            &BG_COLOR,
+        );
+    }
+
+    fn print_breakpoints(&self, frame: &mut [u8], pos_x: i32, pos_y: i32, rows: usize) {
+        self.text_writer.write(
+            frame,
+            pos_x,
+            pos_y,
+            "Breakpoints (B = at PC)",
+            &FG_COLOR,
+            &BG_COLOR,
+        );
+        let breakpoints = self.system.breakpoints();
+        let last = self.last_break.as_ref().map(|hit| &hit.breakpoint);
+        for row in 0..rows {
+            let (line, color) = match breakpoints.get(row) {
+                Some(b) if Some(b) == last => (format!("> {b}"), &HL_COLOR),
+                Some(b) => (format!("  {b}"), &FG_COLOR),
+                None => (String::new(), &FG_COLOR),
+            };
+            self.text_writer.write(
+                frame,
+                pos_x,
+                pos_y + 1 + row as i32,
+                &format!("{line:25.25}"),
+                color,
+                &BG_COLOR,
+            );
+        }
+    }
+
+    /// innermost frame first, with the last return mismatch below
+    fn print_call_stack(&self, frame: &mut [u8], pos_x: i32, pos_y: i32, rows: usize) {
+        self.text_writer
+            .write(frame, pos_x, pos_y, "Call stack", &FG_COLOR, &BG_COLOR);
+        let call_stack = self.system.cpu.call_stack();
+        let mut frames = call_stack.frames().iter().rev();
+        for row in 0..rows {
+            let (line, color) = match frames.next() {
+                Some(f) => {
+                    let target = match self.system.symbol_label(f.target) {
+                        Some(label) => label.to_string(),
+                        None => format!("${:04x}", f.target),
+                    };
+                    (format!("{} {target}", f.kind), &FG_COLOR)
+                }
+                None if row == rows - 1 => match call_stack.mismatch() {
+                    Some(addr) => (format!("mismatch at ${addr:04x}"), &OFF_COLOR),
+                    None => (String::new(), &FG_COLOR),
+                },
+                None => (String::new(), &FG_COLOR),
+            };
+            self.text_writer.write(
+                frame,
+                pos_x,
+                pos_y + 1 + row as i32,
+                &format!("{line:25.25}"),
+                color,
+                &BG_COLOR,
+            );
+        }
+    }
+
+    fn print_watches(&self, frame: &mut [u8], pos_x: i32, pos_y: i32, rows: usize) {
+        self.text_writer
+            .write(frame, pos_x, pos_y, "Watch", &FG_COLOR, &BG_COLOR);
+        for row in 0..rows {
+            let line = match self.watches.get(row) {
+                Some(expr) => match expr.eval(&self.system) {
+                    v @ 0..=0xff => format!("{expr} = ${v:02x}"),
+                    v @ 0..=0xffff => format!("{expr} = ${v:04x}"),
+                    v => format!("{expr} = {v}"),
+                },
+                None => String::new(),
+            };
+            self.text_writer.write(
+                frame,
+                pos_x,
+                pos_y + 1 + row as i32,
+                &format!("{line:25.25}"),
+                &FG_COLOR,
+                &BG_COLOR,
+            );
+        }
+    }
+
     fn print_disasm(&self, frame: &mut [u8], addr: u16, pos_x: i32, pos_y: i32, range: usize) {
         let addr_start = (addr as i32 - (range as i32 * 3)).max(0) as u16;
         let addr_end = (addr as i32 + (range as i32 * 3)).min(0xffff) as u16;
@@ -185,7 +361,10 @@ impl Nessuno {
             frame,
             pos_x,
             pos_y + range as i32,
-            &format!("{:30}", &disasm.get(&addr).unwrap_or(&String::from(""))),
+            &format!(
+                "{:30}",
+                disasm.get(&addr).map(disasm_line).unwrap_or_default()
+            ),
             &HL_COLOR,
             &BG_COLOR,
         );
@@ -193,11 +372,10 @@ impl Nessuno {
         // forward
         let mut it_forward = disasm.range(addr..).skip(1);
         for i in 0..range {
-            let line = if let Some((_, v)) = it_forward.next() {
-                v
-            } else {
-                ""
-            };
+            let line = it_forward
+                .next()
+                .map(|(_, e)| disasm_line(e))
+                .unwrap_or_default();
             self.text_writer.write(
                 frame,
                 pos_x,
@@ -211,11 +389,10 @@ impl Nessuno {
         // backward
         let mut it_backward = disasm.range(..addr);
         for i in 0..range {
-            let line = if let Some((_, v)) = it_backward.next_back() {
-                v
-            } else {
-                ""
-            };
+            let line = it_backward
+                .next_back()
+                .map(|(_, e)| disasm_line(e))
+                .unwrap_or_default();
             self.text_writer.write(
                 frame,
                 pos_x,
@@ -333,19 +510,15 @@ impl Nessuno {
         audio_send: Sender<f32>,
         sample_rate: u32,
         tv_standard: TvStandard,
+        trace: TraceParams,
+        symbols: Symbols,
     ) -> Nessuno {
         let save = SaveState::new(&cart.sha1_digest);
-        let system = match save.load() {
-            Some(system) if !reset => {
-                println!("Loaded save state from: {}", &save.save_file);
-                system
-            }
-            _ => {
-                let mut system = System::new(cart, sample_rate, tv_standard);
-                system.reset();
-                system
-            }
-        };
+        let mut system = load_system(cart, reset, sample_rate, tv_standard, &save);
+        system.set_symbols(symbols);
+        if trace.enabled {
+            start_trace(&mut system, &trace);
+        }
         Nessuno {
             system,
             text_writer: TextWriter::new(
@@ -366,12 +539,16 @@ impl Nessuno {
             input_keyboard: InputKeyboard::new(),
             audio_send,
             save,
+            trace,
+            output: DebugOutput::default(),
             run: false,
             t_residual: 0f64,
             action: None,
             display_oam: false,
             palette_selected: 0,
             paint: false,
+            last_break: None,
+            watches: vec![],
             frame_duration: match tv_standard {
                 TvStandard::Ntsc => FRAME_DURATION_NTSC,
                 TvStandard::Pal => FRAME_DURATION_PAL,
@@ -379,6 +556,17 @@ impl Nessuno {
         }
     }
 
+    /// stop running on a breakpoint hit, returns true if there was one
+    fn break_on(&mut self, hit: Option<BreakpointHit>) -> bool {
+        let Some(hit) = hit else {
+            return false;
+        };
+        println!("Breakpoint: {} ({})", hit.event, hit.breakpoint);
+        self.run = false;
+        self.last_break = Some(hit);
+        true
+    }
+
     pub fn frame(&mut self, frame: &mut [u8], wait_cpu_complete: bool, send_audio: bool) {
         let mut cpu_complete = loop {
             let clock_res = self.system.clock();
@@ -388,6 +576,14 @@ impl Nessuno {
             if send_audio && let Some(s) = clock_res.audio_sample {
                 self.audio_send.try_send(s).unwrap_or(());
             }
+            if clock_res.cpu_jammed && self.run {
+                // stop running, the debugger shows the jammed state
+                report_jam(&self.system);
+                self.run = false;
+            }
+            if self.break_on(clock_res.breakpoint) {
+                return;
+            }
             if clock_res.frame_complete {
                 break clock_res.cpu_complete;
             }
@@ -417,6 +613,9 @@ impl Nessuno {
                 self.audio_send.try_send(s).unwrap_or(());
                 break;
             }
+            if self.break_on(clock_res.breakpoint) {
+                break;
+            }
         }
     }
 
@@ -438,7 +637,7 @@ impl Nessuno {
             if let Some(p) = clock_res.set_pixel {
                 set_video_pixel(&self.render_params, frame, &p);
             }
-            if !clock_res.cpu_complete {
+            if !clock_res.cpu_complete || clock_res.cpu_jammed {
                 break;
             }
         }
@@ -454,7 +653,7 @@ impl ScreenBackend for Nessuno {
             frame.frame,
             5,
             37,
-            "SPACE = run/pause     CTRL+R = reset     S = step     F = frame     T = toggle oam/disasm     F11 = fullscreen     ESC = quit",
+            "SPACE = run/pause  CTRL+R = reset  S = step  F = frame  T = oam/disasm  L = trace log  B = breakpoint  F11 = fullscreen  ESC = quit",
             &FG_COLOR,
             &BG_COLOR,
         );
@@ -476,6 +675,10 @@ impl ScreenBackend for Nessuno {
         } else {
             self.print_disasm(frame.frame, self.system.cpu.pc, 82, 8, 7);
         }
+        self.print_source(frame.frame, self.system.cpu.pc, 82, 23);
+        self.print_breakpoints(frame.frame, 112, 8, 4);
+        self.print_watches(frame.frame, 112, 13, 4);
+        self.print_call_stack(frame.frame, 112, 18, 6);
     }
 
     fn update(&mut self, frame: Frame, dt: f64) {
@@ -484,11 +687,16 @@ impl ScreenBackend for Nessuno {
                 self.system.controller_update(&input_c1, &input_c2);
             }
 
-            while self.audio_send.len() < AUDIO_BUFFER_SIZE / 2 {
+            while self.run && self.audio_send.len() < AUDIO_BUFFER_SIZE / 2 {
                 self.run_until_audio(frame.frame);
             }
 
-            if self.t_residual > 0f64 {
+            if !self.run {
+                // stopped at a breakpoint
+                self.t_residual = 0f64;
+                self.draw_ppu_data(frame.frame);
+                self.paint = true;
+            } else if self.t_residual > 0f64 {
                 self.t_residual -= dt;
             } else {
                 self.t_residual += self.frame_duration - dt;
@@ -540,6 +748,18 @@ impl ScreenBackend for Nessuno {
             self.action = Some(UserAction::Step);
         } else if input.key_pressed(KeyCode::KeyT) {
             self.display_oam = !self.display_oam;
+        } else if input.key_pressed(KeyCode::KeyL) {
+            if self.system.tracer().is_some() {
+                self.system.set_tracer(None);
+                println!("Stopped CPU trace: {}", &self.trace.file);
+            } else {
+                start_trace(&mut self.system, &self.trace);
+            }
+        } else if input.key_pressed(KeyCode::KeyB) {
+            let breakpoint = Breakpoint::Exec(self.system.cpu.pc);
+            if !self.system.remove_breakpoint(&breakpoint) {
+                self.system.add_breakpoint(breakpoint);
+            }
         } else if input.key_pressed(KeyCode::KeyP) {
             self.palette_selected += 1;
             self.palette_selected &= 0x07;
@@ -548,9 +768,15 @@ impl ScreenBackend for Nessuno {
     }
 
     fn shutdown(&mut self, is_clean: bool) {
+        // flush trace log
+        self.system.set_tracer(None);
+        write_debug_output(&self.system, &self.output);
         if is_clean && self.save.save(&self.system) {
             println!("Saved state to: {}", &self.save.save_file);
         }
+        if self.save.save_flash(&self.system) {
+            println!("Saved flash contents to: {}", &self.save.flash_file);
+        }
     }
 }
 
@@ -564,6 +790,7 @@ struct NessunoMin {
     audio_send: Sender<f32>,
 
     save: SaveState,
+    output: DebugOutput,
 
     run: bool,
     t_residual: f64,
@@ -578,19 +805,15 @@ impl NessunoMin {
         audio_send: Sender<f32>,
         sample_rate: u32,
         tv_standard: TvStandard,
+        trace: TraceParams,
+        symbols: Symbols,
     ) -> NessunoMin {
         let save = SaveState::new(&cart.sha1_digest);
-        let system = match save.load() {
-            Some(system) if !reset => {
-                println!("Loaded save state from: {}", &save.save_file);
-                system
-            }
-            _ => {
-                let mut system = System::new(cart, sample_rate, tv_standard);
-                system.reset();
-                system
-            }
-        };
+        let mut system = load_system(cart, reset, sample_rate, tv_standard, &save);
+        system.set_symbols(symbols);
+        if trace.enabled {
+            start_trace(&mut system, &trace);
+        }
         NessunoMin {
             system,
             render_params: VideoRenderParams {
@@ -604,6 +827,7 @@ impl NessunoMin {
             input_keyboard: InputKeyboard::new(),
             audio_send,
             save,
+            output: DebugOutput::default(),
             run: true,
             t_residual: 0f64,
             frame_duration: match tv_standard {
@@ -635,6 +859,10 @@ impl NessunoMin {
             if let Some(s) = clock_res.audio_sample {
                 self.audio_send.try_send(s).unwrap_or(());
             }
+            if clock_res.cpu_jammed && self.run {
+                report_jam(&self.system);
+                self.run = false;
+            }
             if clock_res.frame_complete {
                 break;
             }
@@ -689,12 +917,151 @@ impl ScreenBackend for NessunoMin {
     }
 
     fn shutdown(&mut self, is_clean: bool) {
+        // flush trace log
+        self.system.set_tracer(None);
+        write_debug_output(&self.system, &self.output);
         if is_clean && self.save.save(&self.system) {
             println!("Saved state to: {}", &self.save.save_file);
         }
+        if self.save.save_flash(&self.system) {
+            println!("Saved flash contents to: {}", &self.save.flash_file);
+        }
     }
 }
 
+/// System from the save state (unless `reset`) or powered on with `cart`,
+/// with the saved flash contents of self-flashing boards
+fn load_system(
+    mut cart: Cartridge,
+    reset: bool,
+    sample_rate: u32,
+    tv_standard: TvStandard,
+    save: &SaveState,
+) -> System {
+    let flash = save.load_flash();
+    let mut flash_loaded = false;
+    let system = match save.load() {
+        Some(mut system) if !reset => {
+            println!("Loaded save state from: {}", &save.save_file);
+            // the flash file is newer after an unclean exit
+            if let Some(data) = &flash {
+                flash_loaded = system.load_cart_prg_flash(data);
+            }
+            system
+        }
+        _ => {
+            if let Some(data) = &flash {
+                flash_loaded = cart.load_prg_flash(data);
+            }
+            System::new(cart, sample_rate, tv_standard)
+        }
+    };
+    if flash_loaded {
+        println!("Loaded flash contents from: {}", &save.flash_file);
+    }
+    system
+}
+
+fn start_debug_output(system: &mut System, output: &DebugOutput) -> Result<(), io::Error> {
+    if output.report.is_some() || output.folded.is_some() {
+        println!("Profiling CPU execution");
+        system.set_profiler(Some(Profiler::new()));
+    }
+    if let Some(file) = &output.cdl {
+        let previous = match std::fs::read(file) {
+            Ok(data) => Some(data),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        if !system.start_cdl(previous.as_deref()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Code data log {file} does not match the ROM size"),
+            ));
+        }
+        match previous {
+            Some(_) => println!("Continuing code data log: {file}"),
+            None => println!("Logging code and data to: {file}"),
+        }
+    }
+    Ok(())
+}
+
+fn write_debug_output(system: &System, output: &DebugOutput) {
+    if let Some(profiler) = system.profiler() {
+        let label = |addr| system.symbol_label(addr).map(String::from);
+        for (file, contents) in [
+            (&output.report, profiler.report(label)),
+            (&output.folded, profiler.folded(label)),
+        ] {
+            if let Some(file) = file {
+                match std::fs::write(file, contents) {
+                    Ok(()) => println!("Wrote CPU profile to: {file}"),
+                    Err(e) => eprintln!("Cannot write profile {file}: {e}"),
+                }
+            }
+        }
+    }
+    if let (Some(cdl), Some(file)) = (system.cdl(), &output.cdl) {
+        let (prg, chr) = cdl.logged();
+        match std::fs::write(file, cdl.to_bytes()) {
+            Ok(()) => println!(
+                "Wrote code data log to: {file} ({prg}/{} PRG, {chr}/{} CHR bytes)",
+                cdl.prg().len(),
+                cdl.chr().len()
+            ),
+            Err(e) => eprintln!("Cannot write code data log {file}: {e}"),
+        }
+    }
+}
+
+fn start_trace(system: &mut System, trace: &TraceParams) {
+    match Tracer::to_file(&trace.file, trace.conditions.clone()) {
+        Ok(tracer) => {
+            println!("Tracing CPU to: {}", &trace.file);
+            system.set_tracer(Some(tracer));
+        }
+        Err(e) => eprintln!("Cannot create trace file {}: {e}", &trace.file),
+    }
+}
+
+/// hex address, optionally prefixed with `$` or `0x`
+fn parse_addr(s: &str) -> Result<u16, String> {
+    let hex = s
+        .strip_prefix('$')
+        .or_else(|| s.strip_prefix("0x"))
+        .unwrap_or(s);
+    u16::from_str_radix(hex, 16).map_err(|e| e.to_string())
+}
+
+fn disasm_line(entry: &DisasmEntry) -> String {
+    match &entry.label {
+        Some(label) => format!("${:04x}: {label}: {entry}", entry.addr),
+        None => format!("${:04x}: {entry}", entry.addr),
+    }
+}
+
+/// text for the debug font: tabs expanded to 8 columns, anything but
+/// printable ASCII replaced
+fn printable(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\t' => out.push_str(&" ".repeat(8 - out.len() % 8)),
+            ' '..='~' => out.push(c),
+            _ => out.push('?'),
+        }
+    }
+    out
+}
+
+fn report_jam(system: &System) {
+    println!(
+        "CPU jammed at ${:04x}, press CTRL+R to reset",
+        system.cpu.pc
+    );
+}
+
 fn set_video_pixel(render_params: &VideoRenderParams, frame: &mut [u8], p: &SetPixel) {
     match render_params.scaling_factor {
         1 => {
@@ -727,6 +1094,9 @@ fn set_video_pixel(render_params: &VideoRenderParams, frame: &mut [u8], p: &SetP
 
 fn main() -> Result<(), io::Error> {
     let args = Args::parse();
+    if args.bus_conflicts {
+        registry::force_bus_conflicts();
+    }
     let cart = Cartridge::new(&args.rom_file, args.patch_file.as_deref())?;
 
     let mut window_title = String::from("nessuno");
@@ -749,35 +1119,72 @@ fn main() -> Result<(), io::Error> {
         TvStandard::Ntsc
     };
 
+    let trace = TraceParams {
+        enabled: args.trace.is_some(),
+        file: args.trace.unwrap_or_else(|| String::from("trace.log")),
+        conditions: TraceConditions {
+            start_pc: args.trace_start,
+            stop_pc: args.trace_stop,
+            max_lines: args.trace_limit,
+            filter: args.trace_if,
+        },
+    };
+
+    let mut symbols = Symbols::new();
+    for file in &args.symbols {
+        symbols.load(file)?;
+        println!("Loaded symbols from: {file}");
+    }
+
+    let output = DebugOutput {
+        report: args.profile,
+        folded: args.profile_folded,
+        cdl: args.cdl,
+    };
+
     let screen = if args.debug {
+        let mut nessuno = Nessuno::new(
+            cart,
+            args.reset,
+            audio_send,
+            sample_rate,
+            tv_standard,
+            trace,
+            symbols,
+        );
+        for breakpoint in args.breakpoints {
+            nessuno.system.add_breakpoint(breakpoint);
+        }
+        nessuno.watches = args.watches;
+        start_debug_output(&mut nessuno.system, &output)?;
+        nessuno.output = output;
         Screen::new(
             ScreenParams {
                 width: SCREEN_WIDTH,
                 height: SCREEN_HEIGHT,
                 title: &window_title,
-                backend: Box::new(Nessuno::new(
-                    cart,
-                    args.reset,
-                    audio_send,
-                    sample_rate,
-                    tv_standard,
-                )),
+                backend: Box::new(nessuno),
             },
             args.fullscreen,
         )
     } else {
+        let mut nessuno = NessunoMin::new(
+            cart,
+            args.reset,
+            audio_send,
+            sample_rate,
+            tv_standard,
+            trace,
+            symbols,
+        );
+        start_debug_output(&mut nessuno.system, &output)?;
+        nessuno.output = output;
         Screen::new(
             ScreenParams {
                 width: SCREEN_WIDTH_MIN,
                 height: SCREEN_HEIGHT_MIN,
                 title: &window_title,
-                backend: Box::new(NessunoMin::new(
-                    cart,
-                    args.reset,
-                    audio_send,
-                    sample_rate,
-                    tv_standard,
-                )),
+                backend: Box::new(nessuno),
             },
             args.fullscreen,
         )
@@ -0,0 +1,301 @@
+use crate::expr::{Expr, ExprContext};
+use std::fmt;
+use std::str::FromStr;
+
+/// Condition that stops emulation, reported by `System::clock`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// instruction at this address is about to execute
+    Exec(u16),
+    /// CPU read in the address range (inclusive), including registers
+    Read(u16, u16),
+    /// CPU write in the address range (inclusive), including registers
+    Write(u16, u16),
+    /// CPU read of PPU memory through PPUDATA ($2007) in the PPU address
+    /// range (inclusive)
+    PpuRead(u16, u16),
+    /// CPU write of PPU memory through PPUDATA ($2007)
+    PpuWrite(u16, u16),
+    /// NMI sequence is about to start
+    Nmi,
+    /// IRQ sequence is about to start
+    Irq,
+    /// PPU reaches dot 0 of this scanline (pre-render line: last scanline
+    /// of the frame, e.g. 261 on NTSC)
+    Scanline(isize),
+    /// condition is true before an instruction
+    Cond(Expr),
+    /// breakpoint, only if the condition is true when hit
+    If(Box<Breakpoint>, Expr),
+}
+
+/// Emulation event checked against the breakpoints
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BreakEvent {
+    Exec(u16),
+    /// address, value
+    Read(u16, u8),
+    Write(u16, u8),
+    /// PPU address, value
+    PpuRead(u16, u8),
+    PpuWrite(u16, u8),
+    Nmi,
+    Irq,
+    Scanline(isize),
+}
+
+/// Breakpoint hit, reported in `SystemClockResult`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub breakpoint: Breakpoint,
+    pub event: BreakEvent,
+}
+
+impl Breakpoint {
+    fn matches(&self, event: &BreakEvent) -> bool {
+        let in_range = |start: &u16, end: &u16, addr: &u16| (*start..=*end).contains(addr);
+        match (self, event) {
+            (Breakpoint::If(breakpoint, _), _) => breakpoint.matches(event),
+            (Breakpoint::Cond(_), BreakEvent::Exec(_)) => true,
+            (Breakpoint::Exec(a), BreakEvent::Exec(pc)) => a == pc,
+            (Breakpoint::Read(s, e), BreakEvent::Read(addr, _))
+            | (Breakpoint::Write(s, e), BreakEvent::Write(addr, _))
+            | (Breakpoint::PpuRead(s, e), BreakEvent::PpuRead(addr, _))
+            | (Breakpoint::PpuWrite(s, e), BreakEvent::PpuWrite(addr, _)) => in_range(s, e, addr),
+            (Breakpoint::Nmi, BreakEvent::Nmi) | (Breakpoint::Irq, BreakEvent::Irq) => true,
+            (Breakpoint::Scanline(n), BreakEvent::Scanline(line)) => n == line,
+            _ => false,
+        }
+    }
+
+    /// condition of a `Cond` / `If` breakpoint
+    pub fn condition(&self) -> Option<&Expr> {
+        match self {
+            Breakpoint::Cond(cond) | Breakpoint::If(_, cond) => Some(cond),
+            _ => None,
+        }
+    }
+}
+
+/// Breakpoint list and the hits since the last `take_hit`
+#[derive(Default)]
+pub(crate) struct Breakpoints {
+    list: Vec<Breakpoint>,
+    /// matching breakpoints, conditions not evaluated yet
+    hits: Vec<BreakpointHit>,
+}
+
+impl Breakpoints {
+    pub fn list(&self) -> &[Breakpoint] {
+        &self.list
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        if !self.list.contains(&breakpoint) {
+            self.list.push(breakpoint);
+        }
+    }
+
+    pub fn remove(&mut self, breakpoint: &Breakpoint) -> bool {
+        let len = self.list.len();
+        self.list.retain(|b| b != breakpoint);
+        self.list.len() != len
+    }
+
+    pub fn check(&mut self, event: BreakEvent) {
+        for breakpoint in self.list.iter().filter(|b| b.matches(&event)) {
+            self.hits.push(BreakpointHit {
+                breakpoint: breakpoint.clone(),
+                event,
+            });
+        }
+    }
+
+    pub fn has_hits(&self) -> bool {
+        !self.hits.is_empty()
+    }
+
+    /// first hit with its condition true in `ctx`
+    pub fn take_hit(&mut self, ctx: &impl ExprContext) -> Option<BreakpointHit> {
+        let hit = self
+            .hits
+            .drain(..)
+            .find(|hit| hit.breakpoint.condition().is_none_or(|c| c.is_true(ctx)));
+        self.hits.clear();
+        hit
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let range = |f: &mut fmt::Formatter, kind: &str, start: u16, end: u16| {
+            if start == end {
+                write!(f, "{kind} ${start:04x}")
+            } else {
+                write!(f, "{kind} ${start:04x}-${end:04x}")
+            }
+        };
+        match self {
+            Breakpoint::Exec(addr) => write!(f, "exec ${addr:04x}"),
+            Breakpoint::Read(s, e) => range(f, "read", *s, *e),
+            Breakpoint::Write(s, e) => range(f, "write", *s, *e),
+            Breakpoint::PpuRead(s, e) => range(f, "ppuread", *s, *e),
+            Breakpoint::PpuWrite(s, e) => range(f, "ppuwrite", *s, *e),
+            Breakpoint::Nmi => write!(f, "nmi"),
+            Breakpoint::Irq => write!(f, "irq"),
+            Breakpoint::Scanline(n) => write!(f, "scanline {n}"),
+            Breakpoint::Cond(cond) => write!(f, "if {cond}"),
+            Breakpoint::If(breakpoint, cond) => write!(f, "{breakpoint} if {cond}"),
+        }
+    }
+}
+
+impl fmt::Display for BreakEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BreakEvent::Exec(pc) => write!(f, "exec ${pc:04x}"),
+            BreakEvent::Read(addr, v) => write!(f, "read ${addr:04x} = ${v:02x}"),
+            BreakEvent::Write(addr, v) => write!(f, "write ${addr:04x} = ${v:02x}"),
+            BreakEvent::PpuRead(addr, v) => write!(f, "ppu read ${addr:04x} = ${v:02x}"),
+            BreakEvent::PpuWrite(addr, v) => write!(f, "ppu write ${addr:04x} = ${v:02x}"),
+            BreakEvent::Nmi => write!(f, "nmi"),
+            BreakEvent::Irq => write!(f, "irq"),
+            BreakEvent::Scanline(n) => write!(f, "scanline {n}"),
+        }
+    }
+}
+
+/// `exec ADDR`, `read ADDR[-ADDR]`, `write ...`, `ppuread ...`,
+/// `ppuwrite ...`, `nmi`, `irq`, `scanline N` (addresses in hex), each
+/// optionally followed by `if EXPR`; `if EXPR` alone is checked before
+/// every instruction
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(cond) = s.strip_prefix("if ") {
+            return Ok(Breakpoint::Cond(cond.parse()?));
+        }
+        if let Some((breakpoint, cond)) = s.split_once(" if ") {
+            return Ok(Breakpoint::If(Box::new(breakpoint.parse()?), cond.parse()?));
+        }
+
+        let addr = |s: &str| {
+            let hex = s
+                .strip_prefix('$')
+                .or_else(|| s.strip_prefix("0x"))
+                .unwrap_or(s);
+            u16::from_str_radix(hex, 16).map_err(|_| format!("invalid address '{s}'"))
+        };
+        let range = |s: &str| match s.split_once('-') {
+            Some((start, end)) => Ok((addr(start)?, addr(end)?)),
+            None => addr(s).map(|a| (a, a)),
+        };
+
+        let (kind, arg) = match s.trim().split_once(char::is_whitespace) {
+            Some((kind, arg)) => (kind, arg.trim()),
+            None => (s.trim(), ""),
+        };
+        let breakpoint = match kind.to_ascii_lowercase().as_str() {
+            "exec" => Breakpoint::Exec(addr(arg)?),
+            "read" => range(arg).map(|(s, e)| Breakpoint::Read(s, e))?,
+            "write" => range(arg).map(|(s, e)| Breakpoint::Write(s, e))?,
+            "ppuread" => range(arg).map(|(s, e)| Breakpoint::PpuRead(s, e))?,
+            "ppuwrite" => range(arg).map(|(s, e)| Breakpoint::PpuWrite(s, e))?,
+            "nmi" => Breakpoint::Nmi,
+            "irq" => Breakpoint::Irq,
+            "scanline" => Breakpoint::Scanline(
+                arg.parse()
+                    .map_err(|_| format!("invalid scanline '{arg}'"))?,
+            ),
+            _ => return Err(format!("unknown breakpoint type '{kind}'")),
+        };
+        Ok(breakpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Var;
+
+    struct State;
+
+    impl ExprContext for State {
+        fn var(&self, var: Var) -> i64 {
+            if var == Var::A { 2 } else { 0 }
+        }
+
+        fn read(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn bank(&self, _addr: u16, _chr: bool) -> Option<usize> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_parse_and_match() {
+        let mut breakpoints = Breakpoints::default();
+        for s in [
+            "exec $c000",
+            "write $2000-$2007",
+            "ppuwrite $3f00-$3f1f",
+            "scanline 241",
+        ] {
+            let breakpoint: Breakpoint = s.parse().unwrap();
+            assert_eq!(breakpoint.to_string(), s);
+            breakpoints.add(breakpoint);
+        }
+        assert_eq!("read 4016".parse(), Ok(Breakpoint::Read(0x4016, 0x4016)));
+        assert!("exec".parse::<Breakpoint>().is_err());
+
+        breakpoints.check(BreakEvent::Read(0x2002, 0x80));
+        breakpoints.check(BreakEvent::Exec(0xc001));
+        assert_eq!(breakpoints.take_hit(&State), None);
+
+        // first hit is kept
+        breakpoints.check(BreakEvent::Write(0x2006, 0x3f));
+        breakpoints.check(BreakEvent::Scanline(241));
+        assert_eq!(
+            breakpoints.take_hit(&State),
+            Some(BreakpointHit {
+                breakpoint: Breakpoint::Write(0x2000, 0x2007),
+                event: BreakEvent::Write(0x2006, 0x3f),
+            })
+        );
+        assert_eq!(breakpoints.take_hit(&State), None);
+
+        assert!(breakpoints.remove(&Breakpoint::Exec(0xc000)));
+        assert_eq!(breakpoints.list().len(), 3);
+    }
+
+    #[test]
+    fn test_conditions() {
+        let mut breakpoints = Breakpoints::default();
+        for s in ["exec $c000 if a == 3", "exec $c000 if a == 2", "if x > $10"] {
+            let breakpoint: Breakpoint = s.parse().unwrap();
+            assert_eq!(breakpoint.to_string(), s);
+            breakpoints.add(breakpoint);
+        }
+        assert!("exec $c000 if a ==".parse::<Breakpoint>().is_err());
+
+        // x = 0, a = 2
+        breakpoints.check(BreakEvent::Exec(0xc000));
+        assert_eq!(
+            breakpoints.take_hit(&State),
+            Some(BreakpointHit {
+                breakpoint: "exec c000 if a == 2".parse().unwrap(),
+                event: BreakEvent::Exec(0xc000),
+            })
+        );
+        breakpoints.check(BreakEvent::Exec(0xc001));
+        assert_eq!(breakpoints.take_hit(&State), None);
+    }
+}
@@ -2,4 +2,14 @@ pub trait CpuBus {
     fn cpu_write(&mut self, addr: u16, data: u8);
     fn cpu_read(&mut self, addr: u16) -> u8;
     fn cpu_read_ro(&self, addr: u16) -> u8;
+
+    /// state of the IRQ line (level-triggered)
+    fn irq_line(&self) -> bool {
+        false
+    }
+
+    /// state of the NMI line (edge-triggered)
+    fn nmi_line(&self) -> bool {
+        false
+    }
 }
@@ -1,7 +1,6 @@
-use crate::mapper::{
-    MapResult, Mapper, mapper000::Mapper000, mapper001::Mapper001, mapper002::Mapper002,
-    mapper003::Mapper003, mapper004::Mapper004, mapper007::Mapper007, mapper009::Mapper009,
-};
+use crate::cdl::CodeDataLog;
+use crate::mapper::registry::{self, MapperParams};
+use crate::mapper::{AudioChip, MapResult, Mapper, MapperDebugState, Nametable};
 use std::fs::File;
 use std::io;
 use std::io::{Cursor, Read, Seek, SeekFrom};
@@ -17,9 +16,18 @@ pub struct Cartridge {
 
     mem_prg: Vec<u8>,
     mem_chr: Vec<u8>,
+    /// additional nametable RAM on the board (four-screen only)
+    mem_vram: Vec<u8>,
+    chr_ram: bool,
+    /// PRG memory was modified by a self-flashing board
+    prg_modified: bool,
 
     hw_mirror: Mirror,
     mapper: Box<dyn Mapper>,
+
+    /// code / data log of ROM accesses (not part of save states)
+    #[serde(skip)]
+    cdl: Option<CodeDataLog>,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
@@ -29,6 +37,7 @@ pub enum Mirror {
     Horizontal,
     OneScreenLo,
     OneScreenHi,
+    FourScreen,
 }
 
 struct CartridgeHeader {
@@ -37,10 +46,13 @@ struct CartridgeHeader {
     chr_rom_chunks: u8,
     mapper1: u8,
     mapper2: u8,
-    _prg_ram_size: u8,
+    /// NES 2.0: mapper MSB / submapper (iNES: PRG RAM size)
+    mapper3: u8,
     _tv_system1: u8,
     _tv_system2: u8,
-    _unused: [u8; 5],
+    /// NES 2.0: CHR-RAM size shift counts
+    chr_ram_size: u8,
+    _unused: [u8; 4],
 }
 
 impl CartridgeHeader {
@@ -54,14 +66,45 @@ impl CartridgeHeader {
             chr_rom_chunks: buf[5],
             mapper1: buf[6],
             mapper2: buf[7],
-            _prg_ram_size: buf[8],
+            mapper3: buf[8],
             _tv_system1: buf[9],
             _tv_system2: buf[10],
-            _unused: [buf[11], buf[12], buf[13], buf[14], buf[15]],
+            chr_ram_size: buf[11],
+            _unused: [buf[12], buf[13], buf[14], buf[15]],
         };
 
         Ok(header)
     }
+
+    fn is_nes2(&self) -> bool {
+        self.mapper2 & 0x0c == 0x08
+    }
+
+    fn mapper_id(&self) -> u16 {
+        let mut mapper_id = ((self.mapper2 >> 4) << 4) as u16 | (self.mapper1 >> 4) as u16;
+        if self.is_nes2() {
+            mapper_id |= ((self.mapper3 & 0x0f) as u16) << 8;
+        }
+        mapper_id
+    }
+
+    fn submapper(&self) -> u8 {
+        if self.is_nes2() { self.mapper3 >> 4 } else { 0 }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.mapper1 & 0x02 != 0
+    }
+
+    /// volatile CHR-RAM size in bytes (NES 2.0), `None` if not specified
+    fn chr_ram_bytes(&self) -> Option<usize> {
+        let shift = self.chr_ram_size & 0x0f;
+        if self.is_nes2() && shift != 0 {
+            Some(64 << shift)
+        } else {
+            None
+        }
+    }
 }
 
 impl Cartridge {
@@ -109,27 +152,37 @@ impl Cartridge {
         if header.mapper1 & 0x04 != 0 {
             let _junk = reader.seek(SeekFrom::Current(512))?;
         }
-        let mapper_id = ((header.mapper2 >> 4) << 4) | (header.mapper1 >> 4);
-        let hw_mirror = if header.mapper1 & 0x01 != 0 {
-            Mirror::Vertical
-        } else {
-            Mirror::Horizontal
-        };
+        let mapper_id = header.mapper_id();
+        let submapper = header.submapper();
 
         let num_banks_prg = header.prg_rom_chunks as usize;
         let num_banks_chr = header.chr_rom_chunks as usize;
 
-        let mapper: Box<dyn Mapper> = match mapper_id {
-            0 => Box::new(Mapper000::new(num_banks_prg, num_banks_chr)),
-            1 => Box::new(Mapper001::new(num_banks_prg, num_banks_chr)),
-            2 => Box::new(Mapper002::new(num_banks_prg, num_banks_chr)),
-            3 => Box::new(Mapper003::new(num_banks_prg, num_banks_chr)),
-            4 => Box::new(Mapper004::new(num_banks_prg, num_banks_chr)),
-            7 => Box::new(Mapper007::new(num_banks_prg, num_banks_chr)),
-            9 => Box::new(Mapper009::new(num_banks_prg, num_banks_chr)),
-            _ => panic!("Unsupported mapper: {mapper_id:03}"),
+        let (board, factory) = registry::lookup(mapper_id, submapper).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported mapper: {mapper_id:03}.{submapper}"),
+            )
+        })?;
+        let mapper = factory(&MapperParams {
+            num_banks_prg,
+            num_banks_chr,
+            submapper,
+            battery: header.has_battery(),
+            vertical_mirroring: header.mapper1 & 0x01 != 0,
+            alt_nametables: header.mapper1 & 0x08 != 0,
+        });
+        let hw_mirror = if header.mapper1 & 0x08 != 0 && !board.mapper_nametables {
+            Mirror::FourScreen
+        } else if header.mapper1 & 0x01 != 0 {
+            Mirror::Vertical
+        } else {
+            Mirror::Horizontal
         };
-        println!("Mapper: {mapper_id:03}, #prg: {num_banks_prg}, #chr: {num_banks_chr}");
+        println!(
+            "Mapper: {mapper_id:03}.{submapper} ({}), #prg: {num_banks_prg}, #chr: {num_banks_chr}",
+            board.name
+        );
 
         let file_type = 1;
         match file_type {
@@ -141,7 +194,7 @@ impl Cartridge {
                 reader.read_exact(&mut mem_prg)?;
 
                 let mem_chr = match num_banks_chr {
-                    0 => vec![0; 8192],
+                    0 => vec![0; header.chr_ram_bytes().unwrap_or(board.chr_ram_size)],
                     _ => {
                         let mut m = vec![0; num_banks_chr * 0x2000];
                         reader.read_exact(&mut m)?;
@@ -149,12 +202,21 @@ impl Cartridge {
                     }
                 };
 
+                let mem_vram = match hw_mirror {
+                    Mirror::FourScreen => vec![0; 2 * 1024],
+                    _ => vec![],
+                };
+
                 Ok(Cartridge {
                     sha1_digest,
                     mem_prg,
                     mem_chr,
+                    mem_vram,
+                    chr_ram: num_banks_chr == 0,
+                    prg_modified: false,
                     hw_mirror,
                     mapper,
+                    cdl: None,
                 })
             }
             2 => {
@@ -166,33 +228,69 @@ impl Cartridge {
         }
     }
 
-    pub fn cpu_read(&mut self, addr: u16) -> Option<u8> {
-        match self.mapper.cpu_map_read(addr) {
-            MapResult::MapAddr(mapped_addr) => Some(self.mem_prg[mapped_addr]),
-            MapResult::DirectRead(v) => Some(v),
-            _ => None,
-        }
+    /// CPU read, bits not driven by the cartridge are taken from `open_bus`
+    pub fn cpu_read(&mut self, addr: u16, open_bus: u8) -> Option<u8> {
+        let res = self.mapper.cpu_map_read(addr);
+        self.cpu_read_result(res, open_bus)
     }
 
-    pub fn cpu_read_ro(&self, addr: u16) -> Option<u8> {
-        match self.mapper.cpu_map_read_ro(addr) {
+    pub fn cpu_read_ro(&self, addr: u16, open_bus: u8) -> Option<u8> {
+        self.cpu_read_result(self.mapper.cpu_map_read_ro(addr), open_bus)
+    }
+
+    fn cpu_read_result(&self, res: MapResult, open_bus: u8) -> Option<u8> {
+        match res {
             MapResult::MapAddr(mapped_addr) => Some(self.mem_prg[mapped_addr]),
             MapResult::DirectRead(v) => Some(v),
+            MapResult::PartialRead(v, mask) => Some((v & mask) | (open_bus & !mask)),
             _ => None,
         }
     }
 
-    pub fn cpu_write(&mut self, addr: u16, data: u8) -> bool {
+    pub fn cpu_write(&mut self, addr: u16, mut data: u8) -> bool {
+        if addr >= 0x8000 && self.mapper.bus_conflicts() {
+            // ROM drives the data bus as well, the mapper latches the AND of both
+            if let Some(rom_data) = self.cpu_read_ro(addr, data) {
+                data &= rom_data;
+            }
+        }
+
         match self.mapper.cpu_map_write(addr, data) {
             MapResult::MapAddr(mapped_addr) => {
                 self.mem_prg[mapped_addr] = data;
                 true
             }
+            MapResult::FlashProgram(mapped_addr) => {
+                self.mem_prg[mapped_addr] &= data;
+                self.prg_modified = true;
+                true
+            }
+            MapResult::FlashErase(mapped_addr, len) => {
+                self.mem_prg[mapped_addr..mapped_addr + len].fill(0xff);
+                self.prg_modified = true;
+                true
+            }
             MapResult::DirectWrite => true,
             _ => false,
         }
     }
 
+    /// PRG memory contents, if modified by a self-flashing board
+    pub fn prg_flash(&self) -> Option<&[u8]> {
+        self.prg_modified.then_some(&self.mem_prg[..])
+    }
+
+    /// Replace PRG memory by a flash image saved from an earlier session.
+    /// Returns false if the image size does not match.
+    pub fn load_prg_flash(&mut self, data: &[u8]) -> bool {
+        if data.len() != self.mem_prg.len() {
+            return false;
+        }
+        self.mem_prg.copy_from_slice(data);
+        self.prg_modified = true;
+        true
+    }
+
     pub fn ppu_read(&mut self, addr: u16) -> Option<u8> {
         match self.mapper.ppu_map_read(addr) {
             MapResult::MapAddr(mapped_addr) => Some(self.mem_chr[mapped_addr]),
@@ -214,25 +312,209 @@ impl Cartridge {
 
     pub fn mirror(&self) -> Mirror {
         let m = self.mapper.mirror();
-        match m {
-            Mirror::Hardware => self.hw_mirror,
+        match (self.hw_mirror, m) {
+            // four-screen wiring on the board overrides the mapper
+            (Mirror::FourScreen, _) => Mirror::FourScreen,
+            (_, Mirror::Hardware) => self.hw_mirror,
             _ => m,
         }
     }
 
+    /// backing memory of nametable slot 0..=3
+    pub fn nametable(&self, slot: usize) -> Nametable {
+        if let Some(nametable) = self.mapper.nametable(slot) {
+            return nametable;
+        }
+
+        match self.mirror() {
+            Mirror::Vertical => Nametable::Ciram(slot & 0x01),
+            Mirror::Horizontal => Nametable::Ciram((slot >> 1) & 0x01),
+            Mirror::OneScreenLo => Nametable::Ciram(0),
+            Mirror::OneScreenHi => Nametable::Ciram(1),
+            Mirror::FourScreen => match slot {
+                0 | 1 => Nametable::Ciram(slot),
+                _ => Nametable::CartRam((slot & 0x01) * 0x0400),
+            },
+            Mirror::Hardware => unreachable!(),
+        }
+    }
+
+    /// read from cartridge-side nametable memory (CIRAM is handled by the PPU)
+    pub fn nametable_read(&mut self, addr: u16) -> u8 {
+        let offset = (addr & 0x03ff) as usize;
+        match self.nametable(((addr >> 10) & 0x03) as usize) {
+            Nametable::Chr(page) => self.mem_chr[(page + offset) % self.mem_chr.len()],
+            Nametable::CartRam(page) => self.mem_vram[page + offset],
+            Nametable::Mapper => self.mapper.nametable_read(addr),
+            Nametable::Ciram(_) => 0x00,
+        }
+    }
+
+    /// write to cartridge-side nametable memory (CIRAM is handled by the PPU)
+    pub fn nametable_write(&mut self, addr: u16, data: u8) {
+        let offset = (addr & 0x03ff) as usize;
+        match self.nametable(((addr >> 10) & 0x03) as usize) {
+            Nametable::Chr(page) => {
+                if self.chr_ram {
+                    let len = self.mem_chr.len();
+                    self.mem_chr[(page + offset) % len] = data;
+                }
+            }
+            Nametable::CartRam(page) => {
+                self.mem_vram[page + offset] = data;
+            }
+            Nametable::Mapper => {
+                self.mapper.nametable_write(addr, data);
+            }
+            Nametable::Ciram(_) => {}
+        }
+    }
+
     pub fn on_scanline_end(&mut self) {
         self.mapper.on_scanline_end();
     }
 
-    pub fn irq_state(&self) -> bool {
-        self.mapper.irq_state()
+    pub fn cpu_clock(&mut self) {
+        self.mapper.cpu_clock();
+    }
+
+    pub fn audio_chip(&self) -> Option<AudioChip> {
+        self.mapper.audio_chip()
     }
 
-    pub fn irq_clear(&mut self) {
-        self.mapper.irq_clear()
+    pub fn audio_sample(&self) -> f32 {
+        self.mapper.audio_sample()
+    }
+
+    pub fn irq_state(&self) -> bool {
+        self.mapper.irq_state()
     }
 
     pub fn reset(&mut self) {
         self.mapper.reset();
     }
+
+    /// Start logging ROM accesses, continuing `previous` (.cdl file
+    /// contents) if given. Returns false if `previous` does not match the
+    /// ROM size.
+    pub fn start_cdl(&mut self, previous: Option<&[u8]>) -> bool {
+        let prg_size = self.mem_prg.len();
+        let chr_size = if self.chr_ram { 0 } else { self.mem_chr.len() };
+        let cdl = match previous {
+            Some(data) => match CodeDataLog::from_bytes(data, prg_size, chr_size) {
+                Some(cdl) => cdl,
+                None => return false,
+            },
+            None => CodeDataLog::new(prg_size, chr_size),
+        };
+        self.cdl = Some(cdl);
+        true
+    }
+
+    pub fn cdl(&self) -> Option<&CodeDataLog> {
+        self.cdl.as_ref()
+    }
+
+    pub fn cdl_active(&self) -> bool {
+        self.cdl.is_some()
+    }
+
+    /// log an access to CPU address `addr`, if mapped to PRG ROM
+    pub fn cdl_log_cpu(&mut self, addr: u16, flags: u8) {
+        if let Some(cdl) = &mut self.cdl
+            && let MapResult::MapAddr(offset) = self.mapper.cpu_map_read_ro(addr)
+        {
+            cdl.log_prg(offset, addr, flags);
+        }
+    }
+
+    /// log an access to PPU address `addr`, if mapped to CHR ROM
+    pub fn cdl_log_ppu(&mut self, addr: u16, flags: u8) {
+        if let Some(cdl) = &mut self.cdl
+            && !self.chr_ram
+            && addr < 0x2000
+            && let MapResult::MapAddr(offset) = self.mapper.ppu_map_read_ro(addr)
+        {
+            cdl.log_chr(offset, flags);
+        }
+    }
+
+    /// mapper state for debuggers, with the effective mirroring
+    pub fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState {
+            mirror: self.mirror(),
+            ..self.mapper.debug_state()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// iNES image with 16 KiB PRG ROM and 8 KiB CHR ROM
+    fn image(mapper_id: u8) -> Vec<u8> {
+        let mut image = vec![
+            b'N',
+            b'E',
+            b'S',
+            0x1a,
+            1,
+            1,
+            mapper_id << 4,
+            mapper_id & 0xf0,
+        ];
+        image.resize(16 + 0x4000 + 0x2000, 0);
+        image
+    }
+
+    #[test]
+    fn mapper_lookup() {
+        assert!(Cartridge::new_impl(Cursor::new(image(0))).is_ok());
+
+        let Err(e) = Cartridge::new_impl(Cursor::new(image(5))) else {
+            panic!("mapper 005 is not supported");
+        };
+        assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+        assert_eq!(e.to_string(), "unsupported mapper: 005.0");
+    }
+
+    #[test]
+    fn four_screen() {
+        let mut rom = image(0);
+        rom[6] |= 0x08;
+        let cart = Cartridge::new_impl(Cursor::new(rom)).unwrap();
+        assert!(matches!(cart.mirror(), Mirror::FourScreen));
+        assert_eq!(cart.mem_vram.len(), 0x0800);
+
+        // UNROM 512 one-screen mode: alternative nametables, horizontal
+        let mut rom = image(30);
+        rom[6] |= 0x08;
+        let cart = Cartridge::new_impl(Cursor::new(rom)).unwrap();
+        assert!(matches!(cart.mirror(), Mirror::OneScreenLo));
+        assert!(matches!(cart.debug_state().mirror, Mirror::OneScreenLo));
+        assert!(cart.mem_vram.is_empty());
+    }
+
+    #[test]
+    fn bus_conflicts() {
+        // UxROM, 4 banks: bank number at offset 1 of each bank, $01 at $8000
+        let mut rom = image(2);
+        rom[4] = 4;
+        rom.resize(16 + 4 * 0x4000 + 0x2000, 0);
+        rom[16] = 0x01;
+        for bank in 0..4 {
+            rom[16 + bank * 0x4000 + 1] = bank as u8;
+        }
+
+        let mut cart = Cartridge::new_impl(Cursor::new(rom.clone())).unwrap();
+        cart.cpu_write(0x8000, 0x03);
+        assert_eq!(cart.cpu_read_ro(0x8001, 0), Some(3));
+
+        // written value ANDed with the ROM byte: bank 3 & $01
+        registry::force_bus_conflicts();
+        let mut cart = Cartridge::new_impl(Cursor::new(rom)).unwrap();
+        cart.cpu_write(0x8000, 0x03);
+        assert_eq!(cart.cpu_read_ro(0x8001, 0), Some(1));
+    }
 }
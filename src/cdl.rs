@@ -0,0 +1,167 @@
+use crate::bus::CpuBus;
+use crate::cpu::Cpu;
+use crate::cpu::instr::{Access, AddrMode, INSTR_LOOKUP, Op};
+
+/// PRG flag: executed as opcode / operand
+pub const PRG_CODE: u8 = 0x01;
+/// PRG flag: read as data
+pub const PRG_DATA: u8 = 0x02;
+/// PRG flag: target of an indirect jump
+pub const PRG_INDIRECT_CODE: u8 = 0x10;
+/// PRG flag: read through a zero page pointer ((zp,X) / (zp),Y)
+pub const PRG_INDIRECT_DATA: u8 = 0x20;
+/// CHR flag: fetched by the PPU for rendering
+pub const CHR_RENDERED: u8 = 0x01;
+/// CHR flag: read by the CPU through PPUDATA ($2007)
+pub const CHR_READ: u8 = 0x02;
+
+/// Code / data log: usage flags for every PRG / CHR ROM byte, by offset
+/// in the ROM image.
+///
+/// Stored in the FCEUX .cdl format: PRG flags followed by CHR flags (none
+/// for CHR RAM). PRG code / data flags include the CPU address window
+/// ($8000 + n * $2000) of the access in bits 2-3.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CodeDataLog {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl CodeDataLog {
+    pub fn new(prg_size: usize, chr_size: usize) -> CodeDataLog {
+        CodeDataLog {
+            prg: vec![0; prg_size],
+            chr: vec![0; chr_size],
+        }
+    }
+
+    /// Log from .cdl file contents, to continue logging. Returns `None` if
+    /// the size does not match the ROM.
+    pub fn from_bytes(data: &[u8], prg_size: usize, chr_size: usize) -> Option<CodeDataLog> {
+        if data.len() != prg_size + chr_size {
+            return None;
+        }
+        let (prg, chr) = data.split_at(prg_size);
+        Some(CodeDataLog {
+            prg: prg.to_vec(),
+            chr: chr.to_vec(),
+        })
+    }
+
+    /// .cdl file contents
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.prg[..], &self.chr[..]].concat()
+    }
+
+    pub fn prg(&self) -> &[u8] {
+        &self.prg
+    }
+
+    pub fn chr(&self) -> &[u8] {
+        &self.chr
+    }
+
+    /// PRG ROM byte at `offset` accessed at CPU address `addr`
+    pub fn log_prg(&mut self, offset: usize, addr: u16, flags: u8) {
+        let window = if addr >= 0x8000 && flags & (PRG_CODE | PRG_DATA) != 0 {
+            (((addr - 0x8000) >> 13) as u8) << 2
+        } else {
+            0
+        };
+        if let Some(f) = self.prg.get_mut(offset) {
+            *f |= flags | window;
+        }
+    }
+
+    /// CHR ROM byte at `offset`
+    pub fn log_chr(&mut self, offset: usize, flags: u8) {
+        if let Some(f) = self.chr.get_mut(offset) {
+            *f |= flags;
+        }
+    }
+
+    /// logged PRG / CHR bytes, e.g. for a coverage summary
+    pub fn logged(&self) -> (usize, usize) {
+        let count = |flags: &[u8]| flags.iter().filter(|&&f| f != 0).count();
+        (count(&self.prg), count(&self.chr))
+    }
+}
+
+/// CPU memory accessed by the instruction at the CPU's PC (about to
+/// execute), as (address, PRG flags). Reads on `bus` without side effects.
+pub(crate) fn instruction_accesses<T: CpuBus>(cpu: &Cpu, bus: &T) -> Vec<(u16, u8)> {
+    let byte = |offset: u16| bus.cpu_read_ro(cpu.pc.wrapping_add(offset));
+    let read16 =
+        |lo: u16, hi: u16| ((bus.cpu_read_ro(hi) as u16) << 8) | bus.cpu_read_ro(lo) as u16;
+    let instr = &INSTR_LOOKUP[byte(0) as usize];
+    let op8 = byte(1);
+    let op16 = ((byte(2) as u16) << 8) | op8 as u16;
+
+    let (len, data) = match instr.addr_mode {
+        AddrMode::Imp => (0, None),
+        AddrMode::Imm | AddrMode::Rel => (1, None),
+        AddrMode::Zp0 => (1, Some((op8 as u16, PRG_DATA))),
+        AddrMode::Zpx => (1, Some((op8.wrapping_add(cpu.x) as u16, PRG_DATA))),
+        AddrMode::Zpy => (1, Some((op8.wrapping_add(cpu.y) as u16, PRG_DATA))),
+        AddrMode::Abs => match instr.op {
+            Op::Jmp | Op::Jsr => (2, None),
+            _ => (2, Some((op16, PRG_DATA))),
+        },
+        AddrMode::Abx => (2, Some((op16.wrapping_add(cpu.x as u16), PRG_DATA))),
+        AddrMode::Aby => (2, Some((op16.wrapping_add(cpu.y as u16), PRG_DATA))),
+        AddrMode::Ind => (2, None),
+        AddrMode::Izx => {
+            let ptr = op8.wrapping_add(cpu.x);
+            let addr = read16(ptr as u16, ptr.wrapping_add(1) as u16);
+            (1, Some((addr, PRG_DATA | PRG_INDIRECT_DATA)))
+        }
+        AddrMode::Izy => {
+            let addr = read16(op8 as u16, op8.wrapping_add(1) as u16).wrapping_add(cpu.y as u16);
+            (1, Some((addr, PRG_DATA | PRG_INDIRECT_DATA)))
+        }
+    };
+
+    let mut accesses: Vec<(u16, u8)> = (0..=len)
+        .map(|i| (cpu.pc.wrapping_add(i), PRG_CODE))
+        .collect();
+    if let Some(data) = data
+        && instr.op.access() != Access::Write
+    {
+        accesses.push(data);
+    }
+    if instr.addr_mode == AddrMode::Ind {
+        // pointer read with the page boundary hw bug
+        let hi = (op16 & 0xff00) | (op16.wrapping_add(1) & 0x00ff);
+        accesses.push((op16, PRG_DATA));
+        accesses.push((hi, PRG_DATA));
+        accesses.push((read16(op16, hi), PRG_CODE | PRG_INDIRECT_CODE));
+    }
+    accesses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log() {
+        let mut cdl = CodeDataLog::new(0x8000, 0x2000);
+        cdl.log_prg(0x0010, 0xc010, PRG_CODE);
+        cdl.log_prg(0x0010, 0xc010, PRG_DATA);
+        cdl.log_prg(0x7fff, 0xffff, PRG_CODE | PRG_INDIRECT_CODE);
+        cdl.log_prg(0x0020, 0x6020, PRG_DATA);
+        cdl.log_chr(0x1000, CHR_RENDERED);
+        cdl.log_chr(0x2000, CHR_READ);
+
+        assert_eq!(cdl.prg()[0x0010], 0x0b);
+        assert_eq!(cdl.prg()[0x7fff], 0x1d);
+        assert_eq!(cdl.prg()[0x0020], 0x02);
+        assert_eq!(cdl.logged(), (3, 1));
+
+        let bytes = cdl.to_bytes();
+        assert_eq!(bytes.len(), 0xa000);
+        assert_eq!(bytes[0x9000], CHR_RENDERED);
+        assert_eq!(CodeDataLog::from_bytes(&bytes, 0x8000, 0x2000), Some(cdl));
+        assert_eq!(CodeDataLog::from_bytes(&bytes, 0x4000, 0x2000), None);
+    }
+}
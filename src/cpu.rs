@@ -1,9 +1,13 @@
-mod instr;
+mod callstack;
+mod disasm;
+pub(crate) mod instr;
 
 use crate::bus::CpuBus;
-use instr::{AddrMode, INSTR_LOOKUP, Instr, Op};
+pub use callstack::{CallFrame, CallKind, CallStack};
+pub use disasm::{DisasmEntry, Disassembly};
+pub use instr::AddrMode;
+use instr::{Access, INSTR_LOOKUP, Instr, Op};
 use serde::{Deserialize, Serialize};
-use std::collections::BTreeMap;
 use std::num::Wrapping;
 
 #[derive(Deserialize, Serialize)]
@@ -15,10 +19,42 @@ pub struct Cpu {
     pub pc: u16,    // program counter
     pub status: u8, // status register
 
-    cycles: u8,
+    /// opcode of the current instruction
+    opcode: u8,
+    /// bus cycle within the current instruction or interrupt sequence,
+    /// 0 between instructions
+    cycle: u8,
+    /// interrupt sequence in progress (instead of an instruction)
+    interrupt: Option<Interrupt>,
+    /// NMI line state of the last cycle (edge detection)
+    nmi_line: bool,
+    /// NMI edge detected, not yet serviced
+    nmi_pending: bool,
+    /// interrupt polled at the end of the last cycle
+    poll: Option<Interrupt>,
+    /// interrupt polled at the end of the cycle before
+    poll_prev: Option<Interrupt>,
+    /// remaining cycles of the reset sequence
+    stall: u8,
+    /// halted by a JAM opcode, only reset recovers
+    jammed: bool,
+    /// data latch (operand, read-modify-write value)
     fetched: u8,
+    /// zero page pointer of the indirect addressing modes
+    ptr: u8,
+    /// effective address
     addr_abs: u16,
-    addr_rel: u16,
+    /// address before indexing (to detect page crossing), interrupt vector
+    addr_base: u16,
+    /// shadow call stack for the debugger (not part of save states)
+    #[serde(skip)]
+    call_stack: CallStack,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Interrupt {
+    Irq,
+    Nmi,
 }
 
 impl Default for Cpu {
@@ -37,10 +73,20 @@ impl Cpu {
             pc: 0x0000,
             status: 0x00,
 
-            cycles: 0,
+            opcode: 0x00,
+            cycle: 0,
+            interrupt: None,
+            nmi_line: false,
+            nmi_pending: false,
+            poll: None,
+            poll_prev: None,
+            stall: 0,
+            jammed: false,
             fetched: 0x00,
+            ptr: 0x00,
             addr_abs: 0x0000,
-            addr_rel: 0x0000,
+            addr_base: 0x0000,
+            call_stack: CallStack::default(),
         }
     }
 
@@ -58,624 +104,894 @@ impl Cpu {
         }
     }
 
+    /// between instructions: the next clock fetches an opcode (or starts
+    /// a pending interrupt sequence)
     pub fn complete(&self) -> bool {
-        self.cycles == 0
+        self.cycle == 0 && self.stall == 0
     }
 
-    pub fn clock<T: CpuBus>(&mut self, bus: &mut T) {
-        if self.cycles == 0 {
-            let opcode = bus.cpu_read(self.pc);
-            self.pc += 1;
+    /// CPU halted by a JAM (KIL) opcode
+    pub fn jammed(&self) -> bool {
+        self.jammed
+    }
 
-            let instr = &INSTR_LOOKUP[opcode as usize];
-            self.cycles = instr.cycles;
+    /// the next clock fetches the opcode of an instruction at PC
+    pub fn instruction_start(&self) -> bool {
+        self.complete() && self.interrupt.is_none() && !self.jammed
+    }
 
-            let add_cycle_addr = self.addr_mode(bus, &instr.addr_mode);
-            let add_cycle_op = self.op(bus, opcode, instr);
-            self.cycles += (add_cycle_addr & add_cycle_op) as u8;
-        }
+    /// subroutines / interrupt handlers in progress
+    pub fn call_stack(&self) -> &CallStack {
+        &self.call_stack
+    }
 
-        self.cycles -= 1;
+    /// the next clock starts this interrupt sequence
+    pub fn interrupt_start(&self) -> Option<Interrupt> {
+        if self.complete() && !self.jammed {
+            self.interrupt
+        } else {
+            None
+        }
     }
 
-    fn addr_mode<T: CpuBus>(&mut self, bus: &mut T, addr_mode: &AddrMode) -> bool {
-        match *addr_mode {
-            AddrMode::Imp => {
-                self.fetched = self.a;
+    /// Run one CPU cycle. Every cycle of an instruction or interrupt
+    /// sequence performs exactly one bus access (including the dummy reads /
+    /// writes of the real 6502). Exceptions: after `reset` (which reads the
+    /// vector at once) the 7 cycles of the reset sequence idle, and a jammed
+    /// CPU stops bus activity after the JAM opcode fetch (the real 6502
+    /// keeps reading $FFFF with the data bus at $FF).
+    ///
+    /// The interrupt lines are polled at the end of every cycle. Whether an
+    /// interrupt sequence follows an instruction is decided by the poll of
+    /// its penultimate cycle, so CLI / SEI / PLP take effect one instruction
+    /// late.
+    pub fn clock<T: CpuBus>(&mut self, bus: &mut T) {
+        if self.jammed {
+            return;
+        }
+
+        if self.stall > 0 {
+            self.stall -= 1;
+            return;
+        }
+
+        self.cycle += 1;
+        if self.cycle == 1 {
+            self.call_stack.start(self.pc);
+        }
+        let done = match self.interrupt {
+            Some(_) => self.interrupt_cycle(bus, 0x00),
+            None if self.cycle == 1 => {
+                self.opcode = self.fetch_pc(bus);
                 false
             }
-            AddrMode::Imm => {
-                self.addr_abs = self.pc;
-                self.pc += 1;
-                false
+            None => self.instr_cycle(bus, &INSTR_LOOKUP[self.opcode as usize]),
+        };
+
+        let nmi_line = bus.nmi_line();
+        if nmi_line && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = nmi_line;
+
+        if done {
+            self.track_calls();
+            self.cycle = 0;
+            self.interrupt = match self.interrupt {
+                // the first instruction of the handler always executes
+                Some(_) => None,
+                None => self.poll,
+            };
+            if let Some(Interrupt::Nmi) = self.interrupt {
+                self.nmi_pending = false;
             }
-            AddrMode::Zp0 => {
-                self.addr_abs = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
+        }
+
+        self.poll_prev = self.poll;
+        self.poll = if self.nmi_pending {
+            Some(Interrupt::Nmi)
+        } else if bus.irq_line() && !self.get_flag(Flag::I) {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        };
+    }
+
+    /// update the call stack after the last cycle of an instruction or
+    /// interrupt sequence
+    fn track_calls(&mut self) {
+        // the interrupt sequence (also BRK) reads the NMI vector if hijacked
+        let interrupt_kind = |cpu: &Cpu, kind| {
+            if cpu.addr_base == 0xfffa {
+                CallKind::Nmi
+            } else {
+                kind
+            }
+        };
+        let kind = match (self.interrupt, &INSTR_LOOKUP[self.opcode as usize].op) {
+            (Some(_), _) => interrupt_kind(self, CallKind::Irq),
+            (None, Op::Jsr) => CallKind::Jsr,
+            (None, Op::Brk) => interrupt_kind(self, CallKind::Brk),
+            (None, Op::Rts) => return self.call_stack.ret(false, self.stkp),
+            (None, Op::Rti) => return self.call_stack.ret(true, self.stkp),
+            _ => return,
+        };
+        self.call_stack.call(kind, self.pc, self.stkp);
+    }
+
+    /// read at PC, advance PC
+    fn fetch_pc<T: CpuBus>(&mut self, bus: &mut T) -> u8 {
+        let data = bus.cpu_read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        data
+    }
+
+    fn push<T: CpuBus>(&mut self, bus: &mut T, data: u8) {
+        bus.cpu_write(0x0100 + self.stkp as u16, data);
+        self.stkp = self.stkp.wrapping_sub(1);
+    }
+
+    /// read at the stack pointer, without incrementing it
+    fn read_stack<T: CpuBus>(&mut self, bus: &mut T) -> u8 {
+        bus.cpu_read(0x0100 + self.stkp as u16)
+    }
+
+    /// Cycles of the interrupt sequence (shared with BRK): returns true on
+    /// the last cycle
+    fn interrupt_cycle<T: CpuBus>(&mut self, bus: &mut T, brk: u8) -> bool {
+        match self.cycle {
+            1 | 2 => {
+                bus.cpu_read(self.pc);
                 false
             }
-            AddrMode::Zpx => {
-                self.addr_abs = bus.cpu_read(self.pc) as u16 + self.x as u16;
-                self.addr_abs &= 0x00ff;
-                self.pc += 1;
+            3 => {
+                self.push(bus, (self.pc >> 8) as u8);
                 false
             }
-            AddrMode::Zpy => {
-                self.addr_abs = bus.cpu_read(self.pc) as u16 + self.y as u16;
-                self.addr_abs &= 0x00ff;
-                self.pc += 1;
+            4 => {
+                self.push(bus, self.pc as u8);
                 false
             }
-            AddrMode::Rel => {
-                self.addr_rel = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-                if self.addr_rel & 0x80 > 0 {
-                    self.addr_rel |= 0xff00;
-                }
+            5 => {
+                self.push(bus, self.status | brk | Flag::U.mask());
+                self.set_flag(Flag::I, true);
+                // an NMI detected until now hijacks a BRK / IRQ sequence
+                self.addr_base =
+                    if self.nmi_pending || matches!(self.interrupt, Some(Interrupt::Nmi)) {
+                        self.nmi_pending = false;
+                        0xfffa
+                    } else {
+                        0xfffe
+                    };
                 false
             }
-            AddrMode::Abs => {
-                let lo = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-                let hi = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                self.addr_abs = (hi << 8) | lo;
-
+            6 => {
+                self.addr_abs = bus.cpu_read(self.addr_base) as u16;
                 false
             }
-            AddrMode::Abx => {
-                let lo = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-                let hi = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                self.addr_abs = (hi << 8) | lo;
-                self.addr_abs += self.x as u16;
-
-                // if high byte has changed (page changed), need additional clock cycle
-                (self.addr_abs & 0xff00) != (hi << 8)
-            }
-            AddrMode::Aby => {
-                let lo = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-                let hi = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                self.addr_abs = (hi << 8) | lo;
-                self.addr_abs += self.y as u16;
-
-                // if high byte has changed (page changed), need additional clock cycle
-                (self.addr_abs & 0xff00) != (hi << 8)
+            _ => {
+                self.pc = ((bus.cpu_read(self.addr_base + 1) as u16) << 8) | self.addr_abs;
+                true
             }
-            AddrMode::Ind => {
-                let ptr_lo = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-                let ptr_hi = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                let ptr = (ptr_hi << 8) | ptr_lo;
+        }
+    }
 
-                if ptr_lo == 0x00ff {
-                    // page boundary hw bug
-                    self.addr_abs =
-                        ((bus.cpu_read(ptr & 0xff00) as u16) << 8) | bus.cpu_read(ptr) as u16;
+    /// Cycle 2 and following of an instruction: returns true on the last
+    /// cycle
+    fn instr_cycle<T: CpuBus>(&mut self, bus: &mut T, instr: &Instr) -> bool {
+        let cycle = self.cycle;
+        let op = &instr.op;
+        match (op, &instr.addr_mode) {
+            (Op::Brk, _) => {
+                if cycle == 2 {
+                    // padding byte
+                    self.fetch_pc(bus);
+                    false
                 } else {
-                    self.addr_abs =
-                        ((bus.cpu_read(ptr + 1) as u16) << 8) | bus.cpu_read(ptr) as u16;
+                    self.interrupt_cycle(bus, Flag::B.mask())
                 }
-                false
             }
-            AddrMode::Izx => {
-                let t = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                let lo = bus.cpu_read((t + self.x as u16) & 0x00ff) as u16;
-                let hi = bus.cpu_read((t + self.x as u16 + 1) & 0x00ff) as u16;
-
-                self.addr_abs = (hi << 8) | lo;
-
-                false
+            (Op::Jsr, _) => match cycle {
+                2 => {
+                    self.addr_abs = self.fetch_pc(bus) as u16;
+                    false
+                }
+                3 => {
+                    self.read_stack(bus);
+                    false
+                }
+                4 => {
+                    self.push(bus, (self.pc >> 8) as u8);
+                    false
+                }
+                5 => {
+                    self.push(bus, self.pc as u8);
+                    false
+                }
+                _ => {
+                    self.pc = ((bus.cpu_read(self.pc) as u16) << 8) | self.addr_abs;
+                    true
+                }
+            },
+            (Op::Rti, _) => match cycle {
+                2 => {
+                    bus.cpu_read(self.pc);
+                    false
+                }
+                3 => {
+                    self.read_stack(bus);
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                4 => {
+                    self.status = (self.read_stack(bus) & !Flag::B.mask()) | Flag::U.mask();
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                5 => {
+                    self.pc = self.read_stack(bus) as u16;
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                _ => {
+                    self.pc |= (self.read_stack(bus) as u16) << 8;
+                    true
+                }
+            },
+            (Op::Rts, _) => match cycle {
+                2 => {
+                    bus.cpu_read(self.pc);
+                    false
+                }
+                3 => {
+                    self.read_stack(bus);
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                4 => {
+                    self.pc = self.read_stack(bus) as u16;
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                5 => {
+                    self.pc |= (self.read_stack(bus) as u16) << 8;
+                    false
+                }
+                _ => {
+                    self.fetch_pc(bus);
+                    true
+                }
+            },
+            (Op::Pha | Op::Php, _) => {
+                if cycle == 2 {
+                    bus.cpu_read(self.pc);
+                    false
+                } else {
+                    let data = match op {
+                        Op::Pha => self.a,
+                        _ => self.status | Flag::B.mask() | Flag::U.mask(),
+                    };
+                    self.push(bus, data);
+                    true
+                }
             }
-            AddrMode::Izy => {
-                let t = bus.cpu_read(self.pc) as u16;
-                self.pc += 1;
-
-                let lo = bus.cpu_read(t & 0x00ff) as u16;
-                let hi = bus.cpu_read((t + 1) & 0x00ff) as u16;
-
-                self.addr_abs = (hi << 8) | lo;
-                self.addr_abs += self.y as u16;
-
-                // if high byte has changed (page changed), need additional clock cycle
-                (self.addr_abs & 0xff00) != (hi << 8)
+            (Op::Pla | Op::Plp, _) => match cycle {
+                2 => {
+                    bus.cpu_read(self.pc);
+                    false
+                }
+                3 => {
+                    self.read_stack(bus);
+                    self.stkp = self.stkp.wrapping_add(1);
+                    false
+                }
+                _ => {
+                    let data = self.read_stack(bus);
+                    if let Op::Pla = op {
+                        self.a = data;
+                        self.set_nz(self.a);
+                    } else {
+                        self.status = (data & !Flag::B.mask()) | Flag::U.mask();
+                    }
+                    true
+                }
+            },
+            (Op::Jmp, AddrMode::Abs) => {
+                if cycle == 2 {
+                    self.addr_abs = self.fetch_pc(bus) as u16;
+                    false
+                } else {
+                    self.pc = ((bus.cpu_read(self.pc) as u16) << 8) | self.addr_abs;
+                    true
+                }
             }
-        }
-    }
-
-    fn op<T: CpuBus>(&mut self, bus: &mut T, opcode: u8, instr: &Instr) -> bool {
-        match instr.op {
-            Op::Adc => {
-                // Add with Carry In
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = self.a as u16 + self.fetched as u16 + self.get_flag(Flag::C) as u16;
-                self.set_flag(Flag::C, tmp > 0x00ff);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                self.set_flag(
-                    Flag::V,
-                    !(self.a as u16 ^ self.fetched as u16) & (self.a as u16 ^ tmp) & 0x0080 != 0,
-                );
-                self.a = (tmp & 0x00ff) as u8;
+            (Op::Jmp, _) => match cycle {
+                2 => {
+                    self.addr_base = self.fetch_pc(bus) as u16;
+                    false
+                }
+                3 => {
+                    self.addr_base |= (self.fetch_pc(bus) as u16) << 8;
+                    false
+                }
+                4 => {
+                    self.addr_abs = bus.cpu_read(self.addr_base) as u16;
+                    false
+                }
+                _ => {
+                    // page boundary hw bug: the pointer high byte is not incremented
+                    let ptr_hi =
+                        (self.addr_base & 0xff00) | (self.addr_base.wrapping_add(1) & 0x00ff);
+                    self.pc = ((bus.cpu_read(ptr_hi) as u16) << 8) | self.addr_abs;
+                    true
+                }
+            },
+            (Op::Jam, _) => {
+                // Halt the CPU, PC stays at the opcode (unofficial)
+                self.pc = self.pc.wrapping_sub(1);
+                self.jammed = true;
                 true
             }
-            Op::And => {
-                // Bitwise Logical AND
-                self.fetch(bus, &instr.addr_mode);
-                self.a &= self.fetched;
-                self.set_flag(Flag::Z, self.a == 0x00);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
+            (_, AddrMode::Imp) => {
+                bus.cpu_read(self.pc);
+                self.implied(op);
                 true
             }
-            Op::Asl => {
-                // Arithmetic shift left
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (self.fetched as u16) << 1;
-                self.set_flag(Flag::C, (tmp & 0xff00) > 0);
-                self.set_flag(Flag::Z, (tmp & 0x00ff) == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-
-                if instr.addr_mode == AddrMode::Imp {
-                    self.a = (tmp & 0x00ff) as u8;
+            (_, AddrMode::Imm) => {
+                self.addr_abs = self.pc;
+                self.pc = self.pc.wrapping_add(1);
+                self.access_cycle(bus, op, 0)
+            }
+            (_, AddrMode::Rel) => self.branch_cycle(bus, self.branch_taken(op)),
+            (_, AddrMode::Zp0) => {
+                if cycle == 2 {
+                    self.addr_abs = self.fetch_pc(bus) as u16;
+                    false
                 } else {
-                    bus.cpu_write(self.addr_abs, (tmp & 0x00ff) as u8);
+                    self.access_cycle(bus, op, cycle - 3)
                 }
-
-                false
             }
-            Op::Bcc => {
-                // Branch if Carry Clear
-                if !self.get_flag(Flag::C) {
-                    self.branch();
+            (_, AddrMode::Zpx | AddrMode::Zpy) => match cycle {
+                2 => {
+                    self.addr_abs = self.fetch_pc(bus) as u16;
+                    false
                 }
-                false
-            }
-            Op::Bcs => {
-                // Branch if Carry Set
-                if self.get_flag(Flag::C) {
-                    self.branch();
+                3 => {
+                    bus.cpu_read(self.addr_abs);
+                    let index = match instr.addr_mode {
+                        AddrMode::Zpx => self.x,
+                        _ => self.y,
+                    };
+                    self.addr_abs = (self.addr_abs + index as u16) & 0x00ff;
+                    false
                 }
-                false
-            }
-            Op::Beq => {
-                // Branch if Equal
-                if self.get_flag(Flag::Z) {
-                    self.branch();
+                _ => self.access_cycle(bus, op, cycle - 4),
+            },
+            (_, AddrMode::Abs) => match cycle {
+                2 => {
+                    self.addr_abs = self.fetch_pc(bus) as u16;
+                    false
                 }
-                false
-            }
-            Op::Bit => {
-                // Bit testing ??
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = self.a & self.fetched;
-                self.set_flag(Flag::Z, tmp == 0x00);
-                self.set_flag(Flag::N, self.fetched & (1 << 7) != 0);
-                self.set_flag(Flag::V, self.fetched & (1 << 6) != 0);
-                false
-            }
-            Op::Bmi => {
-                // Branch if Negative
-                if self.get_flag(Flag::N) {
-                    self.branch();
+                3 => {
+                    self.addr_abs |= (self.fetch_pc(bus) as u16) << 8;
+                    false
                 }
-                false
-            }
-            Op::Bne => {
-                // Branch if Not Equal
-                if !self.get_flag(Flag::Z) {
-                    self.branch();
+                _ => self.access_cycle(bus, op, cycle - 4),
+            },
+            (_, AddrMode::Abx | AddrMode::Aby) => match cycle {
+                2 => {
+                    self.addr_base = self.fetch_pc(bus) as u16;
+                    false
                 }
-                false
-            }
-            Op::Bpl => {
-                // Branch if Positive
-                if !self.get_flag(Flag::N) {
-                    self.branch();
+                3 => {
+                    self.addr_base |= (self.fetch_pc(bus) as u16) << 8;
+                    let index = match instr.addr_mode {
+                        AddrMode::Abx => self.x,
+                        _ => self.y,
+                    };
+                    self.addr_abs = self.addr_base.wrapping_add(index as u16);
+                    false
                 }
-                false
-            }
-            Op::Brk => {
-                // Break (Program sourced interrupt)
-                self.pc += 1;
-
-                self.set_flag(Flag::I, true);
-                bus.cpu_write(0x0100 + self.stkp as u16, ((self.pc >> 8) & 0x00ff) as u8);
-                self.stkp -= 1;
-                bus.cpu_write(0x0100 + self.stkp as u16, (self.pc & 0x00ff) as u8);
-                self.stkp -= 1;
+                4 => self.indexed_cycle(bus, op),
+                _ => self.access_cycle(bus, op, cycle - 5),
+            },
+            (_, AddrMode::Izx) => match cycle {
+                2 => {
+                    self.ptr = self.fetch_pc(bus);
+                    false
+                }
+                3 => {
+                    bus.cpu_read(self.ptr as u16);
+                    self.ptr = self.ptr.wrapping_add(self.x);
+                    false
+                }
+                4 => {
+                    self.addr_abs = bus.cpu_read(self.ptr as u16) as u16;
+                    false
+                }
+                5 => {
+                    self.addr_abs |= (bus.cpu_read(self.ptr.wrapping_add(1) as u16) as u16) << 8;
+                    false
+                }
+                _ => self.access_cycle(bus, op, cycle - 6),
+            },
+            (_, AddrMode::Izy) => match cycle {
+                2 => {
+                    self.ptr = self.fetch_pc(bus);
+                    false
+                }
+                3 => {
+                    self.addr_base = bus.cpu_read(self.ptr as u16) as u16;
+                    false
+                }
+                4 => {
+                    self.addr_base |= (bus.cpu_read(self.ptr.wrapping_add(1) as u16) as u16) << 8;
+                    self.addr_abs = self.addr_base.wrapping_add(self.y as u16);
+                    false
+                }
+                5 => self.indexed_cycle(bus, op),
+                _ => self.access_cycle(bus, op, cycle - 6),
+            },
+            (_, AddrMode::Ind) => unreachable!("indirect addressing is used by JMP only"),
+        }
+    }
 
-                self.set_flag(Flag::B, true);
-                bus.cpu_write(0x0100 + self.stkp as u16, self.status);
-                self.stkp -= 1;
-                self.set_flag(Flag::B, false);
+    /// Indexed access before the high byte of the address is fixed up. For
+    /// reads without page crossing this already is the operand read.
+    fn indexed_cycle<T: CpuBus>(&mut self, bus: &mut T, op: &Op) -> bool {
+        if op.access() == Access::Read && !self.page_crossed() {
+            self.access_cycle(bus, op, 0)
+        } else {
+            bus.cpu_read((self.addr_base & 0xff00) | (self.addr_abs & 0x00ff));
+            false
+        }
+    }
 
-                let lo = bus.cpu_read(0xfffe) as u16;
-                let hi = bus.cpu_read(0xffff) as u16;
-                self.pc = (hi << 8) | lo;
+    fn page_crossed(&self) -> bool {
+        (self.addr_base & 0xff00) != (self.addr_abs & 0xff00)
+    }
 
+    /// Access `n` (from 0) to the operand at the effective address: returns
+    /// true on the last cycle
+    fn access_cycle<T: CpuBus>(&mut self, bus: &mut T, op: &Op, n: u8) -> bool {
+        match (op.access(), n) {
+            (Access::Read, _) => {
+                self.fetched = bus.cpu_read(self.addr_abs);
+                self.read_op(op, self.fetched);
+                true
+            }
+            (Access::Write, _) => {
+                self.store(bus, op);
+                true
+            }
+            (Access::Rmw, 0) => {
+                self.fetched = bus.cpu_read(self.addr_abs);
                 false
             }
-            Op::Bvc => {
-                // Branch if Overflow Clear
-                if !self.get_flag(Flag::V) {
-                    self.branch();
-                }
+            (Access::Rmw, 1) => {
+                // the unmodified value is written back while the ALU works
+                bus.cpu_write(self.addr_abs, self.fetched);
+                self.fetched = self.rmw_op(op, self.fetched);
                 false
             }
-            Op::Bvs => {
-                // Branch if Overflow Set
-                if self.get_flag(Flag::V) {
-                    self.branch();
+            (Access::Rmw, _) => {
+                bus.cpu_write(self.addr_abs, self.fetched);
+                true
+            }
+        }
+    }
+
+    /// Relative branch: not taken 2 cycles, taken 3, taken to another page 4
+    fn branch_cycle<T: CpuBus>(&mut self, bus: &mut T, taken: bool) -> bool {
+        match self.cycle {
+            2 => {
+                self.fetched = self.fetch_pc(bus);
+                !taken
+            }
+            3 => {
+                bus.cpu_read(self.pc);
+                self.addr_abs = self.pc.wrapping_add(self.fetched as i8 as u16);
+                let same_page = (self.addr_abs & 0xff00) == (self.pc & 0xff00);
+                self.pc = (self.pc & 0xff00) | (self.addr_abs & 0x00ff);
+                if same_page {
+                    // taken branch without page crossing: interrupts are only
+                    // polled on the operand cycle, the last poll is ignored
+                    self.poll = self.poll_prev;
                 }
-                false
+                same_page
+            }
+            _ => {
+                bus.cpu_read(self.pc);
+                self.pc = self.addr_abs;
+                true
+            }
+        }
+    }
+
+    fn branch_taken(&self, op: &Op) -> bool {
+        match op {
+            Op::Bcc => !self.get_flag(Flag::C),
+            Op::Bcs => self.get_flag(Flag::C),
+            Op::Beq => self.get_flag(Flag::Z),
+            Op::Bmi => self.get_flag(Flag::N),
+            Op::Bne => !self.get_flag(Flag::Z),
+            Op::Bpl => !self.get_flag(Flag::N),
+            Op::Bvc => !self.get_flag(Flag::V),
+            Op::Bvs => self.get_flag(Flag::V),
+            _ => false,
+        }
+    }
+
+    /// Instructions without memory operand (including accumulator shifts)
+    fn implied(&mut self, op: &Op) {
+        match op {
+            Op::Asl | Op::Lsr | Op::Rol | Op::Ror => {
+                // Shift / rotate accumulator
+                self.a = self.rmw_op(op, self.a);
             }
             Op::Clc => {
                 // Clear Carry Flag
                 self.set_flag(Flag::C, false);
-                false
             }
             Op::Cld => {
                 // Clear Decimal Flag
                 self.set_flag(Flag::D, false);
-                false
             }
             Op::Cli => {
                 // Disable Interrupts / Clear Interrupt Flag
                 self.set_flag(Flag::I, false);
-                false
             }
             Op::Clv => {
                 // Clear Overflow Flag
                 self.set_flag(Flag::V, false);
-                false
-            }
-            Op::Cmp => {
-                // Compare Accumulator
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (Wrapping(self.a as u16) - Wrapping(self.fetched as u16)).0;
-                self.set_flag(Flag::C, self.a >= self.fetched);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                true
-            }
-            Op::Cpx => {
-                // Compare X register
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (Wrapping(self.x as u16) - Wrapping(self.fetched as u16)).0;
-                self.set_flag(Flag::C, self.x >= self.fetched);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                false
-            }
-            Op::Cpy => {
-                // Compare Y register
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (Wrapping(self.y as u16) - Wrapping(self.fetched as u16)).0;
-                self.set_flag(Flag::C, self.y >= self.fetched);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                false
-            }
-            Op::Dec => {
-                // Decrement Value at Memory Location
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (Wrapping(self.fetched) - Wrapping(1)).0;
-                bus.cpu_write(self.addr_abs, tmp);
-                self.set_flag(Flag::Z, tmp == 0);
-                self.set_flag(Flag::N, tmp & 0x80 != 0);
-                false
             }
             Op::Dex => {
                 // Decrement X Register
-                self.x = (Wrapping(self.x) - Wrapping(1)).0;
-                self.set_flag(Flag::Z, self.x == 0);
-                self.set_flag(Flag::N, self.x & 0x80 != 0);
-                false
+                self.x = self.x.wrapping_sub(1);
+                self.set_nz(self.x);
             }
             Op::Dey => {
                 // Decrement Y Register
-                self.y = (Wrapping(self.y) - Wrapping(1)).0;
-                self.set_flag(Flag::Z, self.y == 0);
-                self.set_flag(Flag::N, self.y & 0x80 != 0);
-                false
-            }
-            Op::Eor => {
-                // Bitwise Logical AND
-                self.fetch(bus, &instr.addr_mode);
-                self.a ^= self.fetched;
-                self.set_flag(Flag::Z, self.a == 0x00);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                true
-            }
-            Op::Inc => {
-                // Increment Value at Memory Location
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = (Wrapping(self.fetched) + Wrapping(1)).0;
-                bus.cpu_write(self.addr_abs, tmp);
-                self.set_flag(Flag::Z, tmp == 0);
-                self.set_flag(Flag::N, tmp & 0x80 != 0);
-                false
+                self.y = self.y.wrapping_sub(1);
+                self.set_nz(self.y);
             }
             Op::Inx => {
                 // Increment X Register
-                self.x = (Wrapping(self.x) + Wrapping(1)).0;
-                self.set_flag(Flag::Z, self.x == 0);
-                self.set_flag(Flag::N, self.x & 0x80 != 0);
-                false
+                self.x = self.x.wrapping_add(1);
+                self.set_nz(self.x);
             }
             Op::Iny => {
                 // Increment Y Register
-                self.y = (Wrapping(self.y) + Wrapping(1)).0;
-                self.set_flag(Flag::Z, self.y == 0);
-                self.set_flag(Flag::N, self.y & 0x80 != 0);
-                false
+                self.y = self.y.wrapping_add(1);
+                self.set_nz(self.y);
             }
-            Op::Jmp => {
-                // Jump To Location
-                self.pc = self.addr_abs;
-                false
+            Op::Sec => {
+                // Set Carry Flag
+                self.set_flag(Flag::C, true);
             }
-            Op::Jsr => {
-                // Jump To Sub-Routine
-                self.pc -= 1;
-
-                bus.cpu_write(0x0100 + self.stkp as u16, ((self.pc >> 8) & 0x00ff) as u8);
-                self.stkp -= 1;
-                bus.cpu_write(0x0100 + self.stkp as u16, (self.pc & 0x00ff) as u8);
-                self.stkp -= 1;
+            Op::Sed => {
+                // Set Decimal Flag
+                self.set_flag(Flag::D, true);
+            }
+            Op::Sei => {
+                // Set Interrupt Flag / Enable Interrupts
+                self.set_flag(Flag::I, true);
+            }
+            Op::Tax => {
+                // Transfer Accumulator to X Register
+                self.x = self.a;
+                self.set_nz(self.x);
+            }
+            Op::Tay => {
+                // Transfer Accumulator to Y Register
+                self.y = self.a;
+                self.set_nz(self.y);
+            }
+            Op::Tsx => {
+                // Transfer Stack Pointer to X Register
+                self.x = self.stkp;
+                self.set_nz(self.x);
+            }
+            Op::Txa => {
+                // Transfer X Register to Accumulator
+                self.a = self.x;
+                self.set_nz(self.a);
+            }
+            Op::Txs => {
+                // Transfer X Register to Stack Pointer
+                self.stkp = self.x;
+            }
+            Op::Tya => {
+                // Transfer Y Register to Accumulator
+                self.a = self.y;
+                self.set_nz(self.a);
+            }
+            _ => {
+                // No Operation
+            }
+        }
+    }
 
-                self.pc = self.addr_abs;
-                false
+    /// Instructions reading their operand
+    fn read_op(&mut self, op: &Op, value: u8) {
+        match op {
+            Op::Adc => {
+                // Add with Carry In
+                self.adc(value);
+            }
+            Op::And => {
+                // Bitwise Logical AND
+                self.a &= value;
+                self.set_nz(self.a);
+            }
+            Op::Bit => {
+                // Bit testing
+                self.set_flag(Flag::Z, self.a & value == 0x00);
+                self.set_flag(Flag::N, value & (1 << 7) != 0);
+                self.set_flag(Flag::V, value & (1 << 6) != 0);
+            }
+            Op::Cmp => {
+                // Compare Accumulator
+                self.compare(self.a, value);
+            }
+            Op::Cpx => {
+                // Compare X register
+                self.compare(self.x, value);
+            }
+            Op::Cpy => {
+                // Compare Y register
+                self.compare(self.y, value);
+            }
+            Op::Eor => {
+                // Bitwise Logical XOR
+                self.a ^= value;
+                self.set_nz(self.a);
             }
             Op::Lda => {
                 // Load Accumulator
-                self.fetch(bus, &instr.addr_mode);
-                self.a = self.fetched;
-                self.set_flag(Flag::Z, self.a == 0);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                true
+                self.a = value;
+                self.set_nz(self.a);
             }
             Op::Ldx => {
                 // Load X Register
-                self.fetch(bus, &instr.addr_mode);
-                self.x = self.fetched;
-                self.set_flag(Flag::Z, self.x == 0);
-                self.set_flag(Flag::N, self.x & 0x80 != 0);
-                true
+                self.x = value;
+                self.set_nz(self.x);
             }
             Op::Ldy => {
                 // Load Y Register
-                self.fetch(bus, &instr.addr_mode);
-                self.y = self.fetched;
-                self.set_flag(Flag::Z, self.y == 0);
-                self.set_flag(Flag::N, self.y & 0x80 != 0);
-                true
-            }
-            Op::Lsr => {
-                // Logical Shift Right
-                self.fetch(bus, &instr.addr_mode);
-
-                if instr.addr_mode == AddrMode::Imp {
-                    self.set_flag(Flag::C, self.a & 0x01 > 0);
-                    let tmp = self.a >> 1;
-                    self.set_flag(Flag::Z, tmp == 0);
-                    self.set_flag(Flag::N, false);
-                    self.a = tmp;
-                } else {
-                    self.set_flag(Flag::C, self.fetched & 0x01 > 0);
-                    let tmp = self.fetched >> 1;
-                    self.set_flag(Flag::Z, tmp == 0);
-                    self.set_flag(Flag::N, false);
-                    bus.cpu_write(self.addr_abs, tmp);
-                }
-                false
-            }
-            Op::Nop => {
-                // No Operation
-                // some NOP variants add additional cycle
-                matches!(opcode, 0x1c | 0x3c | 0x5c | 0x7c | 0xdc | 0xfc)
+                self.y = value;
+                self.set_nz(self.y);
             }
             Op::Ora => {
                 // Bitwise Logical OR
-                self.fetch(bus, &instr.addr_mode);
-                self.a |= self.fetched;
-                self.set_flag(Flag::Z, self.a == 0x00);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                true
+                self.a |= value;
+                self.set_nz(self.a);
             }
-            Op::Pha => {
-                // Push Accumulator to Stack
-                bus.cpu_write(0x0100 + self.stkp as u16, self.a);
-                self.stkp -= 1;
-                false
+            Op::Sbc => {
+                // Substract With Carry
+                self.adc(!value);
+            }
+            Op::Alr => {
+                // AND immediate, then Logical Shift Right accumulator (unofficial)
+                let tmp = self.a & value;
+                self.set_flag(Flag::C, tmp & 0x01 != 0);
+                self.a = tmp >> 1;
+                self.set_nz(self.a);
+            }
+            Op::Anc => {
+                // AND immediate, copy bit 7 to carry (unofficial)
+                self.a &= value;
+                self.set_nz(self.a);
+                self.set_flag(Flag::C, self.a & 0x80 != 0);
+            }
+            Op::Arr => {
+                // AND immediate, then Rotate Right accumulator (unofficial)
+                let tmp = self.a & value;
+                self.a = (tmp >> 1) | ((self.get_flag(Flag::C) as u8) << 7);
+                self.set_nz(self.a);
+                self.set_flag(Flag::C, self.a & 0x40 != 0);
+                self.set_flag(Flag::V, ((self.a >> 6) ^ (self.a >> 5)) & 0x01 != 0);
+            }
+            Op::Axs => {
+                // X = (A & X) - immediate, without borrow (unofficial)
+                let tmp = self.a & self.x;
+                self.set_flag(Flag::C, tmp >= value);
+                self.x = tmp.wrapping_sub(value);
+                self.set_nz(self.x);
+            }
+            Op::Las => {
+                // A = X = SP = memory & SP (unofficial)
+                let tmp = value & self.stkp;
+                self.a = tmp;
+                self.x = tmp;
+                self.stkp = tmp;
+                self.set_nz(tmp);
+            }
+            Op::Lax => {
+                // Load Accumulator and X Register (unofficial)
+                self.a = value;
+                self.x = value;
+                self.set_nz(self.a);
+            }
+            Op::Lxa => {
+                // A = X = (A | magic) & immediate (unofficial, unstable)
+                self.a = (self.a | 0xff) & value;
+                self.x = self.a;
+                self.set_nz(self.a);
             }
-            Op::Php => {
-                // Push Status Register to Stack
-                bus.cpu_write(
-                    0x0100 + self.stkp as u16,
-                    self.status | Flag::B.mask() | Flag::U.mask(),
-                );
-                self.set_flag(Flag::B, false);
-                self.set_flag(Flag::U, false);
-                self.stkp -= 1;
-                false
+            Op::Xaa => {
+                // A = (A | magic) & X & immediate (unofficial, unstable)
+                self.a = (self.a | 0xee) & self.x & value;
+                self.set_nz(self.a);
             }
-            Op::Pla => {
-                // Pop Accumulator off Stack
-                self.stkp += 1;
-                self.a = bus.cpu_read(0x0100 + self.stkp as u16);
-                self.set_flag(Flag::Z, self.a == 0x00);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                false
+            _ => {
+                // No Operation (unofficial variants read their operand)
             }
-            Op::Plp => {
-                // Pop Status Register off Stack
-                self.stkp += 1;
-                self.status = bus.cpu_read(0x0100 + self.stkp as u16);
-                self.set_flag(Flag::U, true);
-                false
+        }
+    }
+
+    /// Read-modify-write instructions: returns the value to write back
+    fn rmw_op(&mut self, op: &Op, value: u8) -> u8 {
+        match op {
+            Op::Asl => {
+                // Arithmetic shift left
+                let tmp = value << 1;
+                self.set_flag(Flag::C, value & 0x80 != 0);
+                self.set_nz(tmp);
+                tmp
+            }
+            Op::Lsr => {
+                // Logical Shift Right
+                let tmp = value >> 1;
+                self.set_flag(Flag::C, value & 0x01 != 0);
+                self.set_nz(tmp);
+                tmp
             }
             Op::Rol => {
                 // Rotate Left
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = ((self.fetched as u16) << 1) | (self.get_flag(Flag::C) as u16);
-                self.set_flag(Flag::C, tmp & 0xff00 != 0);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                if instr.addr_mode == AddrMode::Imp {
-                    self.a = (tmp & 0x00ff) as u8;
-                } else {
-                    bus.cpu_write(self.addr_abs, (tmp & 0x00ff) as u8);
-                }
-                false
+                let tmp = (value << 1) | (self.get_flag(Flag::C) as u8);
+                self.set_flag(Flag::C, value & 0x80 != 0);
+                self.set_nz(tmp);
+                tmp
             }
             Op::Ror => {
                 // Rotate Right
-                self.fetch(bus, &instr.addr_mode);
-                let tmp = ((self.fetched as u16) >> 1) | ((self.get_flag(Flag::C) as u16) << 7);
-                self.set_flag(Flag::C, self.fetched & 0x01 != 0);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                if instr.addr_mode == AddrMode::Imp {
-                    self.a = (tmp & 0x00ff) as u8;
-                } else {
-                    bus.cpu_write(self.addr_abs, (tmp & 0x00ff) as u8);
-                }
-                false
+                let tmp = (value >> 1) | ((self.get_flag(Flag::C) as u8) << 7);
+                self.set_flag(Flag::C, value & 0x01 != 0);
+                self.set_nz(tmp);
+                tmp
             }
-            Op::Rti => {
-                // Return from Interrupt
-                self.stkp += 1;
-                self.status = bus.cpu_read(0x0100 + self.stkp as u16);
-                self.status &= !Flag::B.mask();
-                self.status &= !Flag::U.mask();
-
-                self.stkp += 1;
-                self.pc = bus.cpu_read(0x100 + self.stkp as u16) as u16;
-                self.stkp += 1;
-                self.pc |= (bus.cpu_read(0x100 + self.stkp as u16) as u16) << 8;
-                false
+            Op::Dec => {
+                // Decrement Value at Memory Location
+                let tmp = value.wrapping_sub(1);
+                self.set_nz(tmp);
+                tmp
             }
-            Op::Rts => {
-                // Return from Subroutine
-                self.stkp += 1;
-                self.pc = bus.cpu_read(0x100 + self.stkp as u16) as u16;
-                self.stkp += 1;
-                self.pc |= (bus.cpu_read(0x100 + self.stkp as u16) as u16) << 8;
+            Op::Inc => {
+                // Increment Value at Memory Location
+                let tmp = value.wrapping_add(1);
+                self.set_nz(tmp);
+                tmp
+            }
+            Op::Dcp => {
+                // Decrement memory, then Compare accumulator (unofficial)
+                let tmp = value.wrapping_sub(1);
+                self.compare(self.a, tmp);
+                tmp
+            }
+            Op::Isc => {
+                // Increment memory, then Substract with Carry (unofficial)
+                let tmp = value.wrapping_add(1);
+                self.adc(!tmp);
+                tmp
+            }
+            Op::Rla => {
+                // Rotate Left memory, then AND accumulator (unofficial)
+                let tmp = self.rmw_op(&Op::Rol, value);
+                self.a &= tmp;
+                self.set_nz(self.a);
+                tmp
+            }
+            Op::Rra => {
+                // Rotate Right memory, then Add with Carry (unofficial)
+                let tmp = self.rmw_op(&Op::Ror, value);
+                self.adc(tmp);
+                tmp
+            }
+            Op::Slo => {
+                // Arithmetic Shift Left memory, then OR accumulator (unofficial)
+                let tmp = self.rmw_op(&Op::Asl, value);
+                self.a |= tmp;
+                self.set_nz(self.a);
+                tmp
+            }
+            Op::Sre => {
+                // Logical Shift Right memory, then XOR accumulator (unofficial)
+                let tmp = self.rmw_op(&Op::Lsr, value);
+                self.a ^= tmp;
+                self.set_nz(self.a);
+                tmp
+            }
+            _ => value,
+        }
+    }
 
-                self.pc += 1;
-                false
-            }
-            Op::Sbc => {
-                // Substract With Carry
-                self.fetch(bus, &instr.addr_mode);
-
-                let tmp =
-                    u16::from(self.a) + u16::from(!self.fetched) + (self.get_flag(Flag::C) as u16);
-
-                self.set_flag(Flag::C, tmp > 0x00ff);
-                self.set_flag(Flag::Z, tmp & 0x00ff == 0);
-                self.set_flag(
-                    Flag::V,
-                    (self.a ^ self.fetched) & (self.a ^ (tmp as u8)) & 0x0080 != 0,
-                );
-                self.set_flag(Flag::N, tmp & 0x0080 != 0);
-                self.a = tmp as u8;
-                true
-            }
-            Op::Sec => {
-                // Set Carry Flag
-                self.set_flag(Flag::C, true);
-                false
-            }
-            Op::Sed => {
-                // Set Decimal Flag
-                self.set_flag(Flag::D, true);
-                false
-            }
-            Op::Sei => {
-                // Set Interrupt Flag / Enable Interrupts
-                self.set_flag(Flag::I, true);
-                false
-            }
+    /// Store instructions
+    fn store<T: CpuBus>(&mut self, bus: &mut T, op: &Op) {
+        match op {
             Op::Sta => {
                 // Store Accumulator at Address
                 bus.cpu_write(self.addr_abs, self.a);
-                false
             }
             Op::Stx => {
                 // Store X Register at Address
                 bus.cpu_write(self.addr_abs, self.x);
-                false
             }
             Op::Sty => {
                 // Store Y Register at Address
                 bus.cpu_write(self.addr_abs, self.y);
-                false
             }
-            Op::Tax => {
-                // Transfer Accumulator to X Register
-                self.x = self.a;
-                self.set_flag(Flag::Z, self.x == 0);
-                self.set_flag(Flag::N, self.x & 0x80 != 0);
-                false
-            }
-            Op::Tay => {
-                // Transfer Accumulator to Y Register
-                self.y = self.a;
-                self.set_flag(Flag::Z, self.y == 0);
-                self.set_flag(Flag::N, self.y & 0x80 != 0);
-                false
+            Op::Sax => {
+                // Store A & X (unofficial)
+                bus.cpu_write(self.addr_abs, self.a & self.x);
             }
-            Op::Tsx => {
-                // Transfer Stack Pointer to X Register
-                self.x = self.stkp;
-                self.set_flag(Flag::Z, self.x == 0);
-                self.set_flag(Flag::N, self.x & 0x80 != 0);
-                false
+            Op::Ahx => {
+                // Store A & X & (high byte of address + 1) (unofficial, unstable)
+                self.store_and_high(bus, self.a & self.x);
             }
-            Op::Txa => {
-                // Transfer X Register to Accumulator
-                self.a = self.x;
-                self.set_flag(Flag::Z, self.a == 0);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                false
+            Op::Shx => {
+                // Store X & (high byte of address + 1) (unofficial, unstable)
+                self.store_and_high(bus, self.x);
             }
-            Op::Txs => {
-                // Transfer X Register to Stack Pointer
-                self.stkp = self.x;
-                false
+            Op::Shy => {
+                // Store Y & (high byte of address + 1) (unofficial, unstable)
+                self.store_and_high(bus, self.y);
             }
-            Op::Tya => {
-                // Transfer Y Register to Accumulator
-                self.a = self.y;
-                self.set_flag(Flag::Z, self.a == 0);
-                self.set_flag(Flag::N, self.a & 0x80 != 0);
-                false
+            Op::Tas => {
+                // SP = A & X, store SP & (high byte of address + 1) (unofficial, unstable)
+                self.stkp = self.a & self.x;
+                self.store_and_high(bus, self.stkp);
             }
-            Op::Xxx => false, // ignore
+            _ => {}
         }
     }
 
-    fn branch(&mut self) {
-        self.cycles += 1;
-        self.addr_abs = (Wrapping(self.pc) + Wrapping(self.addr_rel)).0;
+    fn set_nz(&mut self, value: u8) {
+        self.set_flag(Flag::Z, value == 0);
+        self.set_flag(Flag::N, value & 0x80 != 0);
+    }
 
-        if (self.addr_abs & 0xff00) != (self.pc & 0xff00) {
-            self.cycles += 1;
-        }
+    /// add with carry to accumulator (substraction: add the complement)
+    fn adc(&mut self, value: u8) {
+        let tmp = self.a as u16 + value as u16 + self.get_flag(Flag::C) as u16;
+        self.set_flag(Flag::C, tmp > 0x00ff);
+        self.set_flag(Flag::Z, tmp & 0x00ff == 0);
+        self.set_flag(Flag::N, tmp & 0x0080 != 0);
+        self.set_flag(
+            Flag::V,
+            !(self.a as u16 ^ value as u16) & (self.a as u16 ^ tmp) & 0x0080 != 0,
+        );
+        self.a = (tmp & 0x00ff) as u8;
+    }
+
+    fn compare(&mut self, register: u8, value: u8) {
+        let tmp = (Wrapping(register as u16) - Wrapping(value as u16)).0;
+        self.set_flag(Flag::C, register >= value);
+        self.set_flag(Flag::Z, tmp & 0x00ff == 0);
+        self.set_flag(Flag::N, tmp & 0x0080 != 0);
+    }
 
-        self.pc = self.addr_abs;
+    /// Store of the unstable SHA / SHX / SHY / TAS group: the value is ANDed
+    /// with the high byte of the base address + 1. On page crossing the
+    /// value also replaces the high byte of the target address.
+    fn store_and_high<T: CpuBus>(&mut self, bus: &mut T, value: u8) {
+        let data = value & ((self.addr_base >> 8) as u8).wrapping_add(1);
+        let addr = if self.page_crossed() {
+            ((data as u16) << 8) | (self.addr_abs & 0x00ff)
+        } else {
+            self.addr_abs
+        };
+        bus.cpu_write(addr, data);
     }
 
     pub fn reset<T: CpuBus>(&mut self, bus: &mut T) {
@@ -683,170 +999,180 @@ impl Cpu {
         self.x = 0x00;
         self.y = 0x00;
         self.stkp = 0xfd;
-        self.status = Flag::U.mask();
+        self.status = Flag::U.mask() | Flag::I.mask();
 
-        self.addr_abs = 0xfffc;
-        let lo = bus.cpu_read(self.addr_abs) as u16;
-        let hi = bus.cpu_read(self.addr_abs + 1) as u16;
+        let lo = bus.cpu_read(0xfffc) as u16;
+        let hi = bus.cpu_read(0xfffd) as u16;
         self.pc = (hi << 8) | lo;
-        // self.pc = 0x8000;
 
-        self.addr_rel = 0x0000;
+        self.addr_base = 0x0000;
         self.addr_abs = 0x0000;
         self.fetched = 0x00;
 
-        self.cycles = 8;
+        self.cycle = 0;
+        self.interrupt = None;
+        self.nmi_pending = false;
+        self.poll = None;
+        self.poll_prev = None;
+        self.jammed = false;
+        self.call_stack.clear();
+        // the reset sequence takes 7 cycles until the first opcode fetch
+        self.stall = 7;
     }
 
-    pub fn irq<T: CpuBus>(&mut self, bus: &mut T) {
-        if !self.get_flag(Flag::I) {
-            bus.cpu_write(0x0100 + self.stkp as u16, ((self.pc >> 8) & 0x00ff) as u8);
-            self.stkp -= 1;
-            bus.cpu_write(0x0100 + self.stkp as u16, (self.pc & 0x00ff) as u8);
-            self.stkp -= 1;
-
-            self.set_flag(Flag::B, false);
-            self.set_flag(Flag::U, true);
-            self.set_flag(Flag::I, true);
-            bus.cpu_write(0x0100 + self.stkp as u16, self.status);
-            self.stkp -= 1;
-
-            self.addr_abs = 0xfffe;
-            let lo = bus.cpu_read(self.addr_abs) as u16;
-            let hi = bus.cpu_read(self.addr_abs + 1) as u16;
-            self.pc = (hi << 8) | lo;
-
-            self.cycles = 7;
+    /// Force a maskable interrupt (IRQ) independent of the IRQ line, call
+    /// between instructions. The interrupt sequence runs on the following
+    /// cycles.
+    pub fn irq(&mut self) {
+        if self.cycle == 0 && !self.get_flag(Flag::I) && !self.jammed {
+            self.interrupt = Some(Interrupt::Irq);
         }
     }
 
-    pub fn nmi<T: CpuBus>(&mut self, bus: &mut T) {
-        bus.cpu_write(0x0100 + self.stkp as u16, ((self.pc >> 8) & 0x00ff) as u8);
-        self.stkp -= 1;
-        bus.cpu_write(0x0100 + self.stkp as u16, (self.pc & 0x00ff) as u8);
-        self.stkp -= 1;
-
-        self.set_flag(Flag::B, false);
-        self.set_flag(Flag::U, true);
-        self.set_flag(Flag::I, true);
-        bus.cpu_write(0x0100 + self.stkp as u16, self.status);
-        self.stkp -= 1;
-
-        self.addr_abs = 0xfffa;
-        let lo = bus.cpu_read(self.addr_abs) as u16;
-        let hi = bus.cpu_read(self.addr_abs + 1) as u16;
-        self.pc = (hi << 8) | lo;
-
-        self.cycles = 8;
+    /// Force a non-maskable interrupt (NMI) independent of the NMI line,
+    /// call between instructions.
+    pub fn nmi(&mut self) {
+        if self.cycle == 0 && !self.jammed {
+            self.interrupt = Some(Interrupt::Nmi);
+        }
     }
 
-    fn fetch<T: CpuBus>(&mut self, bus: &mut T, addr_mode: &AddrMode) -> u8 {
-        if addr_mode != &AddrMode::Imp {
-            self.fetched = bus.cpu_read(self.addr_abs);
-        }
-        self.fetched
-    }
-
-    pub fn disassemble<T: CpuBus>(&self, bus: &T, addr_start: u16, addr_stop: u16) -> Disassembly {
-        let mut addr = addr_start as u32;
-        let mut disasm = BTreeMap::new();
-        while addr <= addr_stop as u32 {
-            let line_addr = addr as u16;
-            let mut s = format!("${addr:04x}: ");
-
-            let opcode = bus.cpu_read_ro(addr as u16);
-            addr += 1;
-
-            let instr = &INSTR_LOOKUP[opcode as usize];
-
-            s.push_str(instr.name);
-            s.push(' ');
-
-            let s_addr = match instr.addr_mode {
-                AddrMode::Imp => String::from("{IMP}"),
-                AddrMode::Imm => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("#${value:02x} {{IMM}}")
-                }
-                AddrMode::Zp0 => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("${value:02x} {{ZP0}}")
-                }
-                AddrMode::Zpx => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("${value:02x}, X {{ZPX}}")
-                }
-                AddrMode::Zpy => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("${value:02x}, Y {{ZPY}}")
-                }
-                AddrMode::Rel => {
-                    let mut value = bus.cpu_read_ro(addr as u16) as u16;
-                    if value & 0x80 > 0 {
-                        value |= 0xff00;
-                    }
-                    addr += 1;
-                    let value_abs = (Wrapping(addr as u16) + Wrapping(value)).0;
-                    format!("${value:02x} [${value_abs:04x}] {{REL}}")
-                }
-                AddrMode::Abs => {
-                    let lo = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let hi = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let value = (hi << 8) | lo;
-                    format!("${value:04x} {{ABS}}")
-                }
-                AddrMode::Abx => {
-                    let lo = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let hi = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let value = (hi << 8) | lo;
-                    format!("${value:04x}, X {{ABX}}")
-                }
-                AddrMode::Aby => {
-                    let lo = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let hi = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let value = (hi << 8) | lo;
-                    format!("${value:04x}, Y {{ABY}}")
-                }
-                AddrMode::Ind => {
-                    let lo = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let hi = bus.cpu_read_ro(addr as u16) as u16;
-                    addr += 1;
-                    let value = (hi << 8) | lo;
-                    format!("(${value:04x}) {{IND}}")
-                }
-                AddrMode::Izx => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("(${value:02x}), X {{IZX}}")
-                }
-                AddrMode::Izy => {
-                    let value = bus.cpu_read_ro(addr as u16);
-                    addr += 1;
-                    format!("(${value:02x}), Y {{IZY}}")
-                }
-            };
-            s.push_str(&s_addr);
+    /// Trace line for the instruction at PC in the nestest.log layout (up to
+    /// the SP column): raw bytes, disassembly with effective address and
+    /// value, registers. Operand addresses are replaced by `label(addr)` if
+    /// it returns a name.
+    pub fn trace<T: CpuBus>(&self, bus: &T, label: impl Fn(u16) -> Option<String>) -> String {
+        let byte = |offset: u16| bus.cpu_read_ro(self.pc.wrapping_add(offset));
+        let read16 =
+            |lo: u16, hi: u16| ((bus.cpu_read_ro(hi) as u16) << 8) | bus.cpu_read_ro(lo) as u16;
+        let instr = &INSTR_LOOKUP[byte(0) as usize];
+        let op8 = byte(1);
+        let op16 = ((byte(2) as u16) << 8) | op8 as u16;
+        let name =
+            |addr: u16, digits: usize| label(addr).unwrap_or_else(|| format!("${addr:0digits$X}"));
+
+        let (len, operand) = match instr.addr_mode {
+            AddrMode::Imp => match instr.op {
+                Op::Asl | Op::Lsr | Op::Rol | Op::Ror => (0, String::from("A")),
+                _ => (0, String::new()),
+            },
+            AddrMode::Imm => (1, format!("#${op8:02X}")),
+            AddrMode::Zp0 => (
+                1,
+                format!(
+                    "{} = {:02X}",
+                    name(op8 as u16, 2),
+                    bus.cpu_read_ro(op8 as u16)
+                ),
+            ),
+            AddrMode::Zpx | AddrMode::Zpy => {
+                let (reg, index) = match instr.addr_mode {
+                    AddrMode::Zpx => ('X', self.x),
+                    _ => ('Y', self.y),
+                };
+                let addr = op8.wrapping_add(index);
+                (
+                    1,
+                    format!(
+                        "{},{reg} @ {addr:02X} = {:02X}",
+                        name(op8 as u16, 2),
+                        bus.cpu_read_ro(addr as u16)
+                    ),
+                )
+            }
+            AddrMode::Rel => {
+                let target = self.pc.wrapping_add(2).wrapping_add(op8 as i8 as u16);
+                (1, name(target, 4))
+            }
+            AddrMode::Abs => match instr.op {
+                Op::Jmp | Op::Jsr => (2, name(op16, 4)),
+                _ => (
+                    2,
+                    format!("{} = {:02X}", name(op16, 4), bus.cpu_read_ro(op16)),
+                ),
+            },
+            AddrMode::Abx | AddrMode::Aby => {
+                let (reg, index) = match instr.addr_mode {
+                    AddrMode::Abx => ('X', self.x),
+                    _ => ('Y', self.y),
+                };
+                let addr = op16.wrapping_add(index as u16);
+                (
+                    2,
+                    format!(
+                        "{},{reg} @ {addr:04X} = {:02X}",
+                        name(op16, 4),
+                        bus.cpu_read_ro(addr)
+                    ),
+                )
+            }
+            AddrMode::Ind => {
+                // page boundary hw bug
+                let target = read16(op16, (op16 & 0xff00) | (op16.wrapping_add(1) & 0x00ff));
+                (2, format!("({}) = {target:04X}", name(op16, 4)))
+            }
+            AddrMode::Izx => {
+                let ptr = op8.wrapping_add(self.x);
+                let addr = read16(ptr as u16, ptr.wrapping_add(1) as u16);
+                (
+                    1,
+                    format!(
+                        "({},X) @ {ptr:02X} = {addr:04X} = {:02X}",
+                        name(op8 as u16, 2),
+                        bus.cpu_read_ro(addr)
+                    ),
+                )
+            }
+            AddrMode::Izy => {
+                let base = read16(op8 as u16, op8.wrapping_add(1) as u16);
+                let addr = base.wrapping_add(self.y as u16);
+                (
+                    1,
+                    format!(
+                        "({}),Y = {base:04X} @ {addr:04X} = {:02X}",
+                        name(op8 as u16, 2),
+                        bus.cpu_read_ro(addr)
+                    ),
+                )
+            }
+        };
+
+        let bytes: Vec<String> = (0..=len).map(|i| format!("{:02X}", byte(i))).collect();
+        let text = format!("{} {}", instr.name, operand);
+        format!(
+            "{:04X}  {:<8} {}{:<31} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X}",
+            self.pc,
+            bytes.join(" "),
+            if instr.unofficial { '*' } else { ' ' },
+            text.trim_end(),
+            self.a,
+            self.x,
+            self.y,
+            self.status,
+            self.stkp,
+        )
+    }
 
-            disasm.insert(line_addr, s);
-        }
-        disasm
+    /// Disassemble the address range `addr_start..=addr_stop`, following the
+    /// code from the current PC, the interrupt vectors and `entry_points`
+    /// (e.g. executed addresses). Addresses are named by `label`.
+    pub fn disassemble<T: CpuBus>(
+        &self,
+        bus: &T,
+        addr_start: u16,
+        addr_stop: u16,
+        entry_points: &[u16],
+        label: impl Fn(u16) -> Option<String>,
+    ) -> Disassembly {
+        let vector =
+            |addr: u16| ((bus.cpu_read_ro(addr + 1) as u16) << 8) | bus.cpu_read_ro(addr) as u16;
+        let mut entries = vec![self.pc, vector(0xfffa), vector(0xfffc), vector(0xfffe)];
+        entries.extend_from_slice(entry_points);
+        disasm::disassemble(bus, addr_start, addr_stop, &entries, label)
     }
 }
 
-pub type Disassembly = BTreeMap<u16, String>;
-
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Flag {
     C, // Carry bit
     Z, // Zero
@@ -890,6 +1216,297 @@ impl Flag {
 mod tests {
     use super::*;
 
+    struct RamBus {
+        ram: Vec<u8>,
+        writes: Vec<(u16, u8)>,
+        irq: bool,
+        nmi: bool,
+    }
+
+    impl CpuBus for RamBus {
+        fn cpu_write(&mut self, addr: u16, data: u8) {
+            self.ram[addr as usize] = data;
+            self.writes.push((addr, data));
+        }
+
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        fn cpu_read_ro(&self, addr: u16) -> u8 {
+            self.ram[addr as usize]
+        }
+
+        fn irq_line(&self) -> bool {
+            self.irq
+        }
+
+        fn nmi_line(&self) -> bool {
+            self.nmi
+        }
+    }
+
+    /// run `program` at $0200 until PC leaves it
+    fn run(program: &[u8]) -> (Cpu, RamBus) {
+        let mut bus = RamBus {
+            ram: vec![0; 0x10000],
+            writes: Vec::new(),
+            irq: false,
+            nmi: false,
+        };
+        bus.ram[0x0200..0x0200 + program.len()].copy_from_slice(program);
+        bus.ram[0xfffc] = 0x00;
+        bus.ram[0xfffd] = 0x02;
+
+        let mut cpu = Cpu::new();
+        cpu.reset(&mut bus);
+        while cpu.pc < 0x0200 + program.len() as u16 || !cpu.complete() {
+            cpu.clock(&mut bus);
+        }
+        (cpu, bus)
+    }
+
+    #[test]
+    fn test_unofficial_opcodes() {
+        let (cpu, bus) = run(&[
+            0xa9, 0x5a, // LDA #$5a
+            0x85, 0x10, // STA $10
+            0xa7, 0x10, // LAX $10
+            0xc7, 0x10, // DCP $10 -> $59, A > M
+            0x87, 0x11, // SAX $11
+            0xe7, 0x11, // ISC $11 -> $5b, A = $5a - $5b (C set by DCP)
+        ]);
+        assert_eq!(bus.ram[0x10], 0x59);
+        assert_eq!(bus.ram[0x11], 0x5b);
+        assert_eq!(cpu.x, 0x5a);
+        assert_eq!(cpu.a, 0xff);
+        assert!(!cpu.get_flag(Flag::C));
+        assert!(cpu.get_flag(Flag::N));
+
+        let (cpu, _) = run(&[
+            0x38, // SEC
+            0xa9, 0xff, // LDA #$ff
+            0x6b, 0xc0, // ARR #$c0 -> A = $e0, C = 1, V = 0
+        ]);
+        assert_eq!(cpu.a, 0xe0);
+        assert!(cpu.get_flag(Flag::C));
+        assert!(!cpu.get_flag(Flag::V));
+    }
+
+    #[test]
+    fn test_cycle_counts() {
+        for (opcode, instr) in INSTR_LOOKUP.iter().enumerate() {
+            if matches!(instr.addr_mode, AddrMode::Rel) || matches!(instr.op, Op::Jam) {
+                continue;
+            }
+            // operands 0: no page crossing
+            let (mut cpu, mut bus) = run(&[]);
+            bus.ram[0x0200] = opcode as u8;
+            let mut cycles = 0;
+            loop {
+                cpu.clock(&mut bus);
+                cycles += 1;
+                if cpu.complete() {
+                    break;
+                }
+            }
+            assert_eq!(cycles, instr.cycles, "opcode ${opcode:02x}");
+        }
+
+        // page crossing read: LDA $02ff,X
+        let (cpu, _) = run(&[0xa2, 0x01, 0xbd, 0xff, 0x02]);
+        assert_eq!(cpu.x, 0x01);
+    }
+
+    #[test]
+    fn test_dummy_writes() {
+        // read-modify-write writes the unmodified value first
+        let (_, bus) = run(&[
+            0xa9, 0x41, // LDA #$41
+            0x8d, 0x00, 0x03, // STA $0300
+            0xee, 0x00, 0x03, // INC $0300
+        ]);
+        assert_eq!(bus.writes, [(0x0300, 0x41), (0x0300, 0x41), (0x0300, 0x42)]);
+
+        // BRK pushes the address after the padding byte
+        let (cpu, bus) = run(&[
+            0xa9, 0x03, // LDA #$03
+            0x8d, 0xff, 0xff, // STA $ffff (IRQ vector $0300)
+            0x00, 0xea, // BRK, padding
+        ]);
+        assert_eq!(cpu.pc, 0x0300);
+        assert_eq!(bus.ram[0x01fd], 0x02);
+        assert_eq!(bus.ram[0x01fc], 0x07);
+        assert_eq!(bus.ram[0x01fb] & 0x30, 0x30);
+    }
+
+    /// run one instruction (or interrupt sequence)
+    fn step(cpu: &mut Cpu, bus: &mut RamBus) {
+        loop {
+            cpu.clock(bus);
+            if cpu.complete() {
+                break;
+            }
+        }
+    }
+
+    /// bus with `program` at $0200, IRQ handler at $0300, NMI handler at $0400
+    fn interrupt_bus(program: &[u8]) -> (Cpu, RamBus) {
+        let (cpu, mut bus) = run(&[]);
+        bus.ram[0x0200..0x0200 + program.len()].copy_from_slice(program);
+        bus.ram[0xfffb] = 0x04;
+        bus.ram[0xffff] = 0x03;
+        bus.ram[0x0300] = 0xea; // NOP
+        bus.ram[0x0400] = 0xea; // NOP
+        (cpu, bus)
+    }
+
+    #[test]
+    fn test_interrupt_latency() {
+        let (mut cpu, mut bus) = interrupt_bus(&[
+            0x58, // CLI
+            0x78, // SEI
+            0xea, // NOP
+        ]);
+        bus.irq = true;
+
+        // CLI: IRQ is polled before the flag is cleared
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0201);
+        // SEI: IRQ is polled before the flag is set
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0202);
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0300);
+        assert_eq!(bus.ram[0x01fc], 0x02);
+        assert_ne!(bus.ram[0x01fb] & Flag::I.mask(), 0);
+    }
+
+    #[test]
+    fn test_interrupt_hijack() {
+        // NMI during BRK: NMI vector, B flag pushed
+        let (mut cpu, mut bus) = interrupt_bus(&[
+            0x00, 0xea, // BRK, padding
+        ]);
+        cpu.clock(&mut bus);
+        cpu.clock(&mut bus);
+        bus.nmi = true;
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0400);
+        assert_ne!(bus.ram[0x01fb] & Flag::B.mask(), 0);
+
+        // NMI is edge-triggered
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0401);
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let (mut cpu, mut bus) = interrupt_bus(&[
+            0x20, 0x10, 0x02, // $0200: JSR $0210
+        ]);
+        bus.ram[0x0210..0x0213].copy_from_slice(&[0x20, 0x20, 0x02]); // JSR $0220
+        bus.ram[0x0220..0x0223].copy_from_slice(&[0x68, 0x68, 0x60]); // PLA, PLA, RTS
+        bus.ram[0x0400] = 0x40; // RTI
+
+        step(&mut cpu, &mut bus);
+        bus.nmi = true;
+        step(&mut cpu, &mut bus);
+        step(&mut cpu, &mut bus);
+        let frame = |kind, from, target, sp| CallFrame {
+            kind,
+            from,
+            target,
+            sp,
+        };
+        assert_eq!(
+            cpu.call_stack().frames(),
+            [
+                frame(CallKind::Jsr, 0x0200, 0x0210, 0xfb),
+                frame(CallKind::Jsr, 0x0210, 0x0220, 0xf9),
+                frame(CallKind::Nmi, 0x0220, 0x0400, 0xf6),
+            ]
+        );
+
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.call_stack().frames().len(), 2);
+        assert_eq!(cpu.call_stack().mismatch(), None);
+
+        // RTS with the inner return address dropped
+        step(&mut cpu, &mut bus);
+        step(&mut cpu, &mut bus);
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0203);
+        assert_eq!(cpu.call_stack().frames(), []);
+        assert_eq!(cpu.call_stack().mismatch(), Some(0x0222));
+    }
+
+    #[test]
+    fn test_branch_delay() {
+        let (mut cpu, mut bus) = interrupt_bus(&[
+            0xd0, 0x00, // BNE +0 (taken)
+            0xea, // NOP
+        ]);
+        // NMI edge on the second cycle of the branch
+        cpu.clock(&mut bus);
+        bus.nmi = true;
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0202);
+        // a taken branch without page crossing delays the NMI
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0203);
+        step(&mut cpu, &mut bus);
+        assert_eq!(cpu.pc, 0x0400);
+    }
+
+    #[test]
+    fn test_trace() {
+        let (mut cpu, mut bus) = run(&[]);
+        bus.ram[0x0200..0x0203].copy_from_slice(&[0x4c, 0xf5, 0xc5]);
+        assert_eq!(
+            cpu.trace(&bus, |_| None),
+            "0200  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD"
+        );
+
+        bus.ram[0x0200..0x0202].copy_from_slice(&[0xb1, 0x10]);
+        bus.ram[0x0010..0x0012].copy_from_slice(&[0xff, 0x02]);
+        bus.ram[0x0300] = 0x5a;
+        cpu.y = 0x01;
+        assert_eq!(
+            cpu.trace(&bus, |_| None),
+            "0200  B1 10     LDA ($10),Y = 02FF @ 0300 = 5A  A:00 X:00 Y:01 P:24 SP:FD"
+        );
+
+        bus.ram[0x0200..0x0202].copy_from_slice(&[0x04, 0x10]);
+        assert_eq!(
+            cpu.trace(&bus, |_| None),
+            "0200  04 10    *NOP $10 = FF                    A:00 X:00 Y:01 P:24 SP:FD"
+        );
+    }
+
+    #[test]
+    fn test_jam() {
+        let (mut cpu, mut bus) = run(&[
+            0xe8, // INX
+        ]);
+        bus.ram[0x0201] = 0x02; // JAM
+        for _ in 0..10 {
+            cpu.clock(&mut bus);
+        }
+        assert!(cpu.jammed());
+        assert!(cpu.complete());
+        assert_eq!(cpu.pc, 0x0201);
+        assert_eq!(cpu.x, 0x01);
+
+        // interrupts are ignored, only reset recovers
+        cpu.nmi();
+        cpu.clock(&mut bus);
+        assert_eq!(cpu.pc, 0x0201);
+        cpu.reset(&mut bus);
+        assert!(!cpu.jammed());
+        assert_eq!(cpu.pc, 0x0200);
+    }
+
     #[test]
     fn test_flags() {
         assert_eq!(!Flag::B.mask(), 0b11101111);
@@ -0,0 +1,99 @@
+use std::fmt;
+
+/// How a call stack frame was entered
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    Jsr,
+    Brk,
+    Nmi,
+    Irq,
+}
+
+/// Subroutine call or interrupt handler in progress
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CallFrame {
+    pub kind: CallKind,
+    /// address of the JSR / BRK instruction, interrupted PC for NMI / IRQ
+    pub from: u16,
+    /// subroutine / handler address
+    pub target: u16,
+    /// stack pointer after pushing the return address (and status)
+    pub sp: u8,
+}
+
+/// Shadow call stack, following JSR / RTS, BRK / NMI / IRQ and RTI.
+///
+/// Returns unwind all frames whose return address has been pulled from the
+/// stack. A return that does not match the innermost frame (e.g. RTS as an
+/// indirect jump, return addresses dropped with PLA / TXS) is recorded as a
+/// mismatch.
+#[derive(Clone, Debug, Default)]
+pub struct CallStack {
+    frames: Vec<CallFrame>,
+    mismatch: Option<u16>,
+    /// address of the current instruction / interrupted PC
+    instr_addr: u16,
+}
+
+/// deeper nesting is runaway recursion, drop the outermost frames
+const MAX_FRAMES: usize = 256;
+
+impl CallStack {
+    /// frames, outermost first
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
+    /// address of the last RTS / RTI not matching the innermost frame
+    pub fn mismatch(&self) -> Option<u16> {
+        self.mismatch
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.frames.clear();
+        self.mismatch = None;
+    }
+
+    /// first cycle of an instruction or interrupt sequence at `addr`
+    pub(super) fn start(&mut self, addr: u16) {
+        self.instr_addr = addr;
+    }
+
+    pub(super) fn call(&mut self, kind: CallKind, target: u16, sp: u8) {
+        if self.frames.len() == MAX_FRAMES {
+            self.frames.remove(0);
+        }
+        self.frames.push(CallFrame {
+            kind,
+            from: self.instr_addr,
+            target,
+            sp,
+        });
+    }
+
+    /// RTS (`rti == false`) or RTI, `sp` after pulling the return address
+    pub(super) fn ret(&mut self, rti: bool, sp: u8) {
+        let sp_before = sp.wrapping_sub(if rti { 3 } else { 2 });
+        // without frames the stack is unknown (e.g. after loading a save state)
+        if let Some(frame) = self.frames.last()
+            && (frame.sp != sp_before || (frame.kind == CallKind::Jsr) == rti)
+        {
+            self.mismatch = Some(self.instr_addr);
+        }
+        while self.frames.last().is_some_and(|frame| frame.sp < sp) {
+            self.frames.pop();
+        }
+    }
+}
+
+impl fmt::Display for CallKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            CallKind::Jsr => "jsr",
+            CallKind::Brk => "brk",
+            CallKind::Nmi => "nmi",
+            CallKind::Irq => "irq",
+        };
+        write!(f, "{name}")
+    }
+}
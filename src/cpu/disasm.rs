@@ -0,0 +1,267 @@
+use super::instr::{AddrMode, INSTR_LOOKUP, Op};
+use crate::bus::CpuBus;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Disassembled memory, by address
+pub type Disassembly = BTreeMap<u16, DisasmEntry>;
+
+/// One disassembled instruction, or a data byte not reached as code
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DisasmEntry {
+    pub addr: u16,
+    /// opcode and operand bytes
+    pub bytes: Vec<u8>,
+    /// instruction mnemonic (`.byte` for data)
+    pub mnemonic: &'static str,
+    pub unofficial: bool,
+    /// addressing mode, `None` for data
+    pub addr_mode: Option<AddrMode>,
+    /// raw operand value (zero page address, immediate value, absolute
+    /// address, relative offset)
+    pub operand: Option<u16>,
+    /// branch / jump / subroutine target
+    pub target: Option<u16>,
+    /// symbol name of `addr`
+    pub label: Option<String>,
+    /// symbol name of the operand address / target
+    pub operand_label: Option<String>,
+    /// execution does not continue with the next instruction
+    flow_ends: bool,
+}
+
+impl DisasmEntry {
+    /// decode the instruction at `addr`
+    pub fn decode<T: CpuBus>(bus: &T, addr: u16) -> DisasmEntry {
+        let opcode = bus.cpu_read_ro(addr);
+        let instr = &INSTR_LOOKUP[opcode as usize];
+        let len = match instr.addr_mode {
+            AddrMode::Imp => 1,
+            AddrMode::Abs | AddrMode::Abx | AddrMode::Aby | AddrMode::Ind => 3,
+            _ => 2,
+        };
+        let bytes: Vec<u8> = (0..len)
+            .map(|i| bus.cpu_read_ro(addr.wrapping_add(i)))
+            .collect();
+
+        let operand = match len {
+            2 => Some(bytes[1] as u16),
+            3 => Some(((bytes[2] as u16) << 8) | bytes[1] as u16),
+            _ => None,
+        };
+        let target = match (&instr.op, instr.addr_mode) {
+            (_, AddrMode::Rel) => Some(addr.wrapping_add(2).wrapping_add(bytes[1] as i8 as u16)),
+            (Op::Jmp | Op::Jsr, AddrMode::Abs) => operand,
+            _ => None,
+        };
+
+        DisasmEntry {
+            addr,
+            bytes,
+            mnemonic: instr.name,
+            unofficial: instr.unofficial,
+            addr_mode: Some(instr.addr_mode),
+            operand,
+            target,
+            label: None,
+            operand_label: None,
+            flow_ends: matches!(instr.op, Op::Jmp | Op::Rts | Op::Rti | Op::Brk | Op::Jam),
+        }
+    }
+
+    /// data byte at `addr`
+    pub fn data<T: CpuBus>(bus: &T, addr: u16) -> DisasmEntry {
+        let value = bus.cpu_read_ro(addr);
+        DisasmEntry {
+            addr,
+            bytes: vec![value],
+            mnemonic: ".byte",
+            unofficial: false,
+            addr_mode: None,
+            operand: Some(value as u16),
+            target: None,
+            label: None,
+            operand_label: None,
+            flow_ends: true,
+        }
+    }
+
+    /// address referenced by the operand
+    pub fn operand_addr(&self) -> Option<u16> {
+        match self.addr_mode? {
+            AddrMode::Imp | AddrMode::Imm => None,
+            AddrMode::Rel => self.target,
+            _ => self.operand,
+        }
+    }
+
+    /// ca65 source line, addresses are replaced by `label(addr)` if it
+    /// returns a name
+    pub fn to_asm(&self, label: impl Fn(u16) -> Option<String>) -> String {
+        let op = self.operand.unwrap_or(0);
+        let zp = |value: u16| label(value).unwrap_or_else(|| format!("${value:02x}"));
+        let addr = |value: u16| label(value).unwrap_or_else(|| format!("${value:04x}"));
+        // absolute addressing of a zero page address needs the `a:` prefix
+        // to assemble to the same bytes (JMP / JSR have no zero page mode)
+        let abs = |value: u16| match self.mnemonic {
+            "JMP" | "JSR" => addr(value),
+            _ if value < 0x100 => format!("a:{}", addr(value)),
+            _ => addr(value),
+        };
+
+        let operand = match self.addr_mode {
+            None => format!("${op:02x}"),
+            Some(AddrMode::Imp) => match self.mnemonic {
+                "ASL" | "LSR" | "ROL" | "ROR" => String::from("A"),
+                _ => String::new(),
+            },
+            Some(AddrMode::Imm) => format!("#${op:02x}"),
+            Some(AddrMode::Zp0) => zp(op),
+            Some(AddrMode::Zpx) => format!("{},X", zp(op)),
+            Some(AddrMode::Zpy) => format!("{},Y", zp(op)),
+            Some(AddrMode::Rel) => addr(self.target.unwrap_or(0)),
+            Some(AddrMode::Abs) => abs(op),
+            Some(AddrMode::Abx) => format!("{},X", abs(op)),
+            Some(AddrMode::Aby) => format!("{},Y", abs(op)),
+            Some(AddrMode::Ind) => format!("({})", addr(op)),
+            Some(AddrMode::Izx) => format!("({},X)", zp(op)),
+            Some(AddrMode::Izy) => format!("({}),Y", zp(op)),
+        };
+
+        if operand.is_empty() {
+            String::from(self.mnemonic)
+        } else {
+            format!("{} {}", self.mnemonic, operand)
+        }
+    }
+}
+
+impl fmt::Display for DisasmEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operand_addr = self.operand_addr();
+        let label = |addr| {
+            (Some(addr) == operand_addr)
+                .then(|| self.operand_label.clone())
+                .flatten()
+        };
+        write!(f, "{}", self.to_asm(label))
+    }
+}
+
+/// Disassemble the address range `addr_start..=addr_stop` by following the
+/// code flow from `entry_points` (branches, jumps and subroutine calls are
+/// followed, indirect jumps are not). Bytes not reached as code are data.
+/// Addresses are named by `label`.
+pub fn disassemble<T: CpuBus>(
+    bus: &T,
+    addr_start: u16,
+    addr_stop: u16,
+    entry_points: &[u16],
+    label: impl Fn(u16) -> Option<String>,
+) -> Disassembly {
+    let in_range = |addr: u16| (addr_start..=addr_stop).contains(&addr);
+    let mut covered = vec![false; (addr_stop.wrapping_sub(addr_start)) as usize + 1];
+    let mut disasm = BTreeMap::new();
+
+    let mut pending: Vec<u16> = entry_points.to_vec();
+    while let Some(addr) = pending.pop() {
+        if !in_range(addr) || covered[(addr - addr_start) as usize] {
+            continue;
+        }
+        let entry = DisasmEntry::decode(bus, addr);
+
+        // don't decode instructions overlapping each other
+        let addrs: Vec<u16> = (0..entry.bytes.len() as u16)
+            .map(|i| addr.wrapping_add(i))
+            .filter(|&a| in_range(a))
+            .collect();
+        if addrs.iter().any(|&a| covered[(a - addr_start) as usize]) {
+            continue;
+        }
+        for a in addrs {
+            covered[(a - addr_start) as usize] = true;
+        }
+
+        if let Some(target) = entry.target {
+            pending.push(target);
+        }
+        if !entry.flow_ends {
+            pending.push(addr.wrapping_add(entry.bytes.len() as u16));
+        }
+        disasm.insert(addr, entry);
+    }
+
+    for addr in addr_start..=addr_stop {
+        if !covered[(addr - addr_start) as usize] {
+            disasm.insert(addr, DisasmEntry::data(bus, addr));
+        }
+    }
+
+    for entry in disasm.values_mut() {
+        entry.label = label(entry.addr);
+        entry.operand_label = entry.operand_addr().and_then(&label);
+    }
+    disasm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Mem(Vec<u8>);
+
+    impl CpuBus for Mem {
+        fn cpu_write(&mut self, _addr: u16, _data: u8) {}
+
+        fn cpu_read(&mut self, addr: u16) -> u8 {
+            self.cpu_read_ro(addr)
+        }
+
+        fn cpu_read_ro(&self, addr: u16) -> u8 {
+            self.0.get(addr as usize).copied().unwrap_or(0)
+        }
+    }
+
+    #[test]
+    fn test_follow_code() {
+        let mem = Mem(vec![
+            0xa9, 0x10, // $00: LDA #$10
+            0xf0, 0x03, // $02: BEQ $07
+            0x4c, 0x0c, 0x00, // $04: JMP $000c
+            0x0a, // $07: ASL A
+            0x60, // $08: RTS
+            0xff, 0xff, 0xff, // $09: data
+            0xb5, 0x80, // $0c: LDA $80,X
+            0xbd, 0x34, 0x00, // $0e: LDA a:$0034,X
+            0x91, 0x02, // $11: STA ($02),Y
+            0x6c, 0x00, 0x03, // $13: JMP ($0300)
+        ]);
+        let label = |addr: u16| (addr == 0x0007).then(|| String::from("skip"));
+        let disasm = disassemble(&mem, 0x0000, 0x0015, &[0x0000], label);
+        let lines: Vec<String> = disasm
+            .values()
+            .map(|e| format!("{:02x} {e}", e.addr))
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                "00 LDA #$10",
+                "02 BEQ skip",
+                "04 JMP $000c",
+                "07 ASL A",
+                "08 RTS",
+                "09 .byte $ff",
+                "0a .byte $ff",
+                "0b .byte $ff",
+                "0c LDA $80,X",
+                "0e LDA a:$0034,X",
+                "11 STA ($02),Y",
+                "13 JMP ($0300)",
+            ]
+        );
+
+        assert_eq!(disasm[&0x0007].label.as_deref(), Some("skip"));
+        assert_eq!(disasm[&0x0002].to_asm(|_| None), "BEQ $0007");
+        assert_eq!(disasm[&0x0004].target, Some(0x000c));
+    }
+}
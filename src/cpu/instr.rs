@@ -1,4 +1,4 @@
-#[derive(PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum AddrMode {
     Imp,
     Imm,
@@ -71,14 +71,71 @@ pub enum Op {
     Txa,
     Txs,
     Tya,
-    Xxx,
+    // unofficial
+    Ahx,
+    Alr,
+    Anc,
+    Arr,
+    Axs,
+    Dcp,
+    Isc,
+    Jam,
+    Las,
+    Lax,
+    Lxa,
+    Rla,
+    Rra,
+    Sax,
+    Shx,
+    Shy,
+    Slo,
+    Sre,
+    Tas,
+    Xaa,
+}
+
+/// Memory access pattern of an instruction's operand
+#[derive(PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    /// read-modify-write: read, write back unmodified, write result
+    Rmw,
+}
+
+impl Op {
+    pub fn access(&self) -> Access {
+        match self {
+            Op::Sta | Op::Stx | Op::Sty | Op::Sax | Op::Ahx | Op::Shx | Op::Shy | Op::Tas => {
+                Access::Write
+            }
+            Op::Asl
+            | Op::Lsr
+            | Op::Rol
+            | Op::Ror
+            | Op::Inc
+            | Op::Dec
+            | Op::Dcp
+            | Op::Isc
+            | Op::Rla
+            | Op::Rra
+            | Op::Slo
+            | Op::Sre => Access::Rmw,
+            _ => Access::Read,
+        }
+    }
 }
 
 pub struct Instr<'a> {
     pub name: &'a str,
     pub op: Op,
     pub addr_mode: AddrMode,
+    /// base cycle count, without page crossing / branch penalties (the
+    /// CPU core is checked against it in tests)
+    #[cfg_attr(not(test), allow(dead_code))]
     pub cycles: u8,
+    /// not part of the documented 6502 instruction set
+    pub unofficial: bool,
 }
 
 impl Instr<'_> {
@@ -88,6 +145,17 @@ impl Instr<'_> {
             op,
             addr_mode,
             cycles,
+            unofficial: false,
+        }
+    }
+
+    const fn unofficial(name: &str, op: Op, addr_mode: AddrMode, cycles: u8) -> Instr<'_> {
+        Instr {
+            name,
+            op,
+            addr_mode,
+            cycles,
+            unofficial: true,
         }
     }
 }
@@ -96,273 +164,273 @@ pub const INSTR_LOOKUP: [Instr; 256] = [
     // opcodes 0x00..=0x0f
     Instr::new("BRK", Op::Brk, AddrMode::Imm, 7),
     Instr::new("ORA", Op::Ora, AddrMode::Izx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 3),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Izx, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zp0, 3),
     Instr::new("ORA", Op::Ora, AddrMode::Zp0, 3),
     Instr::new("ASL", Op::Asl, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Zp0, 5),
     Instr::new("PHP", Op::Php, AddrMode::Imp, 3),
     Instr::new("ORA", Op::Ora, AddrMode::Imm, 2),
     Instr::new("ASL", Op::Asl, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("ANC", Op::Anc, AddrMode::Imm, 2),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abs, 4),
     Instr::new("ORA", Op::Ora, AddrMode::Abs, 4),
     Instr::new("ASL", Op::Asl, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Abs, 6),
     // opcodes 0x10..=0x1f
     Instr::new("BPL", Op::Bpl, AddrMode::Rel, 2),
     Instr::new("ORA", Op::Ora, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("ORA", Op::Ora, AddrMode::Zpx, 4),
     Instr::new("ASL", Op::Asl, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Zpx, 6),
     Instr::new("CLC", Op::Clc, AddrMode::Imp, 2),
     Instr::new("ORA", Op::Ora, AddrMode::Aby, 4),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("ORA", Op::Ora, AddrMode::Abx, 4),
     Instr::new("ASL", Op::Asl, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("SLO", Op::Slo, AddrMode::Abx, 7),
     // opcodes 0x20..=0x2f
     Instr::new("JSR", Op::Jsr, AddrMode::Abs, 6),
     Instr::new("AND", Op::And, AddrMode::Izx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Izx, 8),
     Instr::new("BIT", Op::Bit, AddrMode::Zp0, 3),
     Instr::new("AND", Op::And, AddrMode::Zp0, 3),
     Instr::new("ROL", Op::Rol, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Zp0, 5),
     Instr::new("PLP", Op::Plp, AddrMode::Imp, 4),
     Instr::new("AND", Op::And, AddrMode::Imm, 2),
     Instr::new("ROL", Op::Rol, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("ANC", Op::Anc, AddrMode::Imm, 2),
     Instr::new("BIT", Op::Bit, AddrMode::Abs, 4),
     Instr::new("AND", Op::And, AddrMode::Abs, 4),
     Instr::new("ROL", Op::Rol, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Abs, 6),
     // opcodes 0x30..=0x3f
     Instr::new("BMI", Op::Bmi, AddrMode::Rel, 2),
     Instr::new("AND", Op::And, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("AND", Op::And, AddrMode::Zpx, 4),
     Instr::new("ROL", Op::Rol, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Zpx, 6),
     Instr::new("SEC", Op::Sec, AddrMode::Imp, 2),
     Instr::new("AND", Op::And, AddrMode::Aby, 4),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("AND", Op::And, AddrMode::Abx, 4),
     Instr::new("ROL", Op::Rol, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("RLA", Op::Rla, AddrMode::Abx, 7),
     // opcodes 0x40..=0x4f
     Instr::new("RTI", Op::Rti, AddrMode::Imp, 6),
     Instr::new("EOR", Op::Eor, AddrMode::Izx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 3),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Izx, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zp0, 3),
     Instr::new("EOR", Op::Eor, AddrMode::Zp0, 3),
     Instr::new("LSR", Op::Lsr, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Zp0, 5),
     Instr::new("PHA", Op::Pha, AddrMode::Imp, 3),
     Instr::new("EOR", Op::Eor, AddrMode::Imm, 2),
     Instr::new("LSR", Op::Lsr, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("ALR", Op::Alr, AddrMode::Imm, 2),
     Instr::new("JMP", Op::Jmp, AddrMode::Abs, 3),
     Instr::new("EOR", Op::Eor, AddrMode::Abs, 4),
     Instr::new("LSR", Op::Lsr, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Abs, 6),
     // opcodes 0x50..=0x5f
     Instr::new("BVC", Op::Bvc, AddrMode::Rel, 2),
     Instr::new("EOR", Op::Eor, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("EOR", Op::Eor, AddrMode::Zpx, 4),
     Instr::new("LSR", Op::Lsr, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Zpx, 6),
     Instr::new("CLI", Op::Cli, AddrMode::Imp, 2),
     Instr::new("EOR", Op::Eor, AddrMode::Aby, 4),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("EOR", Op::Eor, AddrMode::Abx, 4),
     Instr::new("LSR", Op::Lsr, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("SRE", Op::Sre, AddrMode::Abx, 7),
     // opcodes 0x60..=0x6f
     Instr::new("RTS", Op::Rts, AddrMode::Imp, 6),
     Instr::new("ADC", Op::Adc, AddrMode::Izx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 3),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Izx, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zp0, 3),
     Instr::new("ADC", Op::Adc, AddrMode::Zp0, 3),
     Instr::new("ROR", Op::Ror, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Zp0, 5),
     Instr::new("PLA", Op::Pla, AddrMode::Imp, 4),
     Instr::new("ADC", Op::Adc, AddrMode::Imm, 2),
     Instr::new("ROR", Op::Ror, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("ARR", Op::Arr, AddrMode::Imm, 2),
     Instr::new("JMP", Op::Jmp, AddrMode::Ind, 5),
     Instr::new("ADC", Op::Adc, AddrMode::Abs, 4),
     Instr::new("ROR", Op::Ror, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Abs, 6),
     // opcodes 0x70..=0x7f
     Instr::new("BVS", Op::Bvs, AddrMode::Rel, 2),
     Instr::new("ADC", Op::Adc, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("ADC", Op::Adc, AddrMode::Zpx, 4),
     Instr::new("ROR", Op::Ror, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Zpx, 6),
     Instr::new("SEI", Op::Sei, AddrMode::Imp, 2),
     Instr::new("ADC", Op::Adc, AddrMode::Aby, 4),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("ADC", Op::Adc, AddrMode::Abx, 4),
     Instr::new("ROR", Op::Ror, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("RRA", Op::Rra, AddrMode::Abx, 7),
     // opcodes 0x80..=0x8f
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imm, 2),
     Instr::new("STA", Op::Sta, AddrMode::Izx, 6),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imm, 2),
+    Instr::unofficial("SAX", Op::Sax, AddrMode::Izx, 6),
     Instr::new("STY", Op::Sty, AddrMode::Zp0, 3),
     Instr::new("STA", Op::Sta, AddrMode::Zp0, 3),
     Instr::new("STX", Op::Stx, AddrMode::Zp0, 3),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 3),
+    Instr::unofficial("SAX", Op::Sax, AddrMode::Zp0, 3),
     Instr::new("DEY", Op::Dey, AddrMode::Imp, 2),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imm, 2),
     Instr::new("TXA", Op::Txa, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("XAA", Op::Xaa, AddrMode::Imm, 2),
     Instr::new("STY", Op::Sty, AddrMode::Abs, 4),
     Instr::new("STA", Op::Sta, AddrMode::Abs, 4),
     Instr::new("STX", Op::Stx, AddrMode::Abs, 4),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("SAX", Op::Sax, AddrMode::Abs, 4),
     // opcodes 0x90..=0x9f
     Instr::new("BCC", Op::Bcc, AddrMode::Rel, 2),
     Instr::new("STA", Op::Sta, AddrMode::Izy, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("AHX", Op::Ahx, AddrMode::Izy, 6),
     Instr::new("STY", Op::Sty, AddrMode::Zpx, 4),
     Instr::new("STA", Op::Sta, AddrMode::Zpx, 4),
     Instr::new("STX", Op::Stx, AddrMode::Zpy, 4),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("SAX", Op::Sax, AddrMode::Zpy, 4),
     Instr::new("TYA", Op::Tya, AddrMode::Imp, 2),
     Instr::new("STA", Op::Sta, AddrMode::Aby, 5),
     Instr::new("TXS", Op::Txs, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 5),
+    Instr::unofficial("TAS", Op::Tas, AddrMode::Aby, 5),
+    Instr::unofficial("SHY", Op::Shy, AddrMode::Abx, 5),
     Instr::new("STA", Op::Sta, AddrMode::Abx, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("SHX", Op::Shx, AddrMode::Aby, 5),
+    Instr::unofficial("AHX", Op::Ahx, AddrMode::Aby, 5),
     // opcodes 0xa0..=0xaf
     Instr::new("LDY", Op::Ldy, AddrMode::Imm, 2),
     Instr::new("LDA", Op::Lda, AddrMode::Izx, 6),
     Instr::new("LDX", Op::Ldx, AddrMode::Imm, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Izx, 6),
     Instr::new("LDY", Op::Ldy, AddrMode::Zp0, 3),
     Instr::new("LDA", Op::Lda, AddrMode::Zp0, 3),
     Instr::new("LDX", Op::Ldx, AddrMode::Zp0, 3),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 3),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Zp0, 3),
     Instr::new("TAY", Op::Tay, AddrMode::Imp, 2),
     Instr::new("LDA", Op::Lda, AddrMode::Imm, 2),
     Instr::new("TAX", Op::Tax, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("LXA", Op::Lxa, AddrMode::Imm, 2),
     Instr::new("LDY", Op::Ldy, AddrMode::Abs, 4),
     Instr::new("LDA", Op::Lda, AddrMode::Abs, 4),
     Instr::new("LDX", Op::Ldx, AddrMode::Abs, 4),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Abs, 4),
     // opcodes 0xb0..=0xbf
     Instr::new("BCS", Op::Bcs, AddrMode::Rel, 2),
     Instr::new("LDA", Op::Lda, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Izy, 5),
     Instr::new("LDY", Op::Ldy, AddrMode::Zpx, 4),
     Instr::new("LDA", Op::Lda, AddrMode::Zpx, 4),
     Instr::new("LDX", Op::Ldx, AddrMode::Zpy, 4),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Zpy, 4),
     Instr::new("CLV", Op::Clv, AddrMode::Imp, 2),
     Instr::new("LDA", Op::Lda, AddrMode::Aby, 4),
     Instr::new("TSX", Op::Tsx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("LAS", Op::Las, AddrMode::Aby, 4),
     Instr::new("LDY", Op::Ldy, AddrMode::Abx, 4),
     Instr::new("LDA", Op::Lda, AddrMode::Abx, 4),
     Instr::new("LDX", Op::Ldx, AddrMode::Aby, 4),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 4),
+    Instr::unofficial("LAX", Op::Lax, AddrMode::Aby, 4),
     // opcodes 0xc0..=0xcf
     Instr::new("CPY", Op::Cpy, AddrMode::Imm, 2),
     Instr::new("CMP", Op::Cmp, AddrMode::Izx, 6),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imm, 2),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Izx, 8),
     Instr::new("CPY", Op::Cpy, AddrMode::Zp0, 3),
     Instr::new("CMP", Op::Cmp, AddrMode::Zp0, 3),
     Instr::new("DEC", Op::Dec, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Zp0, 5),
     Instr::new("INY", Op::Iny, AddrMode::Imp, 2),
     Instr::new("CMP", Op::Cmp, AddrMode::Imm, 2),
     Instr::new("DEX", Op::Dex, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
+    Instr::unofficial("AXS", Op::Axs, AddrMode::Imm, 2),
     Instr::new("CPY", Op::Cpy, AddrMode::Abs, 4),
     Instr::new("CMP", Op::Cmp, AddrMode::Abs, 4),
     Instr::new("DEC", Op::Dec, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Abs, 6),
     // opcodes 0xd0..=0xdf
     Instr::new("BNE", Op::Bne, AddrMode::Rel, 2),
     Instr::new("CMP", Op::Cmp, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("CMP", Op::Cmp, AddrMode::Zpx, 4),
     Instr::new("DEC", Op::Dec, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Zpx, 6),
     Instr::new("CLD", Op::Cld, AddrMode::Imp, 2),
     Instr::new("CMP", Op::Cmp, AddrMode::Aby, 4),
-    Instr::new("NOP", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("CMP", Op::Cmp, AddrMode::Abx, 4),
     Instr::new("DEC", Op::Dec, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("DCP", Op::Dcp, AddrMode::Abx, 7),
     // opcodes 0xe0..=0xef
     Instr::new("CPX", Op::Cpx, AddrMode::Imm, 2),
     Instr::new("SBC", Op::Sbc, AddrMode::Izx, 6),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imm, 2),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Izx, 8),
     Instr::new("CPX", Op::Cpx, AddrMode::Zp0, 3),
     Instr::new("SBC", Op::Sbc, AddrMode::Zp0, 3),
     Instr::new("INC", Op::Inc, AddrMode::Zp0, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 5),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Zp0, 5),
     Instr::new("INX", Op::Inx, AddrMode::Imp, 2),
     Instr::new("SBC", Op::Sbc, AddrMode::Imm, 2),
     Instr::new("NOP", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Sbc, AddrMode::Imp, 2),
+    Instr::unofficial("SBC", Op::Sbc, AddrMode::Imm, 2),
     Instr::new("CPX", Op::Cpx, AddrMode::Abs, 4),
     Instr::new("SBC", Op::Sbc, AddrMode::Abs, 4),
     Instr::new("INC", Op::Inc, AddrMode::Abs, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Abs, 6),
     // opcodes 0xf0..=0xff
     Instr::new("BEQ", Op::Beq, AddrMode::Rel, 2),
     Instr::new("SBC", Op::Sbc, AddrMode::Izy, 5),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 8),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("JAM", Op::Jam, AddrMode::Imp, 2),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Izy, 8),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Zpx, 4),
     Instr::new("SBC", Op::Sbc, AddrMode::Zpx, 4),
     Instr::new("INC", Op::Inc, AddrMode::Zpx, 6),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 6),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Zpx, 6),
     Instr::new("SED", Op::Sed, AddrMode::Imp, 2),
     Instr::new("SBC", Op::Sbc, AddrMode::Aby, 4),
-    Instr::new("NOP", Op::Nop, AddrMode::Imp, 2),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
-    Instr::new("???", Op::Nop, AddrMode::Imp, 4),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Imp, 2),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Aby, 7),
+    Instr::unofficial("NOP", Op::Nop, AddrMode::Abx, 4),
     Instr::new("SBC", Op::Sbc, AddrMode::Abx, 4),
     Instr::new("INC", Op::Inc, AddrMode::Abx, 7),
-    Instr::new("???", Op::Xxx, AddrMode::Imp, 7),
+    Instr::unofficial("ISC", Op::Isc, AddrMode::Abx, 7),
 ];
@@ -0,0 +1,338 @@
+use crate::cpu::Flag;
+use std::fmt;
+use std::str::FromStr;
+
+/// Emulator state referenced by name in an expression
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Var {
+    A,
+    X,
+    Y,
+    Sp,
+    Pc,
+    /// status register
+    P,
+    /// status flag (0 / 1)
+    Flag(Flag),
+    /// PPU scanline (pre-render line: last scanline of the frame)
+    Scanline,
+    /// PPU dot in the scanline
+    Cycle,
+    /// frames since power-on / load
+    Frame,
+}
+
+/// Source of the values in an expression
+pub trait ExprContext {
+    fn var(&self, var: Var) -> i64;
+    /// CPU memory read without side effects
+    fn read(&self, addr: u16) -> u8;
+    /// 8 KiB PRG bank mapped at CPU address `addr` (`chr`: 1 KiB CHR bank at
+    /// PPU address `addr`), `None` if not mapped to cartridge ROM
+    fn bank(&self, addr: u16, chr: bool) -> Option<usize>;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Node {
+    Num(i64),
+    Var(Var),
+    /// memory byte `[addr]`
+    Read(Box<Node>),
+    /// `prgbank(addr)` / `chrbank(addr)`
+    Bank(bool, Box<Node>),
+    Unary(char, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+/// Expression over CPU registers and flags, memory, PPU position, frame
+/// count and mapper banks, e.g. `[$00ff] == 3 && scanline >= 240`.
+///
+/// Operators as in C (comparisons and logical operators give 0 / 1),
+/// numbers are decimal, `$` / `0x` hexadecimal or `%` binary. Names:
+/// `a x y sp pc p`, flags `c z i d v n`, `scanline cycle frame`,
+/// `prgbank(addr)` and `chrbank(addr)` (8 KiB / 1 KiB bank number, -1 if
+/// not mapped to ROM).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Expr {
+    text: String,
+    node: Node,
+}
+
+impl Expr {
+    pub fn eval(&self, ctx: &impl ExprContext) -> i64 {
+        eval(&self.node, ctx)
+    }
+
+    /// expression is non-zero
+    pub fn is_true(&self, ctx: &impl ExprContext) -> bool {
+        self.eval(ctx) != 0
+    }
+}
+
+fn eval(node: &Node, ctx: &impl ExprContext) -> i64 {
+    match node {
+        Node::Num(v) => *v,
+        Node::Var(var) => ctx.var(*var),
+        Node::Read(addr) => ctx.read(eval(addr, ctx) as u16) as i64,
+        Node::Bank(chr, addr) => ctx
+            .bank(eval(addr, ctx) as u16, *chr)
+            .map_or(-1, |b| b as i64),
+        Node::Unary(op, a) => {
+            let a = eval(a, ctx);
+            match op {
+                '-' => a.wrapping_neg(),
+                '~' => !a,
+                _ => (a == 0) as i64,
+            }
+        }
+        // short-circuit
+        Node::Binary("&&", a, b) => (eval(a, ctx) != 0 && eval(b, ctx) != 0) as i64,
+        Node::Binary("||", a, b) => (eval(a, ctx) != 0 || eval(b, ctx) != 0) as i64,
+        Node::Binary(op, a, b) => {
+            let (a, b) = (eval(a, ctx), eval(b, ctx));
+            match *op {
+                "|" => a | b,
+                "^" => a ^ b,
+                "&" => a & b,
+                "==" => (a == b) as i64,
+                "!=" => (a != b) as i64,
+                "<" => (a < b) as i64,
+                "<=" => (a <= b) as i64,
+                ">" => (a > b) as i64,
+                ">=" => (a >= b) as i64,
+                "<<" => a.wrapping_shl(b as u32 & 0x3f),
+                ">>" => a.wrapping_shr(b as u32 & 0x3f),
+                "+" => a.wrapping_add(b),
+                "-" => a.wrapping_sub(b),
+                "*" => a.wrapping_mul(b),
+                // no error during emulation
+                _ if b == 0 => 0,
+                "/" => a.wrapping_div(b),
+                _ => a.wrapping_rem(b),
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            chars: s.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.expr(0)?;
+        if parser.peek().is_some() {
+            return Err(format!("invalid expression '{s}'"));
+        }
+        Ok(Expr {
+            text: s.trim().to_string(),
+            node,
+        })
+    }
+}
+
+/// precedence climbing expression parser
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    /// binary operator at the current position: (operator, precedence)
+    fn binary_op(&mut self) -> Option<(&'static str, u8)> {
+        let c = self.peek()?;
+        let next = self.chars.get(self.pos + 1).copied();
+        match (c, next) {
+            ('|', Some('|')) => Some(("||", 1)),
+            ('&', Some('&')) => Some(("&&", 2)),
+            ('=', Some('=')) => Some(("==", 6)),
+            ('!', Some('=')) => Some(("!=", 6)),
+            ('<', Some('=')) => Some(("<=", 7)),
+            ('>', Some('=')) => Some((">=", 7)),
+            ('<', Some('<')) => Some(("<<", 8)),
+            ('>', Some('>')) => Some((">>", 8)),
+            ('|', _) => Some(("|", 3)),
+            ('^', _) => Some(("^", 4)),
+            ('&', _) => Some(("&", 5)),
+            ('<', _) => Some(("<", 7)),
+            ('>', _) => Some((">", 7)),
+            ('+', _) => Some(("+", 9)),
+            ('-', _) => Some(("-", 9)),
+            ('*', _) => Some(("*", 10)),
+            ('/', _) => Some(("/", 10)),
+            ('%', _) => Some(("%", 10)),
+            _ => None,
+        }
+    }
+
+    fn expr(&mut self, min_prec: u8) -> Result<Node, String> {
+        let mut lhs = self.unary()?;
+        while let Some((op, prec)) = self.binary_op() {
+            if prec < min_prec {
+                break;
+            }
+            self.pos += op.len();
+            let rhs = self.expr(prec + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    /// expression up to the closing character `close`
+    fn enclosed(&mut self, close: char) -> Result<Node, String> {
+        self.pos += 1;
+        let node = self.expr(0)?;
+        if self.peek() != Some(close) {
+            return Err(format!("missing '{close}'"));
+        }
+        self.pos += 1;
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        let c = self.peek().ok_or_else(|| String::from("missing operand"))?;
+        match c {
+            '-' | '~' | '!' => {
+                self.pos += 1;
+                Ok(Node::Unary(c, Box::new(self.unary()?)))
+            }
+            '(' => self.enclosed(')'),
+            '[' => Ok(Node::Read(Box::new(self.enclosed(']')?))),
+            '$' | '%' => {
+                let end = self.token_end(self.pos + 1);
+                let digits: String = self.chars[self.pos + 1..end].iter().collect();
+                self.pos = end;
+                let radix = if c == '$' { 16 } else { 2 };
+                i64::from_str_radix(&digits, radix)
+                    .map(Node::Num)
+                    .map_err(|_| format!("invalid number '{c}{digits}'"))
+            }
+            _ => {
+                let end = self.token_end(self.pos);
+                let token: String = self.chars[self.pos..end].iter().collect();
+                if token.is_empty() {
+                    return Err(format!("unexpected '{c}'"));
+                }
+                self.pos = end;
+                if c.is_ascii_digit() {
+                    let value = match token.strip_prefix("0x") {
+                        Some(hex) => i64::from_str_radix(hex, 16),
+                        None => token.parse(),
+                    };
+                    return value
+                        .map(Node::Num)
+                        .map_err(|_| format!("invalid number '{token}'"));
+                }
+                self.name(&token.to_ascii_lowercase())
+            }
+        }
+    }
+
+    fn name(&mut self, name: &str) -> Result<Node, String> {
+        let var = match name {
+            "a" => Var::A,
+            "x" => Var::X,
+            "y" => Var::Y,
+            "sp" => Var::Sp,
+            "pc" => Var::Pc,
+            "p" => Var::P,
+            "c" => Var::Flag(Flag::C),
+            "z" => Var::Flag(Flag::Z),
+            "i" => Var::Flag(Flag::I),
+            "d" => Var::Flag(Flag::D),
+            "v" => Var::Flag(Flag::V),
+            "n" => Var::Flag(Flag::N),
+            "scanline" => Var::Scanline,
+            "cycle" => Var::Cycle,
+            "frame" => Var::Frame,
+            "prgbank" | "chrbank" => {
+                if self.peek() != Some('(') {
+                    return Err(format!("missing '(' after '{name}'"));
+                }
+                let addr = self.enclosed(')')?;
+                return Ok(Node::Bank(name == "chrbank", Box::new(addr)));
+            }
+            _ => return Err(format!("unknown name '{name}'")),
+        };
+        Ok(Node::Var(var))
+    }
+
+    fn token_end(&self, start: usize) -> usize {
+        let mut end = start;
+        while self
+            .chars
+            .get(end)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            end += 1;
+        }
+        end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct State;
+
+    impl ExprContext for State {
+        fn var(&self, var: Var) -> i64 {
+            match var {
+                Var::A => 0x03,
+                Var::Pc => 0xc123,
+                Var::Flag(Flag::C) => 1,
+                Var::Scanline => 241,
+                _ => 0,
+            }
+        }
+
+        fn read(&self, addr: u16) -> u8 {
+            addr as u8
+        }
+
+        fn bank(&self, addr: u16, chr: bool) -> Option<usize> {
+            (addr >= 0x8000 && !chr).then_some(5)
+        }
+    }
+
+    fn eval(s: &str) -> i64 {
+        s.parse::<Expr>().unwrap().eval(&State)
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval("[$00ff] == 255"), 1);
+        assert_eq!(eval("A == 3 && c"), 1);
+        assert_eq!(eval("a == 3 && !c || scanline >= 240"), 1);
+        assert_eq!(eval("1 + 2 * 3 << 1"), 14);
+        assert_eq!(eval("(pc & $ff00) >> 8"), 0xc1);
+        assert_eq!(eval("0x10 | %101 ^ 1"), 0x14);
+        assert_eq!(eval("-a % 2"), -1);
+        assert_eq!(eval("a / 0"), 0);
+        assert_eq!(eval("prgbank($8000) == 5 && chrbank(0) == -1"), 1);
+        assert_eq!(eval("[pc - 2] != ~0"), 1);
+
+        let expr: Expr = " a != 1 ".parse().unwrap();
+        assert_eq!(expr.to_string(), "a != 1");
+        assert!("a ==".parse::<Expr>().is_err());
+        assert!("[$10".parse::<Expr>().is_err());
+        assert!("foo + 1".parse::<Expr>().is_err());
+        assert!("a b".parse::<Expr>().is_err());
+    }
+}
@@ -1,14 +1,21 @@
 pub mod apu;
+pub mod asm;
 pub mod audio;
+pub mod breakpoint;
 pub mod bus;
 pub mod cartridge;
+pub mod cdl;
 pub mod controller;
 pub mod cpu;
+pub mod expr;
 pub mod input;
 pub mod mapper;
 pub mod ppu;
+pub mod profile;
 pub mod romdb;
 pub mod save;
 pub mod screen;
+pub mod symbols;
 pub mod system;
 pub mod system_debug_cpu;
+pub mod trace;
@@ -5,14 +5,166 @@ pub mod mapper003;
 pub mod mapper004;
 pub mod mapper007;
 pub mod mapper009;
+pub mod mapper016;
+pub mod mapper030;
+pub mod mapper060;
+pub mod mapper111;
+pub mod mapper212;
+pub mod mapper225;
+pub mod mapper226;
+pub mod mapper228;
+pub mod registry;
+
+mod eeprom;
+mod flash;
 
 use crate::cartridge::Mirror;
 
+use serde::{Deserialize, Serialize};
+
 pub enum MapResult {
     None,
     MapAddr(usize),
     DirectRead(u8),
+    /// read driving only the data bits in the mask (value, mask), the other
+    /// bits are open bus
+    PartialRead(u8, u8),
     DirectWrite,
+    /// flash program at PRG offset: bits can only be cleared
+    FlashProgram(usize),
+    /// flash erase of PRG range (offset, length): set to $ff
+    FlashErase(usize, usize),
+}
+
+/// Backing memory of a 1 KiB nametable slot in PPU address space
+/// (slot 0: $2000, slot 1: $2400, slot 2: $2800, slot 3: $2c00)
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub enum Nametable {
+    /// console-internal nametable RAM (CIRAM), page 0 or 1
+    Ciram(usize),
+    /// cartridge CHR memory (ROM or RAM), 1 KiB page at byte offset
+    Chr(usize),
+    /// additional nametable RAM on the cartridge board (four-screen), at byte offset
+    CartRam(usize),
+    /// memory internal to the mapper, accessed through `nametable_read` /
+    /// `nametable_write` (e.g. MMC5 ExRAM / fill mode)
+    Mapper,
+}
+
+/// Expansion sound chips found on cartridges
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AudioChip {
+    Vrc6,
+    Vrc7,
+    Namco163,
+    Sunsoft5B,
+    Mmc5,
+    Fds,
+}
+
+impl AudioChip {
+    /// Mixing gain of the chip's full-scale output relative to the full-scale
+    /// 2A03 output. Approximations of measured hardware levels.
+    pub fn gain(&self) -> f32 {
+        match self {
+            AudioChip::Vrc6 => 0.60,
+            AudioChip::Vrc7 => 0.50,
+            AudioChip::Namco163 => 0.60,
+            AudioChip::Sunsoft5B => 0.65,
+            AudioChip::Mmc5 => 0.43,
+            AudioChip::Fds => 0.36,
+        }
+    }
+}
+
+/// Bank slot of CPU (8 KiB) or PPU (1 KiB) address space mapped by the
+/// cartridge
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BankWindow {
+    /// first address of the window
+    pub start: u16,
+    /// last address of the window (inclusive)
+    pub end: u16,
+    /// byte offset into PRG / CHR memory; `None` for memory internal to the
+    /// mapper (e.g. PRG RAM)
+    pub offset: Option<usize>,
+}
+
+/// IRQ counter state of a mapper
+#[derive(Clone, Debug, Default)]
+pub struct IrqDebugState {
+    pub counter: u16,
+    pub reload: u16,
+    pub enabled: bool,
+    /// IRQ line currently asserted
+    pub pending: bool,
+}
+
+/// Snapshot of the mapper state for debuggers
+#[derive(Clone, Debug)]
+pub struct MapperDebugState {
+    /// CPU $6000-$ffff
+    pub prg: Vec<BankWindow>,
+    /// PPU $0000-$1fff
+    pub chr: Vec<BankWindow>,
+    pub mirror: Mirror,
+    /// raw register values by name
+    pub registers: Vec<(&'static str, u16)>,
+    pub irq: Option<IrqDebugState>,
+}
+
+impl MapperDebugState {
+    /// Bank windows determined from the read-only mappings, one per 8 KiB
+    /// PRG / 1 KiB CHR slot (not merged, so contiguous banks stay separate),
+    /// no registers.
+    pub fn probe<M: Mapper + ?Sized>(mapper: &M) -> MapperDebugState {
+        MapperDebugState {
+            prg: Self::probe_windows(0x6000..=0xffff, 0x2000, |addr| mapper.cpu_map_read_ro(addr)),
+            chr: Self::probe_windows(0x0000..=0x1fff, 0x0400, |addr| mapper.ppu_map_read_ro(addr)),
+            mirror: mapper.mirror(),
+            registers: vec![],
+            irq: None,
+        }
+    }
+
+    /// 8 KiB PRG bank mapped at CPU address `addr`, `None` if not mapped to
+    /// PRG memory
+    pub fn prg_bank(&self, addr: u16) -> Option<usize> {
+        Self::bank(&self.prg, addr, 0x2000)
+    }
+
+    /// 1 KiB CHR bank mapped at PPU address `addr`, `None` if not mapped to
+    /// CHR memory
+    pub fn chr_bank(&self, addr: u16) -> Option<usize> {
+        Self::bank(&self.chr, addr, 0x0400)
+    }
+
+    fn bank(windows: &[BankWindow], addr: u16, size: usize) -> Option<usize> {
+        let window = windows.iter().find(|w| (w.start..=w.end).contains(&addr))?;
+        Some((window.offset? + (addr - window.start) as usize) / size)
+    }
+
+    fn probe_windows(
+        range: std::ops::RangeInclusive<u16>,
+        step: usize,
+        map: impl Fn(u16) -> MapResult,
+    ) -> Vec<BankWindow> {
+        range
+            .step_by(step)
+            .filter_map(|start| {
+                let offset = match map(start) {
+                    MapResult::MapAddr(offset) => Some(offset),
+                    MapResult::DirectRead(_) | MapResult::PartialRead(..) => None,
+                    _ => return None,
+                };
+                Some(BankWindow {
+                    start,
+                    end: start + (step - 1) as u16,
+                    offset,
+                })
+            })
+            .collect()
+    }
 }
 
 #[typetag::serde(tag = "id")]
@@ -25,20 +177,136 @@ pub trait Mapper {
 
     fn ppu_map_read(&mut self, addr: u16) -> MapResult;
 
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult;
+
     fn ppu_map_write(&mut self, addr: u16, data: u8) -> MapResult;
 
     fn mirror(&self) -> Mirror {
         Mirror::Hardware
     }
 
+    /// If true, register writes to $8000-$ffff are ANDed with the ROM byte
+    /// at the written address (boards without a ROM /OE decoder).
+    fn bus_conflicts(&self) -> bool {
+        false
+    }
+
+    // Nametable interface
+
+    /// Backing memory of nametable slot 0..=3. `None` selects CIRAM
+    /// according to `mirror()`.
+    fn nametable(&self, _slot: usize) -> Option<Nametable> {
+        None
+    }
+
+    /// Read from mapper-internal nametable memory (`Nametable::Mapper`).
+    fn nametable_read(&mut self, _addr: u16) -> u8 {
+        0x00
+    }
+
+    /// Write to mapper-internal nametable memory (`Nametable::Mapper`).
+    fn nametable_write(&mut self, _addr: u16, _data: u8) {}
+
+    /// Soft reset (console reset button). Power-up state is established by
+    /// the constructor; on-board RAM keeps its contents across resets.
     fn reset(&mut self) {}
 
-    // Scanline IRQ interface
+    // Timing interface
+
+    /// Called once per CPU cycle (M2), also while the CPU is halted by DMA.
+    fn cpu_clock(&mut self) {}
+
+    fn on_scanline_end(&mut self) {}
+
+    // Expansion audio interface
+
+    /// Expansion sound chip on the cartridge, if any.
+    fn audio_chip(&self) -> Option<AudioChip> {
+        None
+    }
+
+    /// Current expansion audio output, normalized to -1.0..=1.0 of the
+    /// chip's full scale. The chip is clocked through `cpu_clock`; the system
+    /// scales the sample by `AudioChip::gain` and mixes it with the 2A03.
+    fn audio_sample(&self) -> f32 {
+        0.0
+    }
+
+    // Debug interface
+
+    /// Current bank mapping, registers and IRQ state. The default derives
+    /// the bank windows from the read-only mappings.
+    fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState::probe(self)
+    }
+
+    // IRQ interface
+
+    /// Level of the cartridge IRQ output. The line is level-triggered: it
+    /// stays asserted until the mapper is acknowledged through its own
+    /// registers.
     fn irq_state(&self) -> bool {
         false
     }
+}
 
-    fn irq_clear(&mut self) {}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapper::mapper000::Mapper000;
+    use crate::mapper::mapper004::Mapper004;
 
-    fn on_scanline_end(&mut self) {}
+    #[test]
+    fn probe_windows() {
+        let window = |start: u16, end: u16, offset| BankWindow { start, end, offset };
+        let state = Mapper000::new(1, 1).debug_state();
+        assert_eq!(
+            state.prg,
+            vec![
+                window(0x8000, 0x9fff, Some(0x0000)),
+                window(0xa000, 0xbfff, Some(0x2000)),
+                window(0xc000, 0xdfff, Some(0x0000)),
+                window(0xe000, 0xffff, Some(0x2000)),
+            ]
+        );
+        assert_eq!(state.chr.len(), 8);
+        assert_eq!(state.chr[7], window(0x1c00, 0x1fff, Some(0x1c00)));
+
+        // MMC3 PRG RAM, R6 / R7 = 2 / 3 stay separate banks
+        let mut mapper = Mapper004::new(4, 2);
+        for (reg, bank) in [(6, 2), (7, 3)] {
+            mapper.cpu_map_write(0x8000, reg);
+            mapper.cpu_map_write(0x8001, bank);
+        }
+        let state = mapper.debug_state();
+        assert_eq!(state.prg.len(), 5);
+        assert_eq!(state.prg[0], window(0x6000, 0x7fff, None));
+        assert_eq!(state.prg[1], window(0x8000, 0x9fff, Some(0x4000)));
+        assert_eq!(state.prg[2], window(0xa000, 0xbfff, Some(0x6000)));
+    }
+
+    #[test]
+    fn bank_numbers() {
+        // NROM-256
+        let state = Mapper000::new(2, 1).debug_state();
+        assert_eq!(state.prg_bank(0x8000), Some(0));
+        assert_eq!(state.prg_bank(0xc000), Some(2));
+        assert_eq!(state.prg_bank(0x6000), None);
+        assert_eq!(state.chr_bank(0x1c00), Some(7));
+
+        // MMC3, 64 KiB PRG / 16 KiB CHR: R6 = 2, R7 = 3, R0 = 4, R1 = 6
+        let mut mapper = Mapper004::new(4, 2);
+        for (reg, bank) in [(6, 2), (7, 3), (0, 4), (1, 6)] {
+            mapper.cpu_map_write(0x8000, reg);
+            mapper.cpu_map_write(0x8001, bank);
+        }
+        let state = mapper.debug_state();
+        assert_eq!(state.prg_bank(0x8000), Some(2));
+        assert_eq!(state.prg_bank(0xbfff), Some(3));
+        assert_eq!(state.prg_bank(0xc000), Some(6));
+        assert_eq!(state.prg_bank(0xe000), Some(7));
+        assert_eq!(state.chr_bank(0x0000), Some(4));
+        assert_eq!(state.chr_bank(0x0400), Some(5));
+        assert_eq!(state.chr_bank(0x0800), Some(6));
+    }
 }
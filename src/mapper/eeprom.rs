@@ -0,0 +1,291 @@
+use serde::{Deserialize, Serialize};
+
+/// Serial EEPROM types found on cartridge boards
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EepromType {
+    /// X24C01, 128 bytes: no device address, 7 bit word address and data
+    /// are transferred LSB first
+    X24C01,
+    /// 24C02, 256 bytes: standard I2C with device address, MSB first
+    C24C02,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+enum Mode {
+    Idle,
+    ChipAddress,
+    Address,
+    Read,
+    Write,
+    SendAck,
+    WaitAck,
+}
+
+/// Bit-level model of an I2C EEPROM driven through SCL / SDA lines
+#[derive(Deserialize, Serialize)]
+pub struct Eeprom {
+    kind: EepromType,
+    data: Vec<u8>,
+
+    mode: Mode,
+    next_mode: Mode,
+    chip_address: u8,
+    address: u8,
+    shift: u8,
+    bit_counter: u8,
+    output: bool,
+
+    prev_scl: bool,
+    prev_sda: bool,
+}
+
+impl Eeprom {
+    pub fn new(kind: EepromType) -> Eeprom {
+        let size = match kind {
+            EepromType::X24C01 => 128,
+            EepromType::C24C02 => 256,
+        };
+        Eeprom {
+            kind,
+            data: vec![0; size],
+
+            mode: Mode::Idle,
+            next_mode: Mode::Idle,
+            chip_address: 0,
+            address: 0,
+            shift: 0,
+            bit_counter: 0,
+            output: true,
+
+            prev_scl: false,
+            prev_sda: false,
+        }
+    }
+
+    /// Level of the SDA line driven by the EEPROM (open drain, idle high).
+    pub fn output(&self) -> bool {
+        self.output
+    }
+
+    /// Update the SCL / SDA lines driven by the mapper.
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        if self.prev_scl && scl && self.prev_sda && !sda {
+            // start condition: SDA falls while SCL is high
+            self.mode = match self.kind {
+                EepromType::X24C01 => Mode::Address,
+                EepromType::C24C02 => Mode::ChipAddress,
+            };
+            self.bit_counter = 0;
+            self.output = true;
+        } else if self.prev_scl && scl && !self.prev_sda && sda {
+            // stop condition: SDA rises while SCL is high
+            self.mode = Mode::Idle;
+            self.output = true;
+        } else if !self.prev_scl && scl {
+            self.clock_rise(sda);
+        } else if self.prev_scl && !scl {
+            self.clock_fall();
+        }
+
+        self.prev_scl = scl;
+        self.prev_sda = sda;
+    }
+
+    fn mask(&self) -> usize {
+        self.data.len() - 1
+    }
+
+    fn bit_pos(&self) -> u8 {
+        match self.kind {
+            EepromType::X24C01 => self.bit_counter,
+            EepromType::C24C02 => 7 - self.bit_counter,
+        }
+    }
+
+    fn shift_in(&mut self, value: bool) {
+        if self.bit_counter < 8 {
+            let bit = self.bit_pos();
+            self.shift = (self.shift & !(1 << bit)) | ((value as u8) << bit);
+            self.bit_counter += 1;
+        }
+    }
+
+    fn shift_out(&mut self) {
+        if self.bit_counter < 8 {
+            self.output = self.shift & (1 << self.bit_pos()) != 0;
+            self.bit_counter += 1;
+        }
+    }
+
+    fn clock_rise(&mut self, sda: bool) {
+        match self.mode {
+            Mode::ChipAddress | Mode::Write => self.shift_in(sda),
+            Mode::Address => match self.kind {
+                EepromType::X24C01 if self.bit_counter == 7 => {
+                    // 8th bit is the R/W flag
+                    self.address = self.shift & 0x7f;
+                    self.bit_counter = 8;
+                    if sda {
+                        self.next_mode = Mode::Read;
+                        self.shift = self.data[self.address as usize];
+                    } else {
+                        self.next_mode = Mode::Write;
+                    }
+                }
+                _ => self.shift_in(sda),
+            },
+            Mode::Read => self.shift_out(),
+            Mode::SendAck => self.output = false,
+            Mode::WaitAck => {
+                if !sda {
+                    // master acknowledged, continue sequential read
+                    self.next_mode = Mode::Read;
+                    self.shift = self.data[self.address as usize];
+                } else {
+                    self.next_mode = Mode::Idle;
+                }
+            }
+            Mode::Idle => {}
+        }
+    }
+
+    fn clock_fall(&mut self) {
+        if self.bit_counter < 8 && !matches!(self.mode, Mode::SendAck | Mode::WaitAck) {
+            return;
+        }
+
+        match self.mode {
+            Mode::ChipAddress => {
+                self.chip_address = self.shift;
+                if self.chip_address & 0xf0 == 0xa0 {
+                    self.mode = Mode::SendAck;
+                    if self.chip_address & 0x01 != 0 {
+                        self.next_mode = Mode::Read;
+                        self.shift = self.data[self.address as usize];
+                    } else {
+                        self.next_mode = Mode::Address;
+                    }
+                } else {
+                    // not addressed, no acknowledge
+                    self.mode = Mode::Idle;
+                }
+                self.output = true;
+            }
+            Mode::Address => {
+                if self.kind == EepromType::C24C02 {
+                    self.address = self.shift;
+                    self.next_mode = Mode::Write;
+                }
+                self.mode = Mode::SendAck;
+                self.output = true;
+            }
+            Mode::Read => {
+                self.mode = Mode::WaitAck;
+                self.address = ((self.address as usize + 1) & self.mask()) as u8;
+                self.output = true;
+            }
+            Mode::Write => {
+                self.data[self.address as usize] = self.shift;
+                self.address = ((self.address as usize + 1) & self.mask()) as u8;
+                self.mode = Mode::SendAck;
+                self.next_mode = match self.kind {
+                    EepromType::X24C01 => Mode::Idle,
+                    EepromType::C24C02 => Mode::Write,
+                };
+                self.output = true;
+            }
+            Mode::SendAck | Mode::WaitAck => {
+                self.mode = self.next_mode;
+                self.bit_counter = 0;
+                self.output = true;
+            }
+            Mode::Idle => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(e: &mut Eeprom) {
+        e.write(false, true);
+        e.write(true, true);
+        e.write(true, false);
+        e.write(false, false);
+    }
+
+    fn stop(e: &mut Eeprom) {
+        e.write(false, false);
+        e.write(true, false);
+        e.write(true, true);
+    }
+
+    fn send_byte(e: &mut Eeprom, byte: u8, msb_first: bool) -> bool {
+        for i in 0..8 {
+            let bit = if msb_first { 7 - i } else { i };
+            let sda = byte & (1 << bit) != 0;
+            e.write(false, sda);
+            e.write(true, sda);
+            e.write(false, sda);
+        }
+        // acknowledge clock, SDA released by master
+        e.write(false, true);
+        e.write(true, true);
+        let ack = !e.output();
+        e.write(false, true);
+        ack
+    }
+
+    fn recv_byte(e: &mut Eeprom, msb_first: bool, ack: bool) -> u8 {
+        let mut byte = 0;
+        for i in 0..8 {
+            let bit = if msb_first { 7 - i } else { i };
+            e.write(false, true);
+            e.write(true, true);
+            byte |= (e.output() as u8) << bit;
+            e.write(false, true);
+        }
+        e.write(false, !ack);
+        e.write(true, !ack);
+        e.write(false, !ack);
+        byte
+    }
+
+    #[test]
+    fn c24c02_write_read() {
+        let mut e = Eeprom::new(EepromType::C24C02);
+
+        start(&mut e);
+        assert!(send_byte(&mut e, 0xa0, true));
+        assert!(send_byte(&mut e, 0x10, true));
+        assert!(send_byte(&mut e, 0x5a, true));
+        assert!(send_byte(&mut e, 0xc3, true));
+        stop(&mut e);
+
+        // random read: dummy write to set address, repeated start
+        start(&mut e);
+        assert!(send_byte(&mut e, 0xa0, true));
+        assert!(send_byte(&mut e, 0x10, true));
+        start(&mut e);
+        assert!(send_byte(&mut e, 0xa1, true));
+        assert_eq!(recv_byte(&mut e, true, true), 0x5a);
+        assert_eq!(recv_byte(&mut e, true, false), 0xc3);
+        stop(&mut e);
+    }
+
+    #[test]
+    fn x24c01_write_read() {
+        let mut e = Eeprom::new(EepromType::X24C01);
+
+        start(&mut e);
+        assert!(send_byte(&mut e, 0x05, false));
+        assert!(send_byte(&mut e, 0x3c, false));
+        stop(&mut e);
+
+        start(&mut e);
+        assert!(send_byte(&mut e, 0x05 | 0x80, false));
+        assert_eq!(recv_byte(&mut e, false, false), 0x3c);
+        stop(&mut e);
+    }
+}
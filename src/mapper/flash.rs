@@ -0,0 +1,189 @@
+use super::MapResult;
+
+use serde::{Deserialize, Serialize};
+
+const SECTOR_SIZE: usize = 0x1000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+enum State {
+    Idle,
+    Unlock1,
+    Unlock2,
+    Program,
+    Erase,
+    EraseUnlock1,
+    EraseUnlock2,
+}
+
+/// Command interface of an SST39SF0x0 style flash chip in PRG space.
+///
+/// Programming and erasing complete immediately; the resulting operations on
+/// the PRG memory are passed to the cartridge as `MapResult::FlashProgram` /
+/// `MapResult::FlashErase`.
+#[derive(Deserialize, Serialize)]
+pub struct Flash {
+    size: usize,
+    state: State,
+    software_id: bool,
+}
+
+impl Flash {
+    /// `size`: chip size in bytes
+    pub fn new(size: usize) -> Flash {
+        Flash {
+            size,
+            state: State::Idle,
+            software_id: false,
+        }
+    }
+
+    /// Result of a read at chip address `chip_addr`: manufacturer / device
+    /// ID in software ID mode, otherwise the memory contents.
+    pub fn read(&self, chip_addr: usize) -> MapResult {
+        if self.software_id {
+            let device_id = match self.size {
+                0..=0x20000 => 0xb5,       // SST39SF010
+                0x20001..=0x40000 => 0xb6, // SST39SF020
+                _ => 0xb7,                 // SST39SF040
+            };
+            MapResult::DirectRead(if chip_addr & 0x01 == 0 {
+                0xbf
+            } else {
+                device_id
+            })
+        } else {
+            MapResult::MapAddr(chip_addr % self.size)
+        }
+    }
+
+    /// Bus write at chip address `chip_addr`.
+    pub fn write(&mut self, chip_addr: usize, data: u8) -> MapResult {
+        let chip_addr = chip_addr % self.size;
+        // command cycles only decode A0-A14
+        let cmd_addr = chip_addr & 0x7fff;
+
+        let mut result = MapResult::DirectWrite;
+        self.state = match (self.state, cmd_addr, data) {
+            (_, _, 0xf0) if self.state != State::Program => {
+                self.software_id = false;
+                State::Idle
+            }
+            (State::Idle, 0x5555, 0xaa) => State::Unlock1,
+            (State::Unlock1, 0x2aaa, 0x55) => State::Unlock2,
+            (State::Unlock2, 0x5555, 0xa0) => State::Program,
+            (State::Unlock2, 0x5555, 0x80) => State::Erase,
+            (State::Unlock2, 0x5555, 0x90) => {
+                self.software_id = true;
+                State::Idle
+            }
+            (State::Program, _, _) => {
+                result = MapResult::FlashProgram(chip_addr);
+                State::Idle
+            }
+            (State::Erase, 0x5555, 0xaa) => State::EraseUnlock1,
+            (State::EraseUnlock1, 0x2aaa, 0x55) => State::EraseUnlock2,
+            (State::EraseUnlock2, _, 0x30) => {
+                result = MapResult::FlashErase(chip_addr & !(SECTOR_SIZE - 1), SECTOR_SIZE);
+                State::Idle
+            }
+            (State::EraseUnlock2, 0x5555, 0x10) => {
+                result = MapResult::FlashErase(0, self.size);
+                State::Idle
+            }
+            _ => State::Idle,
+        };
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(f: &mut Flash, cmd: u8) -> MapResult {
+        f.write(0x5555, 0xaa);
+        f.write(0x2aaa, 0x55);
+        f.write(0x5555, cmd)
+    }
+
+    #[test]
+    fn program() {
+        let mut f = Flash::new(0x80000);
+        assert!(matches!(command(&mut f, 0xa0), MapResult::DirectWrite));
+        assert!(matches!(
+            f.write(0x41234, 0x5a),
+            MapResult::FlashProgram(0x41234)
+        ));
+        // back to idle: plain writes are ignored
+        assert!(matches!(f.write(0x41234, 0x00), MapResult::DirectWrite));
+
+        // A15+ not decoded in command cycles
+        f.write(0x1d555, 0xaa);
+        f.write(0x2aaa, 0x55);
+        f.write(0x5555, 0xa0);
+        assert!(matches!(
+            f.write(0x100, 0x12),
+            MapResult::FlashProgram(0x100)
+        ));
+    }
+
+    #[test]
+    fn erase() {
+        let mut f = Flash::new(0x80000);
+        command(&mut f, 0x80);
+        f.write(0x5555, 0xaa);
+        f.write(0x2aaa, 0x55);
+        assert!(matches!(
+            f.write(0x12345, 0x30),
+            MapResult::FlashErase(0x12000, SECTOR_SIZE)
+        ));
+
+        command(&mut f, 0x80);
+        f.write(0x5555, 0xaa);
+        f.write(0x2aaa, 0x55);
+        assert!(matches!(
+            f.write(0x5555, 0x10),
+            MapResult::FlashErase(0, 0x80000)
+        ));
+    }
+
+    #[test]
+    fn software_id() {
+        let mut f = Flash::new(0x80000);
+        assert!(matches!(f.read(0x0001), MapResult::MapAddr(0x0001)));
+        command(&mut f, 0x90);
+        assert!(matches!(f.read(0x0000), MapResult::DirectRead(0xbf)));
+        assert!(matches!(f.read(0x0001), MapResult::DirectRead(0xb7)));
+        // exit with the reset command
+        f.write(0x0000, 0xf0);
+        assert!(matches!(f.read(0x0001), MapResult::MapAddr(0x0001)));
+
+        let mut f = Flash::new(0x20000);
+        command(&mut f, 0x90);
+        assert!(matches!(f.read(0x0001), MapResult::DirectRead(0xb5)));
+    }
+
+    #[test]
+    fn invalid_sequence_resets() {
+        let mut f = Flash::new(0x80000);
+        // wrong second unlock cycle
+        f.write(0x5555, 0xaa);
+        f.write(0x2aaa, 0x54);
+        f.write(0x5555, 0xa0);
+        assert!(matches!(f.write(0x1000, 0x00), MapResult::DirectWrite));
+
+        // reset command between the erase cycles
+        command(&mut f, 0x80);
+        f.write(0x5555, 0xf0);
+        f.write(0x5555, 0xaa);
+        f.write(0x2aaa, 0x55);
+        assert!(matches!(f.write(0x1000, 0x30), MapResult::DirectWrite));
+
+        // reset command data is programmed in program mode
+        command(&mut f, 0xa0);
+        assert!(matches!(
+            f.write(0x1000, 0xf0),
+            MapResult::FlashProgram(0x1000)
+        ));
+    }
+}
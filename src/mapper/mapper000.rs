@@ -59,6 +59,10 @@ impl Mapper for Mapper000 {
     }
 
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => MapResult::MapAddr(addr as usize),
             _ => MapResult::None,
@@ -1,4 +1,4 @@
-use super::{MapResult, Mapper};
+use super::{MapResult, Mapper, MapperDebugState};
 
 use crate::cartridge::Mirror;
 
@@ -162,6 +162,10 @@ impl Mapper for Mapper001 {
     }
 
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => {
                 if self.num_banks_chr == 0 {
@@ -200,8 +204,6 @@ impl Mapper for Mapper001 {
     }
 
     fn reset(&mut self) {
-        self.prg_ram = [0; 8 * 1024];
-
         self.mirror_mode = Mirror::Horizontal;
         self.control_reg = 0x1c;
         self.load_reg = 0x10;
@@ -212,4 +214,20 @@ impl Mapper for Mapper001 {
         self.chr_bank_select_4_hi = 0;
         self.chr_bank_select_8 = 0;
     }
+
+    fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState {
+            registers: vec![
+                ("control", self.control_reg as u16),
+                ("shift", self.load_reg as u16),
+                ("chr_lo", self.chr_bank_select_4_lo as u16),
+                ("chr_hi", self.chr_bank_select_4_hi as u16),
+                ("chr_8k", self.chr_bank_select_8 as u16),
+                ("prg_lo", self.prg_bank_select_16_lo as u16),
+                ("prg_hi", self.prg_bank_select_16_hi as u16),
+                ("prg_32k", self.prg_bank_select_32 as u16),
+            ],
+            ..MapperDebugState::probe(self)
+        }
+    }
 }
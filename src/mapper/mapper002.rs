@@ -6,16 +6,21 @@ use serde::{Deserialize, Serialize};
 pub struct Mapper002 {
     num_banks_prg: usize,
     num_banks_chr: usize,
+    bus_conflicts: bool,
 
     prg_bank_select_lo: usize,
     prg_bank_select_hi: usize,
 }
 
 impl Mapper002 {
-    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper002 {
+    /// submapper 1: no bus conflicts, 2: AND-type bus conflicts,
+    /// 0: unspecified (no bus conflicts: licensed games avoid them, see
+    /// `registry::force_bus_conflicts` to test against them)
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize, submapper: u8) -> Mapper002 {
         Mapper002 {
             num_banks_prg,
             num_banks_chr,
+            bus_conflicts: submapper == 2,
 
             prg_bank_select_lo: 0,
             prg_bank_select_hi: num_banks_prg - 1,
@@ -56,7 +61,15 @@ impl Mapper for Mapper002 {
         MapResult::None
     }
 
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => MapResult::MapAddr(addr as usize),
             _ => MapResult::None,
@@ -6,14 +6,19 @@ use serde::{Deserialize, Serialize};
 pub struct Mapper003 {
     num_banks_prg: usize,
     num_banks_chr: usize,
+    bus_conflicts: bool,
     chr_bank_select: usize,
 }
 
 impl Mapper003 {
-    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper003 {
+    /// submapper 1: no bus conflicts, 2: AND-type bus conflicts,
+    /// 0: unspecified (no bus conflicts: licensed games avoid them, see
+    /// `registry::force_bus_conflicts` to test against them)
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize, submapper: u8) -> Mapper003 {
         Mapper003 {
             num_banks_prg,
             num_banks_chr,
+            bus_conflicts: submapper == 2,
             chr_bank_select: 0,
         }
     }
@@ -45,7 +50,15 @@ impl Mapper for Mapper003 {
         MapResult::None
     }
 
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select * 0x2000 + addr as usize),
             _ => MapResult::None,
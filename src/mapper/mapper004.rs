@@ -1,4 +1,4 @@
-use super::{MapResult, Mapper};
+use super::{IrqDebugState, MapResult, Mapper, MapperDebugState};
 
 use crate::cartridge::Mirror;
 
@@ -182,6 +182,10 @@ impl Mapper for Mapper004 {
     }
 
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => {
                 if self.num_banks_chr == 0 {
@@ -209,7 +213,6 @@ impl Mapper for Mapper004 {
     }
 
     fn reset(&mut self) {
-        self.prg_ram = [0; 8 * 1024];
         self.mirror_mode = Mirror::Horizontal;
         self.prg_bank_mode = false;
         self.chr_inversion = false;
@@ -235,10 +238,6 @@ impl Mapper for Mapper004 {
         self.irq_active
     }
 
-    fn irq_clear(&mut self) {
-        self.irq_active = false;
-    }
-
     fn on_scanline_end(&mut self) {
         match self.irq_counter {
             0 => {
@@ -255,4 +254,30 @@ impl Mapper for Mapper004 {
             }
         }
     }
+
+    fn debug_state(&self) -> MapperDebugState {
+        let bank_select = self.target_reg_idx as u8
+            | ((self.prg_bank_mode as u8) << 6)
+            | ((self.chr_inversion as u8) << 7);
+        MapperDebugState {
+            registers: vec![
+                ("bank_select", bank_select as u16),
+                ("r0", self.bank_reg[0] as u16),
+                ("r1", self.bank_reg[1] as u16),
+                ("r2", self.bank_reg[2] as u16),
+                ("r3", self.bank_reg[3] as u16),
+                ("r4", self.bank_reg[4] as u16),
+                ("r5", self.bank_reg[5] as u16),
+                ("r6", self.bank_reg[6] as u16),
+                ("r7", self.bank_reg[7] as u16),
+            ],
+            irq: Some(IrqDebugState {
+                counter: self.irq_counter as u16,
+                reload: self.irq_reload as u16,
+                enabled: self.irq_enable,
+                pending: self.irq_active,
+            }),
+            ..MapperDebugState::probe(self)
+        }
+    }
 }
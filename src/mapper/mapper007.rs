@@ -7,15 +7,20 @@ use serde::{Deserialize, Serialize};
 pub struct Mapper007 {
     num_banks_prg: usize,
     num_banks_chr: usize,
+    bus_conflicts: bool,
     prg_bank_select: usize,
     mirror_mode: Mirror,
 }
 
 impl Mapper007 {
-    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper007 {
+    /// submapper 1: ANROM (no bus conflicts), 2: AMROM (AND-type bus
+    /// conflicts), 0 / 3: unspecified / AOROM (no bus conflicts, see
+    /// `registry::force_bus_conflicts`)
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize, submapper: u8) -> Mapper007 {
         Mapper007 {
             num_banks_prg,
             num_banks_chr,
+            bus_conflicts: submapper == 2,
             prg_bank_select: 0,
             mirror_mode: Mirror::OneScreenLo,
         }
@@ -49,7 +54,15 @@ impl Mapper for Mapper007 {
         MapResult::None
     }
 
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
     fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x1fff => MapResult::MapAddr(addr as usize),
             _ => MapResult::None,
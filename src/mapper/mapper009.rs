@@ -1,4 +1,4 @@
-use super::{MapResult, Mapper};
+use super::{MapResult, Mapper, MapperDebugState};
 
 use crate::cartridge::Mirror;
 
@@ -107,6 +107,10 @@ impl Mapper for Mapper009 {
             _ => {}
         }
 
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
         match addr {
             0x0000..=0x0fff => {
                 if self.latch_lo {
@@ -141,4 +145,19 @@ impl Mapper for Mapper009 {
     fn mirror(&self) -> Mirror {
         self.mirror_mode
     }
+
+    fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState {
+            registers: vec![
+                ("prg", self.prg_bank_select_8k as u16),
+                ("chr_lo_fd", self.chr_bank_select_4k_lo_fd as u16),
+                ("chr_lo_fe", self.chr_bank_select_4k_lo_fe as u16),
+                ("chr_hi_fd", self.chr_bank_select_4k_hi_fd as u16),
+                ("chr_hi_fe", self.chr_bank_select_4k_hi_fe as u16),
+                ("latch_lo", if self.latch_lo { 0xfe } else { 0xfd }),
+                ("latch_hi", if self.latch_hi { 0xfe } else { 0xfd }),
+            ],
+            ..MapperDebugState::probe(self)
+        }
+    }
 }
@@ -0,0 +1,322 @@
+use super::eeprom::{Eeprom, EepromType};
+use super::{IrqDebugState, MapResult, Mapper, MapperDebugState};
+
+use crate::cartridge::Mirror;
+
+use serde::{Deserialize, Serialize};
+use serde_big_array::BigArray;
+
+/// Board variants of the Bandai FCG family
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum BandaiBoard {
+    /// FCG-1/2: registers at $6000-$7fff, IRQ counter written directly
+    Fcg,
+    /// LZ93D50 without save chip or with 24C02 EEPROM (mapper 016)
+    Lz93d50,
+    /// FCG-1/2 or LZ93D50 (mapper 016 submapper 0): registers decoded in
+    /// both ranges, 24C02 EEPROM
+    Unspecified,
+    /// LZ93D50 with 8 KiB battery SRAM and 512 KiB PRG (mapper 153)
+    Sram,
+    /// Datach Joint ROM System: LZ93D50, 24C02 EEPROM on the base unit plus
+    /// an optional 24C01 EEPROM on the game cartridge (mapper 157)
+    Datach,
+    /// LZ93D50 with 24C01 EEPROM (mapper 159)
+    Lz93d50X24C01,
+}
+
+impl BandaiBoard {
+    /// board for mapper 016 by NES 2.0 submapper
+    pub fn from_submapper(submapper: u8) -> BandaiBoard {
+        match submapper {
+            4 => BandaiBoard::Fcg,
+            5 => BandaiBoard::Lz93d50,
+            _ => BandaiBoard::Unspecified,
+        }
+    }
+}
+
+/// Bandai FCG-1/2, LZ93D50 and derived boards
+#[derive(Deserialize, Serialize)]
+pub struct Mapper016 {
+    num_banks_prg: usize,
+    num_banks_chr: usize,
+    board: BandaiBoard,
+
+    #[serde(with = "BigArray")]
+    prg_ram: [u8; 8 * 1024],
+    prg_ram_enable: bool,
+
+    /// on-board save EEPROM
+    eeprom: Option<Eeprom>,
+    /// 24C01 on Datach game cartridges, clocked by CHR register bit 3
+    eeprom_ext: Option<Eeprom>,
+    eeprom_ext_scl: bool,
+    eeprom_scl: bool,
+    eeprom_sda: bool,
+    eeprom_read_enable: bool,
+
+    chr_reg: [u8; 8],
+    prg_bank_select_16: usize,
+    mirror_mode: Mirror,
+
+    irq_enable: bool,
+    irq_active: bool,
+    irq_counter: u16,
+    irq_latch: u16,
+}
+
+impl Mapper016 {
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize, board: BandaiBoard) -> Mapper016 {
+        let eeprom = match board {
+            BandaiBoard::Unspecified | BandaiBoard::Lz93d50 | BandaiBoard::Datach => {
+                Some(Eeprom::new(EepromType::C24C02))
+            }
+            BandaiBoard::Lz93d50X24C01 => Some(Eeprom::new(EepromType::X24C01)),
+            BandaiBoard::Fcg | BandaiBoard::Sram => None,
+        };
+        let eeprom_ext = match board {
+            BandaiBoard::Datach => Some(Eeprom::new(EepromType::X24C01)),
+            _ => None,
+        };
+
+        Mapper016 {
+            num_banks_prg,
+            num_banks_chr,
+            board,
+
+            prg_ram: [0; 8 * 1024],
+            prg_ram_enable: false,
+
+            eeprom,
+            eeprom_ext,
+            eeprom_ext_scl: false,
+            eeprom_scl: false,
+            eeprom_sda: false,
+            eeprom_read_enable: false,
+
+            chr_reg: [0; 8],
+            prg_bank_select_16: 0,
+            mirror_mode: Mirror::Vertical,
+
+            irq_enable: false,
+            irq_active: false,
+            irq_counter: 0,
+            irq_latch: 0,
+        }
+    }
+
+    fn registers_at_6000(&self) -> bool {
+        matches!(self.board, BandaiBoard::Fcg | BandaiBoard::Unspecified)
+    }
+
+    fn registers_at_8000(&self) -> bool {
+        self.board != BandaiBoard::Fcg
+    }
+
+    /// PRG bank offset for the 16K bank number within the current outer bank
+    fn prg_offset(&self, bank: usize) -> usize {
+        let outer = match self.board {
+            // 256K outer bank selected by bit 0 of the CHR registers
+            BandaiBoard::Sram => (self.chr_reg.iter().fold(0, |acc, r| acc | r) & 0x01) as usize,
+            _ => 0,
+        };
+        (((outer << 4) | bank) % self.num_banks_prg) * 0x4000
+    }
+
+    fn write_register(&mut self, reg: u16, data: u8) {
+        match reg {
+            0x0..=0x7 => {
+                self.chr_reg[reg as usize] = data;
+                if let Some(eeprom) = &mut self.eeprom_ext {
+                    self.eeprom_ext_scl = data & 0x08 != 0;
+                    eeprom.write(self.eeprom_ext_scl, self.eeprom_sda);
+                }
+            }
+            0x8 => self.prg_bank_select_16 = (data & 0x0f) as usize,
+            0x9 => {
+                self.mirror_mode = match data & 0x03 {
+                    0 => Mirror::Vertical,
+                    1 => Mirror::Horizontal,
+                    2 => Mirror::OneScreenLo,
+                    _ => Mirror::OneScreenHi,
+                }
+            }
+            0xa => {
+                self.irq_enable = data & 0x01 != 0;
+                self.irq_active = false;
+                if self.board != BandaiBoard::Fcg {
+                    // LZ93D50 loads the counter from the latch
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0xb => {
+                if self.board == BandaiBoard::Fcg {
+                    self.irq_counter = (self.irq_counter & 0xff00) | data as u16;
+                } else {
+                    self.irq_latch = (self.irq_latch & 0xff00) | data as u16;
+                }
+            }
+            0xc => {
+                if self.board == BandaiBoard::Fcg {
+                    self.irq_counter = (self.irq_counter & 0x00ff) | (data as u16) << 8;
+                } else {
+                    self.irq_latch = (self.irq_latch & 0x00ff) | (data as u16) << 8;
+                }
+            }
+            0xd => {
+                if self.board == BandaiBoard::Sram {
+                    self.prg_ram_enable = data & 0x20 != 0;
+                } else {
+                    // EEPROM control: bit 5 SCL, bit 6 SDA, bit 7 read enable
+                    self.eeprom_scl = data & 0x20 != 0;
+                    self.eeprom_sda = data & 0x40 != 0;
+                    self.eeprom_read_enable = data & 0x80 != 0;
+                    if let Some(eeprom) = &mut self.eeprom {
+                        eeprom.write(self.eeprom_scl, self.eeprom_sda);
+                    }
+                    if let Some(eeprom) = &mut self.eeprom_ext {
+                        eeprom.write(self.eeprom_ext_scl, self.eeprom_sda);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// EEPROM data output on D4
+    fn eeprom_read(&self) -> u8 {
+        let mut sda = true;
+        if self.eeprom_read_enable {
+            if let Some(eeprom) = &self.eeprom {
+                sda &= eeprom.output();
+            }
+            if let Some(eeprom) = &self.eeprom_ext {
+                sda &= eeprom.output();
+            }
+        }
+        (sda as u8) << 4
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper016 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x6000..=0x7fff => match self.board {
+                BandaiBoard::Sram if self.prg_ram_enable => {
+                    MapResult::DirectRead(self.prg_ram[(addr & 0x1fff) as usize])
+                }
+                BandaiBoard::Sram | BandaiBoard::Fcg => MapResult::None,
+                // only D4 is driven, the other bits are open bus
+                _ => MapResult::PartialRead(self.eeprom_read(), 0x10),
+            },
+            0x8000..=0xbfff => MapResult::MapAddr(
+                self.prg_offset(self.prg_bank_select_16) + (addr & 0x3fff) as usize,
+            ),
+            0xc000..=0xffff => MapResult::MapAddr(self.prg_offset(0x0f) + (addr & 0x3fff) as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) -> MapResult {
+        match addr {
+            0x6000..=0x7fff if self.board == BandaiBoard::Sram => {
+                if self.prg_ram_enable {
+                    self.prg_ram[(addr & 0x1fff) as usize] = data;
+                }
+                MapResult::DirectWrite
+            }
+            0x6000..=0x7fff if self.registers_at_6000() => {
+                self.write_register(addr & 0x000f, data);
+                MapResult::DirectWrite
+            }
+            0x8000..=0xffff if self.registers_at_8000() => {
+                self.write_register(addr & 0x000f, data);
+                MapResult::DirectWrite
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => {
+                if self.num_banks_chr == 0 {
+                    MapResult::MapAddr(addr as usize)
+                } else {
+                    let bank = self.chr_reg[(addr >> 10) as usize] as usize;
+                    MapResult::MapAddr(
+                        (bank % (self.num_banks_chr * 8)) * 0x0400 + (addr & 0x03ff) as usize,
+                    )
+                }
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        if addr < 0x2000 && self.num_banks_chr == 0 {
+            // treat as RAM
+            MapResult::MapAddr(addr as usize)
+        } else {
+            MapResult::None
+        }
+    }
+
+    fn mirror(&self) -> Mirror {
+        self.mirror_mode
+    }
+
+    fn reset(&mut self) {
+        self.chr_reg = [0; 8];
+        self.prg_bank_select_16 = 0;
+        self.mirror_mode = Mirror::Vertical;
+        self.prg_ram_enable = false;
+
+        self.irq_enable = false;
+        self.irq_active = false;
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enable {
+            // the counter is checked before decrementing
+            if self.irq_counter == 0 {
+                self.irq_active = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn debug_state(&self) -> MapperDebugState {
+        let mut registers: Vec<(&'static str, u16)> = [
+            "chr0", "chr1", "chr2", "chr3", "chr4", "chr5", "chr6", "chr7",
+        ]
+        .into_iter()
+        .zip(self.chr_reg.iter().map(|&r| r as u16))
+        .collect();
+        registers.push(("prg", self.prg_bank_select_16 as u16));
+        MapperDebugState {
+            registers,
+            irq: Some(IrqDebugState {
+                counter: self.irq_counter,
+                reload: self.irq_latch,
+                enabled: self.irq_enable,
+                pending: self.irq_active,
+            }),
+            ..MapperDebugState::probe(self)
+        }
+    }
+}
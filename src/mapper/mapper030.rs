@@ -0,0 +1,127 @@
+use super::flash::Flash;
+use super::{MapResult, Mapper, MapperDebugState, Nametable};
+
+use crate::cartridge::Mirror;
+
+use serde::{Deserialize, Serialize};
+
+/// UNROM 512: UxROM style homebrew board with 32 KiB CHR-RAM and optional
+/// self-flashing PRG
+#[derive(Deserialize, Serialize)]
+pub struct Mapper030 {
+    num_banks_prg: usize,
+    bus_conflicts: bool,
+    /// present on flashable boards (battery flag in header)
+    flash: Option<Flash>,
+    /// header nametable configuration: horizontal, vertical, one-screen
+    /// (switchable) or four-screen (last 8 KiB of CHR-RAM)
+    nt_mode: Mirror,
+
+    prg_bank_select_lo: usize,
+    chr_bank_select: usize,
+    one_screen_hi: bool,
+}
+
+impl Mapper030 {
+    /// submapper 1: no bus conflicts, otherwise boards without flash have
+    /// AND-type bus conflicts
+    pub fn new(num_banks_prg: usize, submapper: u8, flashable: bool, nt_mode: Mirror) -> Mapper030 {
+        Mapper030 {
+            num_banks_prg,
+            bus_conflicts: !flashable && submapper != 1,
+            flash: flashable.then(|| Flash::new(num_banks_prg * 0x4000)),
+            nt_mode,
+
+            prg_bank_select_lo: 0,
+            chr_bank_select: 0,
+            one_screen_hi: false,
+        }
+    }
+
+    fn prg_read(&self, chip_addr: usize) -> MapResult {
+        match &self.flash {
+            Some(flash) => flash.read(chip_addr),
+            None => MapResult::MapAddr(chip_addr),
+        }
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper030 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x8000..=0xbfff => {
+                self.prg_read(self.prg_bank_select_lo * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            0xc000..=0xffff => {
+                self.prg_read((self.num_banks_prg - 1) * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) -> MapResult {
+        match (addr, &mut self.flash) {
+            (0x8000..=0xbfff, Some(flash)) => flash.write(
+                self.prg_bank_select_lo * 0x4000 + (addr & 0x3fff) as usize,
+                data,
+            ),
+            (0x8000..=0xffff, _) => {
+                // [MCCP PPPP]: one-screen select, CHR bank, PRG bank
+                self.prg_bank_select_lo = (data & 0x1f) as usize % self.num_banks_prg;
+                self.chr_bank_select = ((data >> 5) & 0x03) as usize;
+                self.one_screen_hi = data & 0x80 != 0;
+                MapResult::DirectWrite
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        self.bus_conflicts
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn mirror(&self) -> Mirror {
+        self.nt_mode
+    }
+
+    fn nametable(&self, slot: usize) -> Option<Nametable> {
+        match self.nt_mode {
+            Mirror::OneScreenLo => Some(Nametable::Ciram(self.one_screen_hi as usize)),
+            Mirror::FourScreen => Some(Nametable::Chr(0x6000 + slot * 0x0400)),
+            _ => None,
+        }
+    }
+
+    fn debug_state(&self) -> MapperDebugState {
+        let reg = self.prg_bank_select_lo as u16
+            | (self.chr_bank_select as u16) << 5
+            | (self.one_screen_hi as u16) << 7;
+        MapperDebugState {
+            registers: vec![("bank", reg)],
+            ..MapperDebugState::probe(self)
+        }
+    }
+}
@@ -0,0 +1,68 @@
+use super::{MapResult, Mapper};
+
+use serde::{Deserialize, Serialize};
+
+/// Reset-based 4-in-1 NROM-128 multicarts: every console reset selects the
+/// next game.
+#[derive(Deserialize, Serialize)]
+pub struct Mapper060 {
+    num_banks_prg: usize,
+    num_banks_chr: usize,
+    game_select: usize,
+}
+
+impl Mapper060 {
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper060 {
+        Mapper060 {
+            num_banks_prg,
+            num_banks_chr,
+            game_select: 0,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper060 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x8000..=0xffff => MapResult::MapAddr(
+                (self.game_select % self.num_banks_prg) * 0x4000 + (addr & 0x3fff) as usize,
+            ),
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, _addr: u16, _data: u8) -> MapResult {
+        MapResult::None
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(
+                (self.game_select % self.num_banks_chr.max(1)) * 0x2000 + addr as usize,
+            ),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        if addr < 0x2000 && self.num_banks_chr == 0 {
+            // treat as RAM
+            MapResult::MapAddr(addr as usize)
+        } else {
+            MapResult::None
+        }
+    }
+
+    fn reset(&mut self) {
+        self.game_select = (self.game_select + 1) & 0x03;
+    }
+}
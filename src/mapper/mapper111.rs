@@ -0,0 +1,98 @@
+use super::flash::Flash;
+use super::{MapResult, Mapper, MapperDebugState, Nametable};
+
+use serde::{Deserialize, Serialize};
+
+/// GTROM (Cheapocabra): 512 KiB self-flashing PRG in 32K banks, 32 KiB RAM
+/// holding two CHR banks and two sets of four nametables
+#[derive(Deserialize, Serialize)]
+pub struct Mapper111 {
+    num_banks_prg: usize,
+    flash: Flash,
+
+    /// [RGNC PPPP]: red / green LED, nametable bank, CHR bank, PRG bank
+    reg: u8,
+}
+
+impl Mapper111 {
+    pub fn new(num_banks_prg: usize) -> Mapper111 {
+        Mapper111 {
+            num_banks_prg,
+            flash: Flash::new(num_banks_prg * 0x4000),
+
+            reg: 0,
+        }
+    }
+
+    fn prg_bank_select_32(&self) -> usize {
+        (self.reg & 0x0f) as usize % (self.num_banks_prg >> 1).max(1)
+    }
+
+    fn chr_bank_select(&self) -> usize {
+        ((self.reg >> 4) & 0x01) as usize
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper111 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x8000..=0xffff => self
+                .flash
+                .read(self.prg_bank_select_32() * 0x8000 + (addr & 0x7fff) as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) -> MapResult {
+        match addr {
+            0x5000..=0x5fff | 0x7000..=0x7fff => {
+                self.reg = data;
+                MapResult::DirectWrite
+            }
+            0x8000..=0xffff => {
+                let chip_addr = self.prg_bank_select_32() * 0x8000 + (addr & 0x7fff) as usize;
+                self.flash.write(chip_addr, data)
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select() * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select() * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn nametable(&self, slot: usize) -> Option<Nametable> {
+        let nt_bank = ((self.reg >> 5) & 0x01) as usize;
+        Some(Nametable::Chr(0x4000 + nt_bank * 0x2000 + slot * 0x0400))
+    }
+
+    fn reset(&mut self) {
+        self.reg = 0;
+    }
+
+    fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState {
+            registers: vec![("bank", self.reg as u16)],
+            ..MapperDebugState::probe(self)
+        }
+    }
+}
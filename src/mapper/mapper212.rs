@@ -0,0 +1,119 @@
+use super::{MapResult, Mapper};
+
+use crate::cartridge::Mirror;
+
+use serde::{Deserialize, Serialize};
+
+/// BMC Super HiK 300-in-1 and similar "XX-in-1" NROM multicarts
+#[derive(Deserialize, Serialize)]
+pub struct Mapper212 {
+    num_banks_prg: usize,
+    num_banks_chr: usize,
+
+    prg_bank_select_16_lo: usize,
+    prg_bank_select_16_hi: usize,
+    chr_bank_select: usize,
+    mirror_mode: Mirror,
+}
+
+impl Mapper212 {
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper212 {
+        let mut mapper = Mapper212 {
+            num_banks_prg,
+            num_banks_chr,
+
+            prg_bank_select_16_lo: 0,
+            prg_bank_select_16_hi: 0,
+            chr_bank_select: 0,
+            mirror_mode: Mirror::Vertical,
+        };
+        mapper.write_reg(0xc000);
+        mapper
+    }
+
+    fn write_reg(&mut self, addr: u16) {
+        // address: [.O.. .... .... MBBB]
+        // O: PRG mode (0: 16K, 1: 32K), M: mirroring (0: vertical, 1: horizontal),
+        // BBB: 16K PRG bank / 8K CHR bank (bits 2-1: 32K PRG bank)
+        let bank = (addr & 0x07) as usize;
+        if addr & 0x4000 != 0 {
+            // 32K mode
+            self.prg_bank_select_16_lo = (bank & !0x01) % self.num_banks_prg;
+            self.prg_bank_select_16_hi = (bank | 0x01) % self.num_banks_prg;
+        } else {
+            // 16K mode
+            self.prg_bank_select_16_lo = bank % self.num_banks_prg;
+            self.prg_bank_select_16_hi = bank % self.num_banks_prg;
+        }
+
+        if self.num_banks_chr > 0 {
+            self.chr_bank_select = bank % self.num_banks_chr;
+        }
+
+        self.mirror_mode = if addr & 0x0008 != 0 {
+            Mirror::Horizontal
+        } else {
+            Mirror::Vertical
+        };
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper212 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            // menu checks D7 to tell the board revision apart, D0-D6 are
+            // open bus
+            0x6000..=0x7fff if addr & 0x0010 == 0 => MapResult::PartialRead(0x80, 0x80),
+            0x8000..=0xbfff => {
+                MapResult::MapAddr(self.prg_bank_select_16_lo * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            0xc000..=0xffff => {
+                MapResult::MapAddr(self.prg_bank_select_16_hi * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        match addr {
+            0x8000..=0xffff => {
+                self.write_reg(addr);
+                MapResult::DirectWrite
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        if addr < 0x2000 && self.num_banks_chr == 0 {
+            // treat as RAM
+            MapResult::MapAddr(addr as usize)
+        } else {
+            MapResult::None
+        }
+    }
+
+    fn mirror(&self) -> Mirror {
+        self.mirror_mode
+    }
+
+    fn reset(&mut self) {
+        self.write_reg(0xc000);
+    }
+}
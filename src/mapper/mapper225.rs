@@ -0,0 +1,126 @@
+use super::{MapResult, Mapper};
+
+use crate::cartridge::Mirror;
+
+use serde::{Deserialize, Serialize};
+
+/// ET-4320 and compatible "52-in-1" / "64-in-1" / "72-in-1" multicarts
+#[derive(Deserialize, Serialize)]
+pub struct Mapper225 {
+    num_banks_prg: usize,
+    num_banks_chr: usize,
+
+    /// 4 x 4 bit RAM at $5800-$5fff (D4-D7 open bus), survives reset (used
+    /// by menus)
+    ram: [u8; 4],
+
+    prg_bank_select_16_lo: usize,
+    prg_bank_select_16_hi: usize,
+    chr_bank_select: usize,
+    mirror_mode: Mirror,
+}
+
+impl Mapper225 {
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper225 {
+        Mapper225 {
+            num_banks_prg,
+            num_banks_chr,
+
+            ram: [0; 4],
+
+            prg_bank_select_16_lo: 0,
+            prg_bank_select_16_hi: 1 % num_banks_prg,
+            chr_bank_select: 0,
+            mirror_mode: Mirror::Vertical,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper225 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x5800..=0x5fff => MapResult::PartialRead(self.ram[(addr & 0x0003) as usize], 0x0f),
+            0x8000..=0xbfff => {
+                MapResult::MapAddr(self.prg_bank_select_16_lo * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            0xc000..=0xffff => {
+                MapResult::MapAddr(self.prg_bank_select_16_hi * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) -> MapResult {
+        match addr {
+            0x5800..=0x5fff => {
+                self.ram[(addr & 0x0003) as usize] = data & 0x0f;
+                MapResult::DirectWrite
+            }
+            0x8000..=0xffff => {
+                // register is latched from the address lines:
+                // A14: outer bank, A13: mirroring, A12: PRG mode,
+                // A11-A6: PRG bank, A5-A0: CHR bank
+                let outer = ((addr >> 14) & 0x01) as usize;
+                let prg_bank = ((addr >> 6) & 0x3f) as usize | (outer << 6);
+                if addr & 0x1000 != 0 {
+                    // 16K mode
+                    self.prg_bank_select_16_lo = prg_bank % self.num_banks_prg;
+                    self.prg_bank_select_16_hi = prg_bank % self.num_banks_prg;
+                } else {
+                    // 32K mode
+                    self.prg_bank_select_16_lo = (prg_bank & !0x01) % self.num_banks_prg;
+                    self.prg_bank_select_16_hi = (prg_bank | 0x01) % self.num_banks_prg;
+                }
+
+                if self.num_banks_chr > 0 {
+                    self.chr_bank_select =
+                        ((addr & 0x3f) as usize | (outer << 6)) % self.num_banks_chr;
+                }
+
+                self.mirror_mode = if addr & 0x2000 != 0 {
+                    Mirror::Horizontal
+                } else {
+                    Mirror::Vertical
+                };
+                MapResult::DirectWrite
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(self.chr_bank_select * 0x2000 + addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        if addr < 0x2000 && self.num_banks_chr == 0 {
+            // treat as RAM
+            MapResult::MapAddr(addr as usize)
+        } else {
+            MapResult::None
+        }
+    }
+
+    fn mirror(&self) -> Mirror {
+        self.mirror_mode
+    }
+
+    fn reset(&mut self) {
+        self.prg_bank_select_16_lo = 0;
+        self.prg_bank_select_16_hi = 1 % self.num_banks_prg;
+        self.chr_bank_select = 0;
+        self.mirror_mode = Mirror::Vertical;
+    }
+}
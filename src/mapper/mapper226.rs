@@ -0,0 +1,125 @@
+use super::{MapResult, Mapper, MapperDebugState};
+
+use crate::cartridge::Mirror;
+
+use serde::{Deserialize, Serialize};
+
+/// "76-in-1" / "42-in-1" multicarts with CHR RAM
+#[derive(Deserialize, Serialize)]
+pub struct Mapper226 {
+    num_banks_prg: usize,
+    num_banks_chr: usize,
+
+    /// $8000 (even) / $8001 (odd) registers
+    reg: [u8; 2],
+
+    prg_bank_select_16_lo: usize,
+    prg_bank_select_16_hi: usize,
+    mirror_mode: Mirror,
+}
+
+impl Mapper226 {
+    pub fn new(num_banks_prg: usize, num_banks_chr: usize) -> Mapper226 {
+        Mapper226 {
+            num_banks_prg,
+            num_banks_chr,
+
+            reg: [0; 2],
+
+            prg_bank_select_16_lo: 0,
+            prg_bank_select_16_hi: 1 % num_banks_prg,
+            mirror_mode: Mirror::Horizontal,
+        }
+    }
+
+    fn update_banks(&mut self) {
+        // reg 0: [PMOP PPPP], reg 1: [.... ...Q]
+        // P: PRG bank bits 0-5, Q: PRG bank bit 6 (outer bank),
+        // M: mirroring (0: horizontal, 1: vertical), O: PRG mode (0: 32K, 1: 16K)
+        let prg_bank = (self.reg[0] & 0x1f) as usize
+            | (((self.reg[0] & 0x80) >> 2) as usize)
+            | (((self.reg[1] & 0x01) as usize) << 6);
+
+        if self.reg[0] & 0x20 != 0 {
+            // 16K mode
+            self.prg_bank_select_16_lo = prg_bank % self.num_banks_prg;
+            self.prg_bank_select_16_hi = prg_bank % self.num_banks_prg;
+        } else {
+            // 32K mode
+            self.prg_bank_select_16_lo = (prg_bank & !0x01) % self.num_banks_prg;
+            self.prg_bank_select_16_hi = (prg_bank | 0x01) % self.num_banks_prg;
+        }
+
+        self.mirror_mode = if self.reg[0] & 0x40 != 0 {
+            Mirror::Vertical
+        } else {
+            Mirror::Horizontal
+        };
+    }
+}
+
+#[typetag::serde]
+impl Mapper for Mapper226 {
+    fn cpu_map_read(&mut self, addr: u16) -> MapResult {
+        self.cpu_map_read_ro(addr)
+    }
+
+    fn cpu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x8000..=0xbfff => {
+                MapResult::MapAddr(self.prg_bank_select_16_lo * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            0xc000..=0xffff => {
+                MapResult::MapAddr(self.prg_bank_select_16_hi * 0x4000 + (addr & 0x3fff) as usize)
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn cpu_map_write(&mut self, addr: u16, data: u8) -> MapResult {
+        match addr {
+            0x8000..=0xffff => {
+                self.reg[(addr & 0x0001) as usize] = data;
+                self.update_banks();
+                MapResult::DirectWrite
+            }
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_read(&mut self, addr: u16) -> MapResult {
+        self.ppu_map_read_ro(addr)
+    }
+
+    fn ppu_map_read_ro(&self, addr: u16) -> MapResult {
+        match addr {
+            0x0000..=0x1fff => MapResult::MapAddr(addr as usize),
+            _ => MapResult::None,
+        }
+    }
+
+    fn ppu_map_write(&mut self, addr: u16, _data: u8) -> MapResult {
+        if addr < 0x2000 && self.num_banks_chr == 0 {
+            // treat as RAM
+            MapResult::MapAddr(addr as usize)
+        } else {
+            MapResult::None
+        }
+    }
+
+    fn mirror(&self) -> Mirror {
+        self.mirror_mode
+    }
+
+    fn reset(&mut self) {
+        self.reg = [0; 2];
+        self.update_banks();
+    }
+
+    fn debug_state(&self) -> MapperDebugState {
+        MapperDebugState {
+            registers: vec![("reg0", self.reg[0] as u16), ("reg1", self.reg[1] as u16)],
+            ..MapperDebugState::probe(self)
+        }
+    }
+}
@@ -0,0 +1,329 @@
+use super::{
+    AudioChip, Mapper, mapper000::Mapper000, mapper001::Mapper001, mapper002::Mapper002,
+    mapper003::Mapper003, mapper004::Mapper004, mapper007::Mapper007, mapper009::Mapper009,
+    mapper016::BandaiBoard, mapper016::Mapper016, mapper030::Mapper030, mapper060::Mapper060,
+    mapper111::Mapper111, mapper212::Mapper212, mapper225::Mapper225, mapper226::Mapper226,
+    mapper228::Mapper228,
+};
+
+use crate::cartridge::Mirror;
+
+use std::sync::{LazyLock, RwLock};
+
+/// Cartridge properties from the ROM header passed to a mapper factory
+#[derive(Copy, Clone, Debug)]
+pub struct MapperParams {
+    /// number of 16 KiB PRG ROM banks
+    pub num_banks_prg: usize,
+    /// number of 8 KiB CHR ROM banks (0: CHR RAM)
+    pub num_banks_chr: usize,
+    /// NES 2.0 submapper (0 for iNES headers)
+    pub submapper: u8,
+    /// battery-backed memory present
+    pub battery: bool,
+    /// solder pad mirroring (flags 6, bit 0)
+    pub vertical_mirroring: bool,
+    /// alternative nametable layout, usually four-screen (flags 6, bit 3)
+    pub alt_nametables: bool,
+}
+
+pub type MapperFactory = fn(&MapperParams) -> Box<dyn Mapper>;
+
+/// Board metadata of a registered mapper
+#[derive(Copy, Clone, Debug)]
+pub struct BoardInfo {
+    pub mapper_id: u16,
+    /// `None`: board applies to all submappers without a dedicated entry
+    pub submapper: Option<u8>,
+    pub name: &'static str,
+    /// supported PRG RAM sizes in bytes (empty if none)
+    pub prg_ram_sizes: &'static [usize],
+    /// CHR RAM size in bytes for boards without CHR ROM, unless given by
+    /// the NES 2.0 header
+    pub chr_ram_size: usize,
+    /// board can assert the IRQ line
+    pub has_irq: bool,
+    pub audio_chip: Option<AudioChip>,
+    /// the mapper interprets the alternative nametable bit (flags 6, bit 3)
+    /// itself, instead of four-screen RAM on the cartridge
+    pub mapper_nametables: bool,
+}
+
+impl BoardInfo {
+    pub const fn new(mapper_id: u16, name: &'static str) -> BoardInfo {
+        BoardInfo {
+            mapper_id,
+            submapper: None,
+            name,
+            prg_ram_sizes: &[],
+            chr_ram_size: 8 * 1024,
+            has_irq: false,
+            audio_chip: None,
+            mapper_nametables: false,
+        }
+    }
+
+    pub const fn submapper(mut self, submapper: u8) -> BoardInfo {
+        self.submapper = Some(submapper);
+        self
+    }
+
+    pub const fn prg_ram(mut self, sizes: &'static [usize]) -> BoardInfo {
+        self.prg_ram_sizes = sizes;
+        self
+    }
+
+    pub const fn chr_ram(mut self, size: usize) -> BoardInfo {
+        self.chr_ram_size = size;
+        self
+    }
+
+    pub const fn irq(mut self) -> BoardInfo {
+        self.has_irq = true;
+        self
+    }
+
+    pub const fn audio(mut self, chip: AudioChip) -> BoardInfo {
+        self.audio_chip = Some(chip);
+        self
+    }
+
+    pub const fn mapper_nametables(mut self) -> BoardInfo {
+        self.mapper_nametables = true;
+        self
+    }
+}
+
+struct Entry {
+    info: BoardInfo,
+    factory: MapperFactory,
+}
+
+static REGISTRY: LazyLock<RwLock<Vec<Entry>>> = LazyLock::new(|| RwLock::new(builtin()));
+
+fn builtin() -> Vec<Entry> {
+    let entries: [(BoardInfo, MapperFactory); 18] = [
+        (BoardInfo::new(0, "NROM"), |p| {
+            Box::new(Mapper000::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (
+            BoardInfo::new(1, "MMC1 (SxROM)").prg_ram(&[8 * 1024]),
+            |p| Box::new(Mapper001::new(p.num_banks_prg, p.num_banks_chr)),
+        ),
+        (BoardInfo::new(2, "UxROM"), |p| {
+            Box::new(Mapper002::new(
+                p.num_banks_prg,
+                p.num_banks_chr,
+                p.submapper,
+            ))
+        }),
+        (BoardInfo::new(3, "CNROM"), |p| {
+            Box::new(Mapper003::new(
+                p.num_banks_prg,
+                p.num_banks_chr,
+                p.submapper,
+            ))
+        }),
+        (
+            BoardInfo::new(4, "MMC3 (TxROM)").prg_ram(&[8 * 1024]).irq(),
+            |p| Box::new(Mapper004::new(p.num_banks_prg, p.num_banks_chr)),
+        ),
+        (BoardInfo::new(7, "AxROM"), |p| {
+            Box::new(Mapper007::new(
+                p.num_banks_prg,
+                p.num_banks_chr,
+                p.submapper,
+            ))
+        }),
+        (BoardInfo::new(9, "MMC2 (PxROM)"), |p| {
+            Box::new(Mapper009::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (BoardInfo::new(16, "Bandai FCG / LZ93D50").irq(), |p| {
+            Box::new(Mapper016::new(
+                p.num_banks_prg,
+                p.num_banks_chr,
+                BandaiBoard::from_submapper(p.submapper),
+            ))
+        }),
+        (
+            BoardInfo::new(30, "UNROM 512")
+                .chr_ram(32 * 1024)
+                .mapper_nametables(),
+            |p| {
+                // one-screen (switchable) / four-screen encoded in the header
+                let nt_mode = match (p.alt_nametables, p.vertical_mirroring) {
+                    (false, false) => Mirror::Horizontal,
+                    (false, true) => Mirror::Vertical,
+                    (true, false) => Mirror::OneScreenLo,
+                    (true, true) => Mirror::FourScreen,
+                };
+                Box::new(Mapper030::new(
+                    p.num_banks_prg,
+                    p.submapper,
+                    p.battery,
+                    nt_mode,
+                ))
+            },
+        ),
+        (BoardInfo::new(60, "Reset-based NROM-128 4-in-1"), |p| {
+            Box::new(Mapper060::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (BoardInfo::new(111, "GTROM").chr_ram(32 * 1024), |p| {
+            Box::new(Mapper111::new(p.num_banks_prg))
+        }),
+        (
+            BoardInfo::new(153, "Bandai LZ93D50 with SRAM")
+                .prg_ram(&[8 * 1024])
+                .irq(),
+            |p| {
+                Box::new(Mapper016::new(
+                    p.num_banks_prg,
+                    p.num_banks_chr,
+                    BandaiBoard::Sram,
+                ))
+            },
+        ),
+        (BoardInfo::new(157, "Bandai Datach").irq(), |p| {
+            Box::new(Mapper016::new(
+                p.num_banks_prg,
+                p.num_banks_chr,
+                BandaiBoard::Datach,
+            ))
+        }),
+        (
+            BoardInfo::new(159, "Bandai LZ93D50 with 24C01").irq(),
+            |p| {
+                Box::new(Mapper016::new(
+                    p.num_banks_prg,
+                    p.num_banks_chr,
+                    BandaiBoard::Lz93d50X24C01,
+                ))
+            },
+        ),
+        (BoardInfo::new(212, "BMC Super HiK 300-in-1"), |p| {
+            Box::new(Mapper212::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (BoardInfo::new(225, "ET-4320 multicart"), |p| {
+            Box::new(Mapper225::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (BoardInfo::new(226, "BMC 76-in-1"), |p| {
+            Box::new(Mapper226::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+        (BoardInfo::new(228, "Action 52"), |p| {
+            Box::new(Mapper228::new(p.num_banks_prg, p.num_banks_chr))
+        }),
+    ];
+
+    entries
+        .into_iter()
+        .map(|(info, factory)| Entry { info, factory })
+        .collect()
+}
+
+/// Register a board. Entries registered later take precedence over existing
+/// entries (including built-in boards) for the same mapper / submapper.
+pub fn register(info: BoardInfo, factory: MapperFactory) {
+    REGISTRY.write().unwrap().push(Entry { info, factory });
+}
+
+/// Emulate AND-type bus conflicts (submapper 2) for UxROM, CNROM and AxROM
+/// images without a submapper, which run without conflicts by default. Real
+/// boards of these types often have conflicts, so homebrew builds can be
+/// tested against them.
+pub fn force_bus_conflicts() {
+    register(BoardInfo::new(2, "UxROM").submapper(0), |p| {
+        Box::new(Mapper002::new(p.num_banks_prg, p.num_banks_chr, 2))
+    });
+    register(BoardInfo::new(3, "CNROM").submapper(0), |p| {
+        Box::new(Mapper003::new(p.num_banks_prg, p.num_banks_chr, 2))
+    });
+    register(BoardInfo::new(7, "AxROM").submapper(0), |p| {
+        Box::new(Mapper007::new(p.num_banks_prg, p.num_banks_chr, 2))
+    });
+}
+
+/// Find the board for a mapper / submapper: an entry for the exact submapper
+/// is preferred over a generic entry for the mapper.
+pub fn lookup(mapper_id: u16, submapper: u8) -> Option<(BoardInfo, MapperFactory)> {
+    let registry = REGISTRY.read().unwrap();
+    let candidates = || {
+        registry
+            .iter()
+            .rev()
+            .filter(|e| e.info.mapper_id == mapper_id)
+    };
+    candidates()
+        .find(|e| e.info.submapper == Some(submapper))
+        .or_else(|| candidates().find(|e| e.info.submapper.is_none()))
+        .map(|e| (e.info, e.factory))
+}
+
+/// All registered boards, ordered by mapper / submapper
+pub fn boards() -> Vec<BoardInfo> {
+    let mut boards: Vec<BoardInfo> = REGISTRY.read().unwrap().iter().map(|e| e.info).collect();
+    boards.sort_by_key(|b| (b.mapper_id, b.submapper));
+    boards
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// mapper number not used by real boards (the registry is shared by
+    /// all tests)
+    const TEST_MAPPER: u16 = 4000;
+
+    fn factory(p: &MapperParams) -> Box<dyn Mapper> {
+        Box::new(Mapper000::new(p.num_banks_prg, p.num_banks_chr))
+    }
+
+    fn name(mapper_id: u16, submapper: u8) -> Option<&'static str> {
+        lookup(mapper_id, submapper).map(|(info, _)| info.name)
+    }
+
+    #[test]
+    fn builtin_lookup() {
+        assert_eq!(name(4, 0), Some("MMC3 (TxROM)"));
+        // generic entry for any submapper
+        assert_eq!(name(4, 3), Some("MMC3 (TxROM)"));
+        assert_eq!(
+            lookup(30, 0).map(|(info, _)| info.chr_ram_size),
+            Some(0x8000)
+        );
+        assert_eq!(name(5, 0), None);
+
+        let info = |mapper_id| lookup(mapper_id, 0).unwrap().0;
+        for mapper_id in [1, 4, 153] {
+            assert_eq!(info(mapper_id).prg_ram_sizes, &[0x2000], "{mapper_id}");
+        }
+        for mapper_id in [0, 2, 16, 30, 159] {
+            assert!(info(mapper_id).prg_ram_sizes.is_empty(), "{mapper_id}");
+        }
+        for mapper_id in [4, 16, 153, 157, 159] {
+            assert!(info(mapper_id).has_irq, "{mapper_id}");
+        }
+        for mapper_id in [0, 1, 30] {
+            assert!(!info(mapper_id).has_irq, "{mapper_id}");
+        }
+        assert!(boards().iter().all(|b| b.audio_chip.is_none()));
+
+        let boards = boards();
+        assert!(boards.windows(2).all(|b| b[0].mapper_id <= b[1].mapper_id));
+    }
+
+    #[test]
+    fn register_precedence() {
+        register(BoardInfo::new(TEST_MAPPER, "generic"), factory);
+        register(
+            BoardInfo::new(TEST_MAPPER, "submapper 1").submapper(1),
+            factory,
+        );
+        assert_eq!(name(TEST_MAPPER, 1), Some("submapper 1"));
+        assert_eq!(name(TEST_MAPPER, 2), Some("generic"));
+
+        // a later registration replaces the earlier one
+        register(BoardInfo::new(TEST_MAPPER, "generic 2"), factory);
+        assert_eq!(name(TEST_MAPPER, 2), Some("generic 2"));
+        assert_eq!(name(TEST_MAPPER, 1), Some("submapper 1"));
+    }
+}
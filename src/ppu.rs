@@ -2,7 +2,9 @@ pub mod palette;
 
 use std::num::Wrapping;
 
-use crate::cartridge::{Cartridge, Mirror};
+use crate::cartridge::Cartridge;
+use crate::cdl;
+use crate::mapper::Nametable;
 use crate::system::TvStandard;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
@@ -52,7 +54,6 @@ pub struct Ppu {
 }
 
 pub struct PpuClockResult {
-    pub nmi: bool,
     pub set_pixel: Option<SetPixel>,
     pub frame_complete: bool,
 }
@@ -161,7 +162,6 @@ impl Ppu {
 
     pub fn clock(&mut self, cart: &mut Cartridge) -> PpuClockResult {
         let mut res = PpuClockResult {
-            nmi: false,
             set_pixel: None,
             frame_complete: false,
         };
@@ -221,7 +221,7 @@ impl Ppu {
                                 ((self.control.get_flag(ControlRegFlag::PatternBg) as u16) << 12)
                                     + ((self.bg_next_tile_id as u16) << 4)
                                     + self.vram_addr.fine_y();
-                            self.bg_next_tile_lsb = self.ppu_read(cart, read_addr);
+                            self.bg_next_tile_lsb = self.pattern_read(cart, read_addr);
                         }
                         6 => {
                             // Fetch next bg tile MSB bit plane
@@ -230,7 +230,7 @@ impl Ppu {
                                     + ((self.bg_next_tile_id as u16) << 4)
                                     + self.vram_addr.fine_y()
                                     + 8;
-                            self.bg_next_tile_msb = self.ppu_read(cart, read_addr);
+                            self.bg_next_tile_msb = self.pattern_read(cart, read_addr);
                         }
                         7 => {
                             self.increment_scroll_x();
@@ -343,8 +343,10 @@ impl Ppu {
                                 }
                             };
                         let sprite_pattern_addr_hi = sprite_pattern_addr_lo + 8;
-                        let sprite_pattern_bits_lo = self.ppu_read(cart, sprite_pattern_addr_lo);
-                        let sprite_pattern_bits_hi = self.ppu_read(cart, sprite_pattern_addr_hi);
+                        let sprite_pattern_bits_lo =
+                            self.pattern_read(cart, sprite_pattern_addr_lo);
+                        let sprite_pattern_bits_hi =
+                            self.pattern_read(cart, sprite_pattern_addr_hi);
 
                         let (sprite_pattern_bits_lo, sprite_pattern_bits_hi) =
                             if s.attrib() & 0x40 > 0 {
@@ -376,11 +378,7 @@ impl Ppu {
             241.. => {
                 if self.scanline == 241 && self.cycle == 1 {
                     // end of frame, start of vblank period
-                    // if configured, emit CPU NMI
                     self.status.set_flag(StatusRegFlag::VerticalBlank, true);
-                    if self.control.get_flag(ControlRegFlag::EnableNmi) {
-                        res.nmi = true;
-                    }
                 }
             }
             _ => {
@@ -608,6 +606,30 @@ impl Ppu {
         }
     }
 
+    /// current scanline and dot, the pre-render scanline is reported as
+    /// the last scanline of the frame (as in nestest.log)
+    pub fn position(&self) -> (isize, usize) {
+        let scanline = if self.scanline < 0 {
+            self.scanline_max + 1
+        } else {
+            self.scanline
+        };
+        (scanline, self.cycle)
+    }
+
+    /// current VRAM address (accessed through PPUDATA)
+    pub fn vram_addr(&self) -> u16 {
+        self.vram_addr.reg & 0x3fff
+    }
+
+    /// NMI output: active during vblank if enabled in the control register.
+    /// The CPU triggers on the rising edge, so enabling NMI during vblank
+    /// also raises an NMI.
+    pub fn nmi_line(&self) -> bool {
+        self.status.get_flag(StatusRegFlag::VerticalBlank)
+            && self.control.get_flag(ControlRegFlag::EnableNmi)
+    }
+
     pub fn cpu_read(&mut self, cart: &mut Cartridge, addr: u16) -> u8 {
         // Some PPU registers change the internal state (mut)
         // in response to a read
@@ -644,6 +666,7 @@ impl Ppu {
                 // get last read result from buffer
                 let mut data = self.ppu_data_buffer;
                 // store current read result in buffer
+                cart.cdl_log_ppu(self.vram_addr.reg & 0x3fff, cdl::CHR_READ);
                 self.ppu_data_buffer = self.ppu_read(cart, self.vram_addr.reg);
 
                 // special case: palette memory is returned without cycle delay
@@ -815,6 +838,12 @@ impl Ppu {
         color_idx as usize
     }
 
+    /// pattern table fetch for rendering
+    fn pattern_read(&self, cart: &mut Cartridge, addr: u16) -> u8 {
+        cart.cdl_log_ppu(addr, cdl::CHR_RENDERED);
+        self.ppu_read(cart, addr)
+    }
+
     fn ppu_read(&self, cart: &mut Cartridge, mut addr: u16) -> u8 {
         addr &= 0x3fff;
 
@@ -825,29 +854,12 @@ impl Ppu {
                 0x0000..=0x1fff => {
                     self.tbl_pattern[((addr & 0x1000) >> 12) as usize].b[(addr & 0x0fff) as usize]
                 }
-                0x2000..=0x3eff => {
-                    addr &= 0x0fff;
-
-                    match cart.mirror() {
-                        Mirror::Vertical => match addr {
-                            0x0000..=0x03ff | 0x0800..=0x0bff => {
-                                self.tbl_name[0].b[(addr & 0x03ff) as usize]
-                            }
-                            0x0400..=0x07ff | 0x0c00..=0x0fff => {
-                                self.tbl_name[1].b[(addr & 0x03ff) as usize]
-                            }
-                            _ => 0x00,
-                        },
-                        Mirror::Horizontal => match addr {
-                            0x0000..=0x07ff => self.tbl_name[0].b[(addr & 0x03ff) as usize],
-                            0x0800..=0x0fff => self.tbl_name[1].b[(addr & 0x03ff) as usize],
-                            _ => 0x00,
-                        },
-                        Mirror::OneScreenLo => self.tbl_name[0].b[(addr & 0x03ff) as usize],
-                        Mirror::OneScreenHi => self.tbl_name[1].b[(addr & 0x03ff) as usize],
-                        _ => 0x00,
+                0x2000..=0x3eff => match cart.nametable(((addr >> 10) & 0x03) as usize) {
+                    Nametable::Ciram(page) => {
+                        self.tbl_name[page & 0x01].b[(addr & 0x03ff) as usize]
                     }
-                }
+                    _ => cart.nametable_read(addr),
+                },
                 0x3f00..=0x3fff => {
                     addr &= 0x001f;
                     addr = match addr {
@@ -870,37 +882,14 @@ impl Ppu {
                     self.tbl_pattern[((addr & 0x1000) >> 12) as usize].b
                         [(addr & 0x0fff) as usize] = data;
                 }
-                0x2000..=0x3eff => {
-                    addr &= 0x0fff;
-
-                    match cart.mirror() {
-                        Mirror::Vertical => match addr {
-                            0x0000..=0x03ff | 0x0800..=0x0bff => {
-                                self.tbl_name[0].b[(addr & 0x03ff) as usize] = data;
-                            }
-                            0x0400..=0x07ff | 0x0c00..=0x0fff => {
-                                self.tbl_name[1].b[(addr & 0x03ff) as usize] = data;
-                            }
-                            _ => {}
-                        },
-                        Mirror::Horizontal => match addr {
-                            0x0000..=0x07ff => {
-                                self.tbl_name[0].b[(addr & 0x03ff) as usize] = data;
-                            }
-                            0x0800..=0x0fff => {
-                                self.tbl_name[1].b[(addr & 0x03ff) as usize] = data;
-                            }
-                            _ => {}
-                        },
-                        Mirror::OneScreenLo => {
-                            self.tbl_name[0].b[(addr & 0x03ff) as usize] = data;
-                        }
-                        Mirror::OneScreenHi => {
-                            self.tbl_name[1].b[(addr & 0x03ff) as usize] = data;
-                        }
-                        _ => {}
+                0x2000..=0x3eff => match cart.nametable(((addr >> 10) & 0x03) as usize) {
+                    Nametable::Ciram(page) => {
+                        self.tbl_name[page & 0x01].b[(addr & 0x03ff) as usize] = data;
                     }
-                }
+                    _ => {
+                        cart.nametable_write(addr, data);
+                    }
+                },
                 0x3f00..=0x3fff => {
                     addr &= 0x001f;
                     addr = match addr {
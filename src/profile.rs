@@ -0,0 +1,247 @@
+use crate::cpu::CallFrame;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// name of the call path root in reports
+const TOP_LEVEL: &str = "(top level)";
+
+/// number of instructions listed in the report
+const REPORT_INSTRUCTIONS: usize = 100;
+
+/// CPU execution profiler: cycles per instruction address and per call
+/// path (subroutines and interrupt handlers from the shadow call stack).
+///
+/// The cycles from one instruction start to the next are counted for the
+/// instruction, including DMA stalls and a following interrupt sequence.
+pub struct Profiler {
+    /// cycles by instruction address
+    pc_cycles: Vec<u64>,
+    /// cycles by call path (subroutine / handler addresses, outermost first)
+    stacks: HashMap<Vec<u16>, u64>,
+    /// number of calls by subroutine / handler address
+    calls: HashMap<u16, u64>,
+    frames: u64,
+    total: u64,
+    /// instruction in progress: address, CPU cycle at its start
+    current: Option<(u16, u64)>,
+    /// call path of the instruction in progress
+    path: Vec<u16>,
+}
+
+/// cycles spent in a subroutine / interrupt handler
+struct Subroutine {
+    addr: u16,
+    /// including callees
+    inclusive: u64,
+    exclusive: u64,
+    calls: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler {
+            pc_cycles: vec![0; 0x10000],
+            stacks: HashMap::new(),
+            calls: HashMap::new(),
+            frames: 0,
+            total: 0,
+            current: None,
+            path: Vec::new(),
+        }
+    }
+
+    /// Instruction at `pc` starts at CPU cycle `cycle`, with the call stack
+    /// `frames`
+    pub fn instruction(&mut self, pc: u16, cycle: u64, frames: &[CallFrame]) {
+        if let Some((addr, start)) = self.current {
+            let cycles = cycle - start;
+            self.pc_cycles[addr as usize] += cycles;
+            self.total += cycles;
+            match self.stacks.get_mut(self.path.as_slice()) {
+                Some(c) => *c += cycles,
+                None => {
+                    self.stacks.insert(self.path.clone(), cycles);
+                }
+            }
+        }
+
+        // a call adds one frame before the first instruction of the callee
+        if frames.len() > self.path.len()
+            && let Some(frame) = frames.last()
+        {
+            *self.calls.entry(frame.target).or_default() += 1;
+        }
+        self.path.clear();
+        self.path.extend(frames.iter().map(|f| f.target));
+        self.current = Some((pc, cycle));
+    }
+
+    /// video frame completed
+    pub fn frame(&mut self) {
+        self.frames += 1;
+    }
+
+    /// profiled CPU cycles
+    pub fn total_cycles(&self) -> u64 {
+        self.total
+    }
+
+    /// cycles counted for the instruction at `addr`
+    pub fn pc_cycles(&self, addr: u16) -> u64 {
+        self.pc_cycles[addr as usize]
+    }
+
+    /// subroutines by inclusive cycles, descending
+    fn subroutines(&self) -> Vec<Subroutine> {
+        let mut subs: HashMap<u16, Subroutine> = HashMap::new();
+        for (path, &cycles) in &self.stacks {
+            for (i, &addr) in path.iter().enumerate() {
+                let sub = subs.entry(addr).or_insert_with(|| Subroutine {
+                    addr,
+                    inclusive: 0,
+                    exclusive: 0,
+                    calls: self.calls.get(&addr).copied().unwrap_or(0),
+                });
+                // count recursive calls once
+                if !path[..i].contains(&addr) {
+                    sub.inclusive += cycles;
+                }
+                if i == path.len() - 1 {
+                    sub.exclusive += cycles;
+                }
+            }
+        }
+        let mut subs: Vec<Subroutine> = subs.into_values().collect();
+        subs.sort_by(|a, b| b.inclusive.cmp(&a.inclusive).then(a.addr.cmp(&b.addr)));
+        subs
+    }
+
+    /// Text report: cycles per subroutine and the most expensive
+    /// instructions, addresses named by `label`
+    pub fn report(&self, label: impl Fn(u16) -> Option<String>) -> String {
+        let name = |addr: u16| label(addr).unwrap_or_else(|| format!("${addr:04x}"));
+        let percent = |cycles: u64| 100.0 * cycles as f64 / self.total.max(1) as f64;
+
+        let mut s = String::new();
+        writeln!(s, "CPU cycles: {}, frames: {}", self.total, self.frames).unwrap();
+        if let Some(per_frame) = self.total.checked_div(self.frames) {
+            writeln!(s, "Cycles per frame: {per_frame}").unwrap();
+        }
+
+        writeln!(
+            s,
+            "\n   inclusive       %        self       %     calls  subroutine"
+        )
+        .unwrap();
+        let top_level = self.stacks.get([].as_slice()).copied().unwrap_or(0);
+        writeln!(
+            s,
+            "{:>12} {:6.2}% {:>11} {:6.2}% {:>9}  {TOP_LEVEL}",
+            self.total,
+            percent(self.total),
+            top_level,
+            percent(top_level),
+            ""
+        )
+        .unwrap();
+        for sub in self.subroutines() {
+            writeln!(
+                s,
+                "{:>12} {:6.2}% {:>11} {:6.2}% {:>9}  {}",
+                sub.inclusive,
+                percent(sub.inclusive),
+                sub.exclusive,
+                percent(sub.exclusive),
+                sub.calls,
+                name(sub.addr)
+            )
+            .unwrap();
+        }
+
+        writeln!(s, "\n      cycles       %  address").unwrap();
+        let mut pcs: Vec<u16> = (0..=0xffff).filter(|&a| self.pc_cycles(a) > 0).collect();
+        pcs.sort_by_key(|&a| std::cmp::Reverse(self.pc_cycles(a)));
+        for addr in pcs.into_iter().take(REPORT_INSTRUCTIONS) {
+            let cycles = self.pc_cycles(addr);
+            let addr_name = match label(addr) {
+                Some(l) => format!("${addr:04x} {l}"),
+                None => format!("${addr:04x}"),
+            };
+            writeln!(s, "{cycles:>12} {:6.2}%  {addr_name}", percent(cycles)).unwrap();
+        }
+        s
+    }
+
+    /// Folded stacks (`root;sub;sub cycles` per line) for flamegraph tools,
+    /// addresses named by `label`
+    pub fn folded(&self, label: impl Fn(u16) -> Option<String>) -> String {
+        let name = |addr: u16| label(addr).unwrap_or_else(|| format!("${addr:04x}"));
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .filter(|&(_, &cycles)| cycles > 0)
+            .map(|(path, cycles)| {
+                let mut line = String::from(TOP_LEVEL);
+                for &addr in path {
+                    line.push(';');
+                    line.push_str(&name(addr));
+                }
+                format!("{line} {cycles}")
+            })
+            .collect();
+        lines.sort();
+        lines.iter().map(|l| format!("{l}\n")).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::CallKind;
+
+    #[test]
+    fn test_profile() {
+        let frame = |target, sp| CallFrame {
+            kind: CallKind::Jsr,
+            from: 0x8000,
+            target,
+            sp,
+        };
+        let main = frame(0x9000, 0xfb);
+        let sub = frame(0xa000, 0xf9);
+
+        let mut profiler = Profiler::new();
+        profiler.instruction(0x8000, 0, &[]);
+        profiler.instruction(0x9000, 6, std::slice::from_ref(&main));
+        profiler.instruction(0xa000, 8, &[main.clone(), sub.clone()]);
+        profiler.instruction(0xa001, 10, &[main.clone(), sub.clone()]);
+        profiler.instruction(0x9003, 16, std::slice::from_ref(&main));
+        profiler.instruction(0xa000, 22, &[main.clone(), sub.clone()]);
+        profiler.instruction(0xa001, 24, &[main.clone(), sub]);
+        profiler.frame();
+
+        assert_eq!(profiler.total_cycles(), 24);
+        assert_eq!(profiler.pc_cycles(0xa000), 4);
+
+        let label = |addr: u16| (addr == 0xa000).then(|| String::from("sub"));
+        assert_eq!(
+            profiler.folded(label),
+            "(top level) 6\n(top level);$9000 8\n(top level);$9000;sub 10\n"
+        );
+
+        let subs = profiler.subroutines();
+        assert_eq!(
+            subs.iter()
+                .map(|s| (s.addr, s.inclusive, s.exclusive, s.calls))
+                .collect::<Vec<_>>(),
+            [(0x9000, 18, 8, 1), (0xa000, 10, 10, 2)]
+        );
+        assert!(profiler.report(label).contains("Cycles per frame: 24"));
+    }
+}
@@ -9,6 +9,8 @@ use std::path::{Path, PathBuf};
 
 pub struct SaveState {
     pub save_file: String,
+    /// companion file with the PRG image of self-flashing boards
+    pub flash_file: String,
 }
 
 impl SaveState {
@@ -27,9 +29,14 @@ impl SaveState {
         let rom_file_stem = rom_file_path.file_stem().unwrap();
         save_file_buf.push(rom_file_stem);
         save_file_buf.set_extension("sav");
+        let save_file = String::from(save_file_buf.to_str().unwrap());
+
+        save_file_buf.set_extension("flash");
+        let flash_file = String::from(save_file_buf.to_str().unwrap());
 
         SaveState {
-            save_file: String::from(save_file_buf.to_str().unwrap()),
+            save_file,
+            flash_file,
         }
     }
 
@@ -53,4 +60,18 @@ impl SaveState {
         let encoder = ZlibEncoder::new(writer, Compression::best());
         postcard::to_io(&system, encoder).is_ok()
     }
+
+    /// PRG image of a self-flashing board, to be applied to the cartridge
+    /// of a new system or of a loaded save state (it is saved on every exit,
+    /// the save state only on a clean one)
+    pub fn load_flash(&self) -> Option<Vec<u8>> {
+        std::fs::read(&self.flash_file).ok()
+    }
+
+    pub fn save_flash(&self, system: &System) -> bool {
+        match system.cart_prg_flash() {
+            Some(data) => std::fs::write(&self.flash_file, data).is_ok(),
+            None => false,
+        }
+    }
 }
@@ -0,0 +1,372 @@
+use crate::mapper::BankWindow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// size of the iNES header preceding PRG ROM in the linker output
+const INES_HEADER_SIZE: usize = 16;
+/// FCEUX label files are per 16 KiB PRG bank
+const NL_BANK_SIZE: usize = 0x4000;
+
+/// Location of a symbol
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum SymbolAddr {
+    /// CPU address not in banked memory (RAM, registers, constants)
+    Cpu(u16),
+    /// byte offset into PRG ROM, resolved through the current bank mapping
+    Prg(usize),
+}
+
+/// Source file position of an address
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLine {
+    pub file: String,
+    pub line: usize,
+    /// text of the line, if the source file could be read
+    pub text: Option<String>,
+}
+
+/// Symbol table: labels and source lines, imported from ca65 debug info
+/// (`.dbg`), FCEUX (`.nl`) and Mesen (`.mlb`) label files
+#[derive(Default)]
+pub struct Symbols {
+    labels: HashMap<SymbolAddr, String>,
+    lines: HashMap<SymbolAddr, SourceLine>,
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        Symbols::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.lines.is_empty()
+    }
+
+    /// Load a symbol file, the format is determined by the file name:
+    /// `*.dbg` (ca65 / ld65 debug info), `*.mlb` (Mesen), `*.ram.nl` and
+    /// `*.<bank>.nl` (FCEUX, bank number in hex).
+    pub fn load(&mut self, filename: &str) -> Result<(), io::Error> {
+        let path = Path::new(filename);
+        let text = fs::read_to_string(path)?;
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("dbg") => {
+                let dir = path.parent().unwrap_or(Path::new("."));
+                self.parse_dbg(&text, dir).map_err(|e| invalid(&e))
+            }
+            Some("mlb") => self.parse_mlb(&text).map_err(|e| invalid(&e)),
+            Some("nl") => {
+                let bank = match path.file_stem().and_then(|s| Path::new(s).extension()) {
+                    Some(s) if s == "ram" => None,
+                    Some(s) => Some(
+                        usize::from_str_radix(&s.to_string_lossy(), 16)
+                            .map_err(|_| invalid("invalid bank number in .nl file name"))?,
+                    ),
+                    None => return Err(invalid("expected <rom>.ram.nl or <rom>.<bank>.nl")),
+                };
+                self.parse_nl(&text, bank).map_err(|e| invalid(&e))
+            }
+            _ => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unknown symbol file type (expected .dbg, .nl or .mlb)",
+            )),
+        }
+    }
+
+    /// add a label for a CPU address (not banked)
+    pub fn add_label(&mut self, addr: u16, name: &str) {
+        self.labels.insert(SymbolAddr::Cpu(addr), name.to_string());
+    }
+
+    /// label at CPU address `addr`, PRG ROM addresses are resolved through
+    /// the bank windows `prg` (see `MapperDebugState`)
+    pub fn label(&self, addr: u16, prg: &[BankWindow]) -> Option<&str> {
+        Self::lookup(&self.labels, addr, prg).map(|s| s.as_str())
+    }
+
+    /// source line of the code at CPU address `addr`
+    pub fn source_line(&self, addr: u16, prg: &[BankWindow]) -> Option<&SourceLine> {
+        Self::lookup(&self.lines, addr, prg)
+    }
+
+    fn lookup<'a, T>(
+        map: &'a HashMap<SymbolAddr, T>,
+        addr: u16,
+        prg: &[BankWindow],
+    ) -> Option<&'a T> {
+        let prg_offset = prg
+            .iter()
+            .find(|w| (w.start..=w.end).contains(&addr))
+            .and_then(|w| w.offset.map(|offset| offset + (addr - w.start) as usize));
+        prg_offset
+            .and_then(|offset| map.get(&SymbolAddr::Prg(offset)))
+            .or_else(|| map.get(&SymbolAddr::Cpu(addr)))
+    }
+
+    /// FCEUX label file: `$C000#name#comment` per line, for internal RAM
+    /// (`bank` = `None`) or a 16 KiB PRG bank
+    pub fn parse_nl(&mut self, text: &str, bank: Option<usize>) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let mut fields = line.splitn(3, '#');
+            let (addr, name) = match (fields.next(), fields.next()) {
+                (Some(addr), Some(name)) if !name.is_empty() => (addr, name),
+                _ => return Err(format!("line {}: expected $ADDR#name#comment", n + 1)),
+            };
+            // arrays: $0300/10
+            let addr = addr.split('/').next().unwrap_or(addr);
+            let addr = addr
+                .strip_prefix('$')
+                .and_then(|a| u16::from_str_radix(a, 16).ok())
+                .ok_or_else(|| format!("line {}: invalid address", n + 1))?;
+
+            let loc = match bank {
+                Some(bank) if addr >= 0x8000 => {
+                    SymbolAddr::Prg(bank * NL_BANK_SIZE + (addr as usize % NL_BANK_SIZE))
+                }
+                _ => SymbolAddr::Cpu(addr),
+            };
+            self.labels.insert(loc, name.to_string());
+        }
+        Ok(())
+    }
+
+    /// Mesen label file: `type:addr[-end]:name[:comment]` per line (Mesen 1
+    /// and Mesen 2 memory types)
+    pub fn parse_mlb(&mut self, text: &str) -> Result<(), String> {
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.splitn(4, ':').collect();
+            if fields.len() < 3 {
+                return Err(format!("line {}: expected type:addr:name", n + 1));
+            }
+            let (mem, addr, name) = (fields[0], fields[1], fields[2]);
+            // comment-only entries have no name
+            if name.is_empty() {
+                continue;
+            }
+            let addr = addr.split('-').next().unwrap_or(addr);
+            let addr = usize::from_str_radix(addr, 16)
+                .map_err(|_| format!("line {}: invalid address", n + 1))?;
+
+            let loc = match mem {
+                "P" | "NesPrgRom" => SymbolAddr::Prg(addr),
+                "R" | "NesInternalRam" => SymbolAddr::Cpu(addr as u16),
+                "S" | "W" | "NesSaveRam" | "NesWorkRam" => SymbolAddr::Cpu(0x6000 + addr as u16),
+                "G" | "NesMemory" => SymbolAddr::Cpu(addr as u16),
+                // CHR, palette, OAM etc.: not CPU addresses
+                _ => continue,
+            };
+            self.labels.insert(loc, name.to_string());
+        }
+        Ok(())
+    }
+
+    /// ca65 / ld65 debug info file (`ld65 --dbgfile`). Source files are
+    /// read relative to `dir`. The linker output is expected to be an iNES
+    /// file, PRG segments located after the 16 byte header.
+    pub fn parse_dbg(&mut self, text: &str, dir: &Path) -> Result<(), String> {
+        let mut files: HashMap<usize, String> = HashMap::new();
+        // id -> (start address, output file offset)
+        let mut segs: HashMap<usize, (usize, Option<usize>)> = HashMap::new();
+        // id -> (segment, offset in segment)
+        let mut spans: HashMap<usize, (usize, usize)> = HashMap::new();
+        let mut syms = vec![];
+        let mut src_lines = vec![];
+
+        for (n, line) in text.lines().enumerate() {
+            let Some((kind, attrs)) = line.split_once('\t') else {
+                continue;
+            };
+            let attrs = parse_dbg_attrs(attrs);
+            let num = |key: &str| attrs.get(key).and_then(|v| parse_dbg_num(v));
+            let err = |key: &str| format!("line {}: {kind} without {key}", n + 1);
+
+            match kind {
+                "file" => {
+                    let name = attrs.get("name").ok_or_else(|| err("name"))?;
+                    files.insert(num("id").ok_or_else(|| err("id"))?, name.to_string());
+                }
+                "seg" => {
+                    let start = num("start").ok_or_else(|| err("start"))?;
+                    // only read-only segments run from ROM (DATA is copied to RAM)
+                    let ooffs = num("ooffs")
+                        .filter(|_| attrs.get("type").map(String::as_str) == Some("ro"));
+                    segs.insert(num("id").ok_or_else(|| err("id"))?, (start, ooffs));
+                }
+                "span" => {
+                    let seg = num("seg").ok_or_else(|| err("seg"))?;
+                    let start = num("start").ok_or_else(|| err("start"))?;
+                    spans.insert(num("id").ok_or_else(|| err("id"))?, (seg, start));
+                }
+                // labels only, equates are mostly constants
+                "sym" if attrs.get("type").map(String::as_str) == Some("lab") => {
+                    let name = attrs.get("name").ok_or_else(|| err("name"))?;
+                    syms.push((
+                        name.to_string(),
+                        num("val").ok_or_else(|| err("val"))?,
+                        num("seg"),
+                    ));
+                }
+                // macro expansions (type 2) point into the macro definition
+                "line" if num("type") != Some(2) => {
+                    if let (Some(file), Some(line), Some(span)) =
+                        (num("file"), num("line"), attrs.get("span"))
+                    {
+                        for id in span.split('+').filter_map(parse_dbg_num) {
+                            src_lines.push((file, line, id));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        // PRG ROM offset for segments in the output file, CPU address otherwise
+        let location = |seg: Option<usize>, addr: usize| match seg.and_then(|s| segs.get(&s)) {
+            Some(&(start, Some(ooffs))) if ooffs >= INES_HEADER_SIZE && addr >= start => {
+                SymbolAddr::Prg(ooffs - INES_HEADER_SIZE + addr - start)
+            }
+            _ => SymbolAddr::Cpu(addr as u16),
+        };
+
+        for (name, val, seg) in syms {
+            self.labels.insert(location(seg, val), name);
+        }
+
+        let mut sources: HashMap<usize, Option<Vec<String>>> = HashMap::new();
+        for (file, line, span) in src_lines {
+            let (Some(&(seg, offset)), Some(name)) = (spans.get(&span), files.get(&file)) else {
+                continue;
+            };
+            let Some(&(start, _)) = segs.get(&seg) else {
+                continue;
+            };
+            let text = sources
+                .entry(file)
+                .or_insert_with(|| {
+                    fs::read_to_string(dir.join(name))
+                        .ok()
+                        .map(|s| s.lines().map(String::from).collect())
+                })
+                .as_ref()
+                .and_then(|l| l.get(line.wrapping_sub(1)))
+                .map(|s| s.trim().to_string());
+
+            self.lines
+                .entry(location(Some(seg), start + offset))
+                .or_insert(SourceLine {
+                    file: name.clone(),
+                    line,
+                    text,
+                });
+        }
+        Ok(())
+    }
+}
+
+/// `key=value,key="quoted, string",...`
+fn parse_dbg_attrs(attrs: &str) -> HashMap<&str, String> {
+    let mut map = HashMap::new();
+    let mut rest = attrs;
+    while let Some((key, value)) = rest.split_once('=') {
+        let (value, next) = if let Some(quoted) = value.strip_prefix('"') {
+            let end = quoted.find('"').unwrap_or(quoted.len());
+            let next = quoted.get(end + 1..).unwrap_or("");
+            (
+                quoted[..end].to_string(),
+                next.strip_prefix(',').unwrap_or(next),
+            )
+        } else {
+            let (value, next) = value.split_once(',').unwrap_or((value, ""));
+            (value.to_string(), next)
+        };
+        map.insert(key.trim(), value);
+        rest = next;
+    }
+    map
+}
+
+fn parse_dbg_num(s: &str) -> Option<usize> {
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn windows() -> Vec<BankWindow> {
+        vec![
+            BankWindow {
+                start: 0x8000,
+                end: 0xbfff,
+                offset: Some(0x4000),
+            },
+            BankWindow {
+                start: 0xc000,
+                end: 0xffff,
+                offset: Some(0xc000),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_dbg() {
+        let dbg = "version\tmajor=2,minor=0\n\
+            file\tid=0,name=\"main, test.s\",size=100,mtime=0x5f000000,mod=0\n\
+            seg\tid=0,name=\"ZEROPAGE\",start=0x000000,size=0x0010,addrsize=zeropage,type=rw\n\
+            seg\tid=1,name=\"CODE\",start=0x008000,size=0x0100,addrsize=absolute,type=ro,oname=\"game.nes\",ooffs=16400\n\
+            span\tid=0,seg=1,start=2,size=3\n\
+            line\tid=0,file=0,line=12,span=0\n\
+            sym\tid=0,name=\"ptr\",addrsize=zeropage,scope=0,def=1,val=0x10,seg=0,type=lab\n\
+            sym\tid=1,name=\"loop\",addrsize=absolute,scope=0,def=2,val=0x8002,seg=1,type=lab\n\
+            sym\tid=2,name=\"PPUCTRL\",addrsize=absolute,scope=0,def=3,val=0x2000,type=equ\n";
+        let mut symbols = Symbols::new();
+        symbols.parse_dbg(dbg, Path::new("/nonexistent")).unwrap();
+
+        // CODE is the second 16 KiB bank, mapped at $8000
+        assert_eq!(symbols.label(0x8002, &windows()), Some("loop"));
+        assert_eq!(symbols.label(0xc002, &windows()), None);
+        assert_eq!(symbols.label(0x0010, &windows()), Some("ptr"));
+        assert_eq!(symbols.label(0x2000, &windows()), None);
+        assert_eq!(
+            symbols.source_line(0x8002, &windows()),
+            Some(&SourceLine {
+                file: String::from("main, test.s"),
+                line: 12,
+                text: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_label_files() {
+        let mut symbols = Symbols::new();
+        symbols
+            .parse_nl("$0300/10#buffer#\n$8010#nmi#handler\n", Some(3))
+            .unwrap();
+        symbols
+            .parse_mlb("R:0010:ptr\nP:C020-C021:table:comment\nNesPrgRom:C030:irq\nP:C040::only a comment\n")
+            .unwrap();
+
+        assert_eq!(symbols.label(0x0300, &windows()), Some("buffer"));
+        assert_eq!(symbols.label(0xc010, &windows()), Some("nmi"));
+        assert_eq!(symbols.label(0x0010, &windows()), Some("ptr"));
+        assert_eq!(symbols.label(0xc020, &windows()), Some("table"));
+        assert_eq!(symbols.label(0x8030, &windows()), None);
+        assert_eq!(symbols.label(0xc030, &windows()), Some("irq"));
+        assert_eq!(symbols.label(0xc040, &windows()), None);
+    }
+}
@@ -1,9 +1,16 @@
 use crate::apu::Apu;
+use crate::breakpoint::{BreakEvent, Breakpoint, BreakpointHit, Breakpoints};
 use crate::bus::CpuBus;
 use crate::cartridge::Cartridge;
+use crate::cdl::{self, CodeDataLog};
 use crate::controller::{Controller, ControllerInput};
-use crate::cpu::{Cpu, Disassembly};
+use crate::cpu::{Cpu, Disassembly, Interrupt};
+use crate::expr::{ExprContext, Var};
+use crate::mapper::{BankWindow, MapperDebugState};
 use crate::ppu::{PatternTable, Ppu, SetPixel};
+use crate::profile::Profiler;
+use crate::symbols::{SourceLine, Symbols};
+use crate::trace::Tracer;
 use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
@@ -23,10 +30,32 @@ pub struct System {
     tv_standard: TvStandard,
     /// clock counter (in PPU cycles)
     clock_counter: usize,
+    /// frames since power-on / load (for expressions, not part of save
+    /// states)
+    #[serde(skip)]
+    frame_count: u64,
     /// rate at which APU emulation produces new samples (depends on audio driver output frequency)
     time_per_sample: f64,
     /// elapsed time since last audio sample producation
     time_audio: f64,
+
+    /// CPU trace logger (not part of save states)
+    #[serde(skip)]
+    tracer: Option<Tracer>,
+    /// CPU execution profiler (not part of save states)
+    #[serde(skip)]
+    profiler: Option<Profiler>,
+    /// addresses at which instructions were executed (disassembly entry
+    /// points, not part of save states)
+    #[serde(skip, default = "executed_map")]
+    executed: Vec<bool>,
+    /// debug symbols for disassembly and trace (not part of save states)
+    #[serde(skip)]
+    symbols: Symbols,
+}
+
+fn executed_map() -> Vec<bool> {
+    vec![false; 0x10000]
 }
 
 #[derive(Deserialize, Serialize, Copy, Clone)]
@@ -60,6 +89,13 @@ struct Bus {
     dma_addr: u8,
     /// current DMA transfer data (cache)
     dma_data: u8,
+    /// last value on the CPU data bus, returned by reads of unmapped
+    /// addresses (open bus)
+    open_bus: u8,
+
+    /// breakpoints / watchpoints (not part of save states)
+    #[serde(skip)]
+    breakpoints: Breakpoints,
 }
 
 /// result state after system clock
@@ -70,8 +106,12 @@ pub struct SystemClockResult {
     pub frame_complete: bool,
     /// flag if completed CPU instruction
     pub cpu_complete: bool,
+    /// flag if CPU is halted by a JAM opcode (until reset)
+    pub cpu_jammed: bool,
     /// audio sample (optional, produced based on audio driver output frequency)
     pub audio_sample: Option<f32>,
+    /// breakpoint / watchpoint hit during this cycle
+    pub breakpoint: Option<BreakpointHit>,
 }
 
 impl System {
@@ -83,7 +123,7 @@ impl System {
     ///   determine APU sample production rate.
     ///
     pub fn new(cart: Cartridge, sample_rate: u32, tv_standard: TvStandard) -> System {
-        System {
+        let mut system = System {
             cpu: Cpu::new(),
             bus: Bus {
                 ram_cpu: [0; 2 * 1024],
@@ -95,14 +135,25 @@ impl System {
                 dma_page: 0x00,
                 dma_addr: 0x00,
                 dma_data: 0x00,
+                open_bus: 0x00,
                 dma_transfer: false,
                 dma_start_wait: true,
+                breakpoints: Breakpoints::default(),
             },
             tv_standard,
             clock_counter: 0,
+            frame_count: 0,
             time_per_sample: 1f64 / (sample_rate as f64),
             time_audio: 0f64,
-        }
+            tracer: None,
+            profiler: None,
+            executed: executed_map(),
+            symbols: Symbols::new(),
+        };
+
+        // power-up: CPU starts at the reset vector
+        system.cpu.reset(&mut system.bus);
+        system
     }
 
     /// advance system clock one PPU cycle
@@ -114,13 +165,27 @@ impl System {
             set_pixel: None,
             frame_complete: false,
             cpu_complete: false,
+            cpu_jammed: false,
             audio_sample: None,
+            breakpoint: None,
         };
 
         // run PPU cycle, forward results
         let ppu_res = self.bus.ppu.clock(&mut self.bus.cart);
         res.set_pixel = ppu_res.set_pixel;
         res.frame_complete = ppu_res.frame_complete;
+        if res.frame_complete {
+            self.frame_count += 1;
+            if let Some(profiler) = &mut self.profiler {
+                profiler.frame();
+            }
+        }
+        if !self.bus.breakpoints.is_empty() {
+            let (scanline, dot) = self.bus.ppu.position();
+            if dot == 0 {
+                self.bus.breakpoints.check(BreakEvent::Scanline(scanline));
+            }
+        }
 
         // APU cycle
         //
@@ -131,12 +196,62 @@ impl System {
 
         // CPU cycle if applicable. CPU runs at 1/3 of PPU rate
         if self.clock_counter.is_multiple_of(3) {
+            // cartridge sees every CPU cycle (M2), even while DMA halts the CPU
+            self.bus.cart.cpu_clock();
+
             if self.bus.dma_transfer {
                 // CPU interrupted by DMA, advance DMA
                 self.handle_dma_transfer();
             } else {
-                // regular CPU cycle
+                if self.cpu.instruction_start() {
+                    self.executed[self.cpu.pc as usize] = true;
+                }
+                if self.bus.cart.cdl_active() && self.cpu.instruction_start() {
+                    for (addr, flags) in cdl::instruction_accesses(&self.cpu, &self.bus) {
+                        self.bus.cart.cdl_log_cpu(addr, flags);
+                    }
+                }
+                if let Some(profiler) = &mut self.profiler
+                    && self.cpu.instruction_start()
+                {
+                    let cycle = (self.clock_counter / 3) as u64;
+                    profiler.instruction(self.cpu.pc, cycle, self.cpu.call_stack().frames());
+                }
+                if let Some(tracer) = &mut self.tracer
+                    && self.cpu.instruction_start()
+                {
+                    let (cpu, bus, symbols) = (&self.cpu, &self.bus, &self.symbols);
+                    let (scanline, dot) = bus.ppu.position();
+                    let cycles = self.clock_counter / 3;
+                    let state = ExprState {
+                        cpu,
+                        bus,
+                        frame_count: self.frame_count,
+                    };
+                    tracer.instruction(cpu.pc, &state, || {
+                        let prg = Self::symbol_banks(bus, symbols);
+                        let label = |addr| symbols.label(addr, &prg).map(String::from);
+                        format!(
+                            "{} PPU:{scanline:3},{dot:3} CYC:{cycles}",
+                            cpu.trace(bus, label)
+                        )
+                    });
+                }
+
+                // regular CPU cycle, the CPU polls the interrupt lines
                 self.cpu.clock(&mut self.bus);
+
+                // break before the next instruction / interrupt sequence
+                if !self.bus.breakpoints.is_empty() {
+                    if self.cpu.instruction_start() {
+                        self.bus.breakpoints.check(BreakEvent::Exec(self.cpu.pc));
+                    }
+                    match self.cpu.interrupt_start() {
+                        Some(Interrupt::Nmi) => self.bus.breakpoints.check(BreakEvent::Nmi),
+                        Some(Interrupt::Irq) => self.bus.breakpoints.check(BreakEvent::Irq),
+                        None => {}
+                    }
+                }
             }
         }
 
@@ -147,28 +262,120 @@ impl System {
         };
         if self.time_audio >= self.time_per_sample {
             self.time_audio -= self.time_per_sample;
-            res.audio_sample = Some(self.bus.apu.get_output_sample());
-        }
-
-        // NMI triggered by PPU?
-        if ppu_res.nmi {
-            self.cpu.nmi(&mut self.bus);
-        }
-
-        // IRQ triggered by cartridge?
-        if self.bus.cart.irq_state() {
-            self.bus.cart.irq_clear();
-            self.cpu.irq(&mut self.bus);
+            res.audio_sample = Some(self.audio_sample());
         }
 
         // notify if we completed a cpu instruction (for step)
         res.cpu_complete = self.cpu.complete();
+        res.cpu_jammed = self.cpu.jammed();
+        if self.bus.breakpoints.has_hits() {
+            // evaluate the conditions on the current state
+            let mut breakpoints = std::mem::take(&mut self.bus.breakpoints);
+            res.breakpoint = breakpoints.take_hit(self);
+            self.bus.breakpoints = breakpoints;
+        }
         self.clock_counter += 1;
 
         res
     }
 
-    /// reset all system components
+    /// log executed instructions to `tracer` (`None`: stop tracing)
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+
+    pub fn tracer(&self) -> Option<&Tracer> {
+        self.tracer.as_ref()
+    }
+
+    /// profile CPU execution with `profiler` (`None`: stop profiling)
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+
+    /// Log PRG / CHR ROM usage, continuing `previous` (.cdl file contents)
+    /// if given. Returns false if `previous` does not match the ROM size.
+    pub fn start_cdl(&mut self, previous: Option<&[u8]>) -> bool {
+        self.bus.cart.start_cdl(previous)
+    }
+
+    pub fn cdl(&self) -> Option<&CodeDataLog> {
+        self.bus.cart.cdl()
+    }
+
+    /// stop condition, reported by `clock` when hit
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.bus.breakpoints.add(breakpoint);
+    }
+
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        self.bus.breakpoints.remove(breakpoint)
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        self.bus.breakpoints.list()
+    }
+
+    fn expr_state(&self) -> ExprState<'_> {
+        ExprState {
+            cpu: &self.cpu,
+            bus: &self.bus,
+            frame_count: self.frame_count,
+        }
+    }
+
+    /// debug symbols for disassembly and trace
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
+    /// symbol name of a CPU address, resolved with the current bank mapping
+    pub fn symbol_label(&self, addr: u16) -> Option<&str> {
+        let prg = Self::symbol_banks(&self.bus, &self.symbols);
+        self.symbols.label(addr, &prg)
+    }
+
+    /// source line of the code at a CPU address
+    pub fn source_line(&self, addr: u16) -> Option<&SourceLine> {
+        let prg = Self::symbol_banks(&self.bus, &self.symbols);
+        self.symbols.source_line(addr, &prg)
+    }
+
+    /// PRG bank windows for symbol lookup (skipped without symbols)
+    fn symbol_banks(bus: &Bus, symbols: &Symbols) -> Vec<BankWindow> {
+        if symbols.is_empty() {
+            vec![]
+        } else {
+            bus.cart.debug_state().prg
+        }
+    }
+
+    /// read CPU memory without side effects
+    pub fn read(&self, addr: u16) -> u8 {
+        self.bus.cpu_read_ro(addr)
+    }
+
+    /// cartridge bank mapping, mapper registers and IRQ state
+    pub fn mapper_debug_state(&self) -> MapperDebugState {
+        self.bus.cart.debug_state()
+    }
+
+    /// PRG memory of a self-flashing cartridge, if modified
+    pub fn cart_prg_flash(&self) -> Option<&[u8]> {
+        self.bus.cart.prg_flash()
+    }
+
+    /// Replace the PRG memory of a self-flashing board (e.g. in a loaded
+    /// save state). Returns false if the image size does not match.
+    pub fn load_cart_prg_flash(&mut self, data: &[u8]) -> bool {
+        self.bus.cart.load_prg_flash(data)
+    }
+
+    /// reset all system components (console reset button)
     ///
     pub fn reset(&mut self) {
         self.bus.cart.reset();
@@ -180,23 +387,30 @@ impl System {
     /// manually trigger CPU maskable interrupct (IRQ)
     ///
     pub fn cpu_irq(&mut self) {
-        self.cpu.irq(&mut self.bus);
+        self.cpu.irq();
     }
 
     /// manually trigger CPU non-maskable interrupct (NMI)
     ///
     pub fn cpu_nmi(&mut self) {
-        self.cpu.nmi(&mut self.bus);
+        self.cpu.nmi();
     }
 
-    /// produce disassembly for address range (inclusive)
+    /// produce disassembly for address range (inclusive), following the
+    /// code from executed addresses, the interrupt vectors and the PC
     ///
     /// # Arguments
     ///
     /// - `addr_start`, `addr_stop` - inclusive memory address range
     ///
     pub fn cpu_disassemble(&self, addr_start: u16, addr_stop: u16) -> Disassembly {
-        self.cpu.disassemble(&self.bus, addr_start, addr_stop)
+        let executed: Vec<u16> = (addr_start..=addr_stop)
+            .filter(|&addr| self.executed[addr as usize])
+            .collect();
+        let prg = Self::symbol_banks(&self.bus, &self.symbols);
+        let label = |addr| self.symbols.label(addr, &prg).map(String::from);
+        self.cpu
+            .disassemble(&self.bus, addr_start, addr_stop, &executed, label)
     }
 
     /// get PPU pattern table contents
@@ -238,6 +452,15 @@ impl System {
         self.bus.ppu.debug_oam(entry)
     }
 
+    /// mix 2A03 output with cartridge expansion audio (if present)
+    fn audio_sample(&self) -> f32 {
+        let sample = self.bus.apu.get_output_sample();
+        match self.bus.cart.audio_chip() {
+            Some(chip) => (sample + chip.gain() * self.bus.cart.audio_sample()).clamp(-1.0, 1.0),
+            None => sample,
+        }
+    }
+
     fn handle_dma_transfer(&mut self) {
         if self.bus.dma_start_wait {
             // wait for start: after first odd cycle
@@ -265,8 +488,106 @@ impl System {
     }
 }
 
+/// values for expressions (breakpoint conditions, trace filter, watches)
+impl ExprContext for System {
+    fn var(&self, var: Var) -> i64 {
+        self.expr_state().var(var)
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.expr_state().read(addr)
+    }
+
+    fn bank(&self, addr: u16, chr: bool) -> Option<usize> {
+        self.expr_state().bank(addr, chr)
+    }
+}
+
+/// CPU and bus state for expressions, borrowed separately from the tracer
+struct ExprState<'a> {
+    cpu: &'a Cpu,
+    bus: &'a Bus,
+    frame_count: u64,
+}
+
+impl ExprContext for ExprState<'_> {
+    fn var(&self, var: Var) -> i64 {
+        let (scanline, dot) = self.bus.ppu.position();
+        match var {
+            Var::A => self.cpu.a as i64,
+            Var::X => self.cpu.x as i64,
+            Var::Y => self.cpu.y as i64,
+            Var::Sp => self.cpu.stkp as i64,
+            Var::Pc => self.cpu.pc as i64,
+            Var::P => self.cpu.status as i64,
+            Var::Flag(flag) => self.cpu.get_flag(flag) as i64,
+            Var::Scanline => scanline as i64,
+            Var::Cycle => dot as i64,
+            Var::Frame => self.frame_count as i64,
+        }
+    }
+
+    fn read(&self, addr: u16) -> u8 {
+        self.bus.cpu_read_ro(addr)
+    }
+
+    fn bank(&self, addr: u16, chr: bool) -> Option<usize> {
+        let state = self.bus.cart.debug_state();
+        if chr {
+            state.chr_bank(addr)
+        } else {
+            state.prg_bank(addr)
+        }
+    }
+}
+
+impl Bus {
+    fn cpu_read_mem(&mut self, addr: u16) -> u8 {
+        // first check if read is handled by cartridge
+        if let Some(data) = self.cart.cpu_read(addr, self.open_bus) {
+            data
+        } else {
+            match addr {
+                // CPU Ram
+                0x0000..=0x1fff => self.ram_cpu[(addr & 0x07ff) as usize],
+                // PPU Registers
+                0x2000..=0x3fff => self.ppu.cpu_read(&mut self.cart, addr & 0x0007),
+                // APU Status, bit 5 is open bus
+                0x4015 => self.apu.cpu_read(addr) | (self.open_bus & 0x20),
+                // Controller Ports: D0 from the controller, D5-D7 open bus
+                0x4016..=0x4017 => {
+                    (self.open_bus & 0xe0) | self.controller[(addr & 0x0001) as usize].read()
+                }
+                _ => self.open_bus,
+            }
+        }
+    }
+
+    /// check a CPU access (and a PPU memory access through PPUDATA at
+    /// VRAM address `ppu_addr`) against the watchpoints
+    fn check_watchpoints(&mut self, addr: u16, data: u8, write: bool, ppu_addr: u16) {
+        let ppu_data = (0x2000..=0x3fff).contains(&addr) && addr & 0x0007 == 0x0007;
+        if write {
+            self.breakpoints.check(BreakEvent::Write(addr, data));
+            if ppu_data {
+                self.breakpoints.check(BreakEvent::PpuWrite(ppu_addr, data));
+            }
+        } else {
+            self.breakpoints.check(BreakEvent::Read(addr, data));
+            if ppu_data {
+                self.breakpoints.check(BreakEvent::PpuRead(ppu_addr, data));
+            }
+        }
+    }
+}
+
 impl CpuBus for Bus {
     fn cpu_write(&mut self, addr: u16, data: u8) {
+        if !self.breakpoints.is_empty() {
+            self.check_watchpoints(addr, data, true, self.ppu.vram_addr());
+        }
+        self.open_bus = data;
+
         // first check if write is handled by cartridge
         if !self.cart.cpu_write(addr, data) {
             match addr {
@@ -298,27 +619,21 @@ impl CpuBus for Bus {
     }
 
     fn cpu_read(&mut self, addr: u16) -> u8 {
-        // first check if read is handled by cartridge
-        if let Some(data) = self.cart.cpu_read(addr) {
-            data
-        } else {
-            match addr {
-                // CPU Ram
-                0x0000..=0x1fff => self.ram_cpu[(addr & 0x07ff) as usize],
-                // PPU Registers
-                0x2000..=0x3fff => self.ppu.cpu_read(&mut self.cart, addr & 0x0007),
-                // APU Status
-                0x4015 => self.apu.cpu_read(addr),
-                // Controller Ports
-                0x4016..=0x4017 => self.controller[(addr & 0x0001) as usize].read(),
-                _ => 0,
-            }
+        let ppu_addr = self.ppu.vram_addr();
+        let data = self.cpu_read_mem(addr);
+        // the APU status is read inside the CPU, the data bus keeps its value
+        if addr != 0x4015 {
+            self.open_bus = data;
+        }
+        if !self.breakpoints.is_empty() {
+            self.check_watchpoints(addr, data, false, ppu_addr);
         }
+        data
     }
 
     fn cpu_read_ro(&self, addr: u16) -> u8 {
         // first check if read is handled by cartridge
-        if let Some(data) = self.cart.cpu_read_ro(addr) {
+        if let Some(data) = self.cart.cpu_read_ro(addr, self.open_bus) {
             data
         } else {
             match addr {
@@ -326,12 +641,23 @@ impl CpuBus for Bus {
                 0x0000..=0x1fff => self.ram_cpu[(addr & 0x07ff) as usize],
                 // PPU Registers
                 0x2000..=0x3fff => self.ppu.cpu_read_ro(addr & 0x0007),
-                // APU Status
-                0x4015 => self.apu.cpu_read(addr),
-                // Controller Ports
-                0x4016..=0x4017 => self.controller[(addr & 0x0001) as usize].read_ro(),
-                _ => 0,
+                // APU Status, bit 5 is open bus
+                0x4015 => self.apu.cpu_read_ro(addr) | (self.open_bus & 0x20),
+                // Controller Ports: D0 from the controller, D5-D7 open bus
+                0x4016..=0x4017 => {
+                    (self.open_bus & 0xe0) | self.controller[(addr & 0x0001) as usize].read_ro()
+                }
+                _ => self.open_bus,
             }
         }
     }
+
+    /// state of the shared IRQ line (wired-OR of all IRQ sources)
+    fn irq_line(&self) -> bool {
+        self.apu.irq_state() || self.cart.irq_state()
+    }
+
+    fn nmi_line(&self) -> bool {
+        self.ppu.nmi_line()
+    }
 }
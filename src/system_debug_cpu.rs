@@ -1,9 +1,30 @@
 use crate::bus::CpuBus;
-use crate::cpu::{Cpu, Disassembly};
+use crate::cpu::{Cpu, Disassembly, Flag};
+use crate::symbols::Symbols;
+use std::fs;
+use std::io;
+use std::str::FromStr;
 
 pub struct SystemDebugCpu {
     memory: MemoryDebugCpu,
     pub cpu: Cpu,
+    /// labels for the disassembly
+    symbols: Symbols,
+    /// CPU cycles since power-on
+    cycles: u64,
+}
+
+/// Reason for `SystemDebugCpu::run` to return
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Stop {
+    /// exit port written, with the status
+    Exit(u8),
+    /// CPU halted by a JAM opcode
+    Jam,
+    /// instruction jumping / branching to itself
+    Trap(u16),
+    /// cycle limit reached
+    Limit,
 }
 
 impl Default for SystemDebugCpu {
@@ -13,27 +34,51 @@ impl Default for SystemDebugCpu {
 }
 
 impl SystemDebugCpu {
+    /// flat 64 KiB RAM
     pub fn new() -> SystemDebugCpu {
+        Self::with_machine(&Machine::default())
+    }
+
+    /// memory map from `machine`
+    pub fn with_machine(machine: &Machine) -> SystemDebugCpu {
         SystemDebugCpu {
-            memory: MemoryDebugCpu::new(),
+            memory: MemoryDebugCpu::new(machine),
             cpu: Cpu::new(),
+            symbols: Symbols::new(),
+            cycles: 0,
         }
     }
 
     pub fn load_from_str(&mut self, s: &str, addr: u16) {
         for (offset, n) in s.split_ascii_whitespace().enumerate() {
-            self.memory.ram[addr as usize + offset] = u8::from_str_radix(n, 16).unwrap();
+            self.memory.poke(
+                addr.wrapping_add(offset as u16),
+                u8::from_str_radix(n, 16).unwrap(),
+            );
         }
     }
 
+    /// copy a memory image to `addr` (RAM and ROM, unmapped addresses are
+    /// skipped)
+    pub fn load(&mut self, data: &[u8], addr: u16) {
+        for (offset, &value) in data.iter().enumerate() {
+            self.memory.poke(addr.wrapping_add(offset as u16), value);
+        }
+    }
+
+    pub fn set_symbols(&mut self, symbols: Symbols) {
+        self.symbols = symbols;
+    }
+
     pub fn set_reset_vector(&mut self, addr: u16) {
-        self.memory.ram[0xfffc] = (addr & 0x00ff) as u8;
-        self.memory.ram[0xfffd] = ((addr >> 8) & 0x00ff) as u8;
+        self.memory.poke(0xfffc, (addr & 0x00ff) as u8);
+        self.memory.poke(0xfffd, ((addr >> 8) & 0x00ff) as u8);
     }
 
     pub fn cpu_step(&mut self) {
         loop {
             self.cpu.clock(&mut self.memory);
+            self.cycles += 1;
             if self.cpu.complete() {
                 break;
             }
@@ -42,47 +87,378 @@ impl SystemDebugCpu {
 
     pub fn cpu_reset(&mut self) {
         self.cpu.reset(&mut self.memory);
+        self.memory.exit = None;
     }
 
+    /// run the IRQ sequence (if not masked)
     pub fn cpu_irq(&mut self) {
-        self.cpu.irq(&mut self.memory);
+        if !self.cpu.get_flag(Flag::I) {
+            self.cpu.irq();
+            self.cpu_step();
+        }
     }
 
+    /// run the NMI sequence
     pub fn cpu_nmi(&mut self) {
-        self.cpu.nmi(&mut self.memory);
+        self.cpu.nmi();
+        self.cpu_step();
+    }
+
+    /// Run instructions until the program writes the exit port, the CPU
+    /// jams or traps, or `max_cycles` have elapsed. Bytes written to the
+    /// character output port are passed to `output`.
+    pub fn run(&mut self, max_cycles: Option<u64>, mut output: impl FnMut(&[u8])) -> Stop {
+        loop {
+            if let Some(status) = self.memory.exit {
+                break Stop::Exit(status);
+            }
+            if self.cpu.jammed() {
+                break Stop::Jam;
+            }
+            if max_cycles.is_some_and(|max| self.cycles >= max) {
+                break Stop::Limit;
+            }
+            // not an instruction: reset / interrupt sequence
+            let instruction = self.cpu.instruction_start();
+            let pc = self.cpu.pc;
+            self.cpu_step();
+            if !self.memory.output.is_empty() {
+                output(&self.memory.output);
+                self.memory.output.clear();
+            }
+            if instruction && self.cpu.pc == pc && !self.cpu.jammed() && self.memory.exit.is_none()
+            {
+                break Stop::Trap(pc);
+            }
+        }
+    }
+
+    /// bytes written to the character output port since the last call
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.memory.output)
+    }
+
+    /// status written to the exit port
+    pub fn exit_status(&self) -> Option<u8> {
+        self.memory.exit
+    }
+
+    /// CPU cycles since power-on
+    pub fn cycles(&self) -> u64 {
+        self.cycles
     }
 
     pub fn cpu_disassemble(&self, addr_start: u16, addr_stop: u16) -> Disassembly {
-        self.cpu.disassemble(&self.memory, addr_start, addr_stop)
+        let label = |addr| self.symbols.label(addr, &[]).map(String::from);
+        self.cpu
+            .disassemble(&self.memory, addr_start, addr_stop, &[], label)
     }
 
     pub fn read(&self, addr: u16) -> u8 {
-        self.memory.ram[addr as usize]
+        self.memory.cpu_read_ro(addr)
+    }
+}
+
+/// Memory map of the generic 6502 machine.
+///
+/// Configuration file, one entry per line (addresses in hex, `#` starts a
+/// comment):
+///
+/// ```text
+/// ram     0000-07ff
+/// mirror  0800-1fff 0000-07ff   # repeats RAM $0000-$07ff
+/// rom     8000-ffff             # writes are ignored
+/// putchar f001                  # writes print a character
+/// exit    f002                  # writes stop the program, value = status
+/// ```
+///
+/// Entries are applied in file order, later entries override earlier ones
+/// where they overlap. A mirror repeats whatever is mapped at its source
+/// range at that point (RAM / ROM, ports or other mirrors).
+///
+/// Reads of unmapped addresses and of the ports return 0.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Machine {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    Region(Region),
+    Mirror(Mirror),
+}
+
+/// memory or port (single address)
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Region {
+    cell: Cell,
+    start: u16,
+    end: u16,
+}
+
+/// `start..=end` repeats `source_start..=source_end`
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Mirror {
+    start: u16,
+    end: u16,
+    source_start: u16,
+    source_end: u16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Cell {
+    Unmapped,
+    Ram,
+    Rom,
+    Putchar,
+    Exit,
+}
+
+impl Default for Machine {
+    fn default() -> Self {
+        Machine {
+            entries: vec![Entry::Region(Region {
+                cell: Cell::Ram,
+                start: 0x0000,
+                end: 0xffff,
+            })],
+        }
+    }
+}
+
+impl Machine {
+    pub fn load(filename: &str) -> Result<Machine, io::Error> {
+        fs::read_to_string(filename)?
+            .parse()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{filename}: {e}")))
+    }
+}
+
+impl FromStr for Machine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut machine = Machine {
+            entries: Vec::new(),
+        };
+        for (n, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or(line);
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let err = |msg: &str| format!("line {}: {msg}", n + 1);
+            let addr = |s: &str| {
+                let hex = s
+                    .strip_prefix('$')
+                    .or_else(|| s.strip_prefix("0x"))
+                    .unwrap_or(s);
+                u16::from_str_radix(hex, 16).map_err(|_| err(&format!("invalid address '{s}'")))
+            };
+            let range = |s: &str| {
+                let (start, end) = match s.split_once('-') {
+                    Some((start, end)) => (addr(start)?, addr(end)?),
+                    None => (addr(s)?, addr(s)?),
+                };
+                if start > end {
+                    return Err(err(&format!("invalid range '{s}'")));
+                }
+                Ok((start, end))
+            };
+
+            let entry = match fields.as_slice() {
+                [] => continue,
+                [kind @ ("ram" | "rom"), r] => {
+                    let (start, end) = range(r)?;
+                    let cell = if *kind == "ram" { Cell::Ram } else { Cell::Rom };
+                    Entry::Region(Region { cell, start, end })
+                }
+                ["mirror", r, source] => {
+                    let (start, end) = range(r)?;
+                    let (source_start, source_end) = range(source)?;
+                    Entry::Mirror(Mirror {
+                        start,
+                        end,
+                        source_start,
+                        source_end,
+                    })
+                }
+                [kind @ ("putchar" | "exit"), a] => {
+                    let addr = addr(a)?;
+                    let cell = if *kind == "putchar" {
+                        Cell::Putchar
+                    } else {
+                        Cell::Exit
+                    };
+                    Entry::Region(Region {
+                        cell,
+                        start: addr,
+                        end: addr,
+                    })
+                }
+                _ => {
+                    return Err(err(
+                        "expected 'ram RANGE', 'rom RANGE', 'mirror RANGE RANGE', 'putchar ADDR' or 'exit ADDR'",
+                    ));
+                }
+            };
+            machine.entries.push(entry);
+        }
+        Ok(machine)
     }
 }
 
 struct MemoryDebugCpu {
-    ram: [u8; 64 * 1024],
+    /// backing store, by (mirror resolved) address
+    ram: Vec<u8>,
+    /// memory type by address
+    cells: Vec<Cell>,
+    /// backing store address by address
+    targets: Vec<u16>,
+    /// bytes written to the character output port
+    output: Vec<u8>,
+    /// status written to the exit port
+    exit: Option<u8>,
 }
 
 impl MemoryDebugCpu {
-    fn new() -> MemoryDebugCpu {
+    fn new(machine: &Machine) -> MemoryDebugCpu {
+        let mut cells = vec![Cell::Unmapped; 0x10000];
+        let mut targets: Vec<u16> = (0..=0xffff).collect();
+        for entry in &machine.entries {
+            match entry {
+                Entry::Region(region) => {
+                    for addr in region.start..=region.end {
+                        cells[addr as usize] = region.cell;
+                        targets[addr as usize] = addr;
+                    }
+                }
+                Entry::Mirror(mirror) => {
+                    let len = (mirror.source_end - mirror.source_start) as usize + 1;
+                    for addr in mirror.start as usize..=mirror.end as usize {
+                        let source =
+                            mirror.source_start as usize + (addr - mirror.start as usize) % len;
+                        cells[addr] = cells[source];
+                        targets[addr] = targets[source];
+                    }
+                }
+            }
+        }
+
         MemoryDebugCpu {
-            ram: [0; 64 * 1024],
+            ram: vec![0; 0x10000],
+            cells,
+            targets,
+            output: Vec::new(),
+            exit: None,
+        }
+    }
+
+    /// write RAM / ROM without side effects
+    fn poke(&mut self, addr: u16, data: u8) {
+        if let Cell::Ram | Cell::Rom = self.cells[addr as usize] {
+            self.ram[self.targets[addr as usize] as usize] = data;
         }
     }
 }
 
 impl CpuBus for MemoryDebugCpu {
     fn cpu_write(&mut self, addr: u16, data: u8) {
-        self.ram[addr as usize] = data;
+        match self.cells[addr as usize] {
+            Cell::Ram => self.ram[self.targets[addr as usize] as usize] = data,
+            Cell::Putchar => self.output.push(data),
+            Cell::Exit => self.exit = Some(data),
+            Cell::Rom | Cell::Unmapped => {}
+        }
     }
 
     fn cpu_read(&mut self, addr: u16) -> u8 {
-        self.ram[addr as usize]
+        self.cpu_read_ro(addr)
     }
 
     fn cpu_read_ro(&self, addr: u16) -> u8 {
-        self.ram[addr as usize]
+        match self.cells[addr as usize] {
+            Cell::Ram | Cell::Rom => self.ram[self.targets[addr as usize] as usize],
+            _ => 0x00,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asm::assemble;
+
+    const MACHINE: &str = "
+        ram     0000-07ff
+        mirror  0800-1fff 0000-07ff
+        rom     8000-ffff   # program
+        putchar f001
+        exit    f002
+    ";
+
+    const PROGRAM: &str = "
+        .org $8000
+reset:  ldx #0
+print:  lda text,x
+        beq done
+        sta $f001
+        inx
+        bne print
+done:   lda #$55
+        sta $0810       ; mirror of $0010
+        sta $9000       ; ROM
+        lda $0010
+        sta $f002
+text:   .byte \"Hi\", 0
+
+        .org $fffc
+        .word reset
+";
+
+    #[test]
+    fn test_machine() {
+        let machine: Machine = MACHINE.parse().unwrap();
+        let mut system = SystemDebugCpu::with_machine(&machine);
+        let program = assemble(PROGRAM, 0x8000).unwrap();
+        for segment in &program.segments {
+            system.load(&segment.data, segment.addr);
+        }
+        system.cpu_reset();
+
+        let mut output = Vec::new();
+        let stop = system.run(Some(10_000), |bytes| output.extend_from_slice(bytes));
+        assert_eq!(stop, Stop::Exit(0x55));
+        assert_eq!(output, b"Hi");
+        assert_eq!(system.read(0x1010), 0x55);
+        assert_eq!(system.read(0x9000), 0x00);
+        assert_eq!(system.read(0x4000), 0x00);
+
+        // entries in file order: the later region overrides part of the
+        // mirror, mirrors of mirrors resolve to the first source
+        let machine: Machine = "ram 0000-00ff\n\
+            mirror 0100-01ff 0000-00ff\n\
+            ram 0180-01ff\n\
+            mirror 0200-03ff 0100-01ff\n\
+            exit 0005\n\
+            mirror 0400-04ff 0000-00ff"
+            .parse()
+            .unwrap();
+        let mut system = SystemDebugCpu::with_machine(&machine);
+        system.load(&[0xab], 0x0004);
+        system.load(&[0xcd], 0x0105);
+        system.load(&[0xef], 0x0184);
+        assert_eq!(system.read(0x0104), 0xab);
+        assert_eq!(system.read(0x0184), 0xef);
+        assert_eq!(system.read(0x0084), 0x00);
+        assert_eq!(system.read(0x0204), 0xab);
+        assert_eq!(system.read(0x0384), 0xef);
+        // the port overrides RAM mirrored before it, is mirrored after it
+        assert_eq!(system.read(0x0105), 0xcd);
+        assert_eq!(system.read(0x0005), 0x00);
+        assert_eq!(system.read(0x0405), 0x00);
+
+        assert_eq!(
+            "ram 0000-07ff\nrom ffff-8000".parse::<Machine>(),
+            Err(String::from("line 2: invalid range 'ffff-8000'"))
+        );
+        assert!("putchar".parse::<Machine>().is_err());
     }
 }
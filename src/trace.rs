@@ -0,0 +1,178 @@
+use crate::expr::{Expr, ExprContext};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Destination of trace lines
+pub enum TraceSink {
+    File(BufWriter<File>),
+    Callback(Box<dyn FnMut(&str)>),
+}
+
+/// When to start and stop tracing
+#[derive(Clone, Debug, Default)]
+pub struct TraceConditions {
+    /// start when reaching this PC (`None`: immediately)
+    pub start_pc: Option<u16>,
+    /// stop when reaching this PC (the instruction is not logged)
+    pub stop_pc: Option<u16>,
+    /// stop after this number of lines
+    pub max_lines: Option<u64>,
+    /// only log instructions for which this expression is true (start /
+    /// stop conditions are checked for every instruction)
+    pub filter: Option<Expr>,
+}
+
+/// CPU trace logger: one line per executed instruction in the nestest.log
+/// layout, e.g.
+///
+/// `C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7`
+pub struct Tracer {
+    sink: TraceSink,
+    conditions: TraceConditions,
+    active: bool,
+    finished: bool,
+    lines: u64,
+}
+
+impl Tracer {
+    pub fn new(sink: TraceSink, conditions: TraceConditions) -> Tracer {
+        Tracer {
+            sink,
+            active: conditions.start_pc.is_none(),
+            conditions,
+            finished: false,
+            lines: 0,
+        }
+    }
+
+    /// trace to a (newly created) file
+    pub fn to_file(path: &str, conditions: TraceConditions) -> io::Result<Tracer> {
+        let file = File::create(path)?;
+        Ok(Tracer::new(
+            TraceSink::File(BufWriter::new(file)),
+            conditions,
+        ))
+    }
+
+    /// pass each trace line to `f`
+    pub fn to_callback(f: impl FnMut(&str) + 'static, conditions: TraceConditions) -> Tracer {
+        Tracer::new(TraceSink::Callback(Box::new(f)), conditions)
+    }
+
+    /// stop condition reached, no more lines are logged
+    pub fn finished(&self) -> bool {
+        self.finished
+    }
+
+    /// number of lines logged
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    /// Instruction at `pc` is about to execute: log the line produced by
+    /// `line` if tracing is active and the filter is true in `ctx`.
+    pub fn instruction(&mut self, pc: u16, ctx: &impl ExprContext, line: impl FnOnce() -> String) {
+        if self.finished {
+            return;
+        }
+        if !self.active && self.conditions.start_pc == Some(pc) {
+            self.active = true;
+        }
+        if self.active && self.conditions.stop_pc == Some(pc) {
+            self.finish();
+        }
+        if !self.active || self.finished {
+            return;
+        }
+        if let Some(filter) = &self.conditions.filter
+            && !filter.is_true(ctx)
+        {
+            return;
+        }
+
+        let line = line();
+        match &mut self.sink {
+            TraceSink::File(w) => {
+                if writeln!(w, "{line}").is_err() {
+                    // e.g. disk full, don't retry for every instruction
+                    self.finished = true;
+                }
+            }
+            TraceSink::Callback(f) => f(&line),
+        }
+        self.lines += 1;
+
+        if self.conditions.max_lines == Some(self.lines) {
+            self.finish();
+        }
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+        if let TraceSink::File(w) = &mut self.sink {
+            w.flush().unwrap_or(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Var;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct State {
+        pc: u16,
+    }
+
+    impl ExprContext for State {
+        fn var(&self, var: Var) -> i64 {
+            if var == Var::Pc { self.pc as i64 } else { 0 }
+        }
+
+        fn read(&self, _addr: u16) -> u8 {
+            0
+        }
+
+        fn bank(&self, _addr: u16, _chr: bool) -> Option<usize> {
+            None
+        }
+    }
+
+    #[test]
+    fn test_conditions() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let log_cb = log.clone();
+        let mut tracer = Tracer::to_callback(
+            move |l| log_cb.borrow_mut().push(l.to_string()),
+            TraceConditions {
+                start_pc: Some(0x8002),
+                stop_pc: Some(0x8005),
+                max_lines: None,
+                filter: None,
+            },
+        );
+        for pc in 0x8000..0x8008 {
+            tracer.instruction(pc, &State { pc }, || format!("{pc:04X}"));
+        }
+        assert_eq!(*log.borrow(), ["8002", "8003", "8004"]);
+        assert!(tracer.finished());
+
+        // odd PCs only, limit counts logged lines
+        log.borrow_mut().clear();
+        let log_cb = log.clone();
+        let mut tracer = Tracer::to_callback(
+            move |l| log_cb.borrow_mut().push(l.to_string()),
+            TraceConditions {
+                max_lines: Some(2),
+                filter: Some("pc & 1".parse().unwrap()),
+                ..Default::default()
+            },
+        );
+        for pc in 0x8000..0x8008 {
+            tracer.instruction(pc, &State { pc }, || format!("{pc:04X}"));
+        }
+        assert_eq!(*log.borrow(), ["8001", "8003"]);
+    }
+}
@@ -0,0 +1,288 @@
+//! CPU conformance tests against external test ROMs / vectors.
+//!
+//! The external fixtures are not part of the repository, see
+//! `tests/fixtures/README.md`. The tests using them are ignored by default,
+//! run them with `cargo test -- --ignored` (fixtures in `tests/fixtures` or
+//! the directory in `NESSUNO_FIXTURES`); a missing fixture fails the test.
+//! The hand-derived vectors in `tests/vectors` always run.
+
+use nessuno::bus::CpuBus;
+use nessuno::cartridge::Cartridge;
+use nessuno::cpu::Cpu;
+use nessuno::system::{System, TvStandard};
+use nessuno::system_debug_cpu::SystemDebugCpu;
+use nessuno::trace::{TraceConditions, Tracer};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+fn fixture(name: &str) -> PathBuf {
+    let dir = match std::env::var_os("NESSUNO_FIXTURES") {
+        Some(dir) => PathBuf::from(dir),
+        None => Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures"),
+    };
+    let path = dir.join(name);
+    assert!(
+        path.exists(),
+        "fixture not found: {} (see tests/fixtures/README.md)",
+        path.display()
+    );
+    path
+}
+
+/// nestest in automation mode (start at $C000), compared with the golden
+/// log (CPU state and cycle count, PPU position is not compared)
+#[test]
+#[ignore = "needs fixtures"]
+fn nestest() {
+    let (rom, log) = (fixture("nestest.nes"), fixture("nestest.log"));
+    let golden: Vec<String> = fs::read_to_string(log)
+        .unwrap()
+        .lines()
+        .map(|l| l.replace("ISB", "ISC"))
+        .collect();
+
+    let cart = Cartridge::new(rom.to_str().unwrap(), None).unwrap();
+    let mut system = System::new(cart, 44100, TvStandard::Ntsc);
+    system.cpu.pc = 0xc000;
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let lines_cb = lines.clone();
+    system.set_tracer(Some(Tracer::to_callback(
+        move |l| lines_cb.borrow_mut().push(l.to_string()),
+        TraceConditions {
+            max_lines: Some(golden.len() as u64),
+            ..Default::default()
+        },
+    )));
+
+    // nestest takes ~27k CPU cycles
+    for _ in 0..3 * 100_000 {
+        system.clock();
+        if system.tracer().unwrap().finished() {
+            break;
+        }
+    }
+
+    // CPU part up to the SP column, cycle count
+    let cpu_part = |l: &str| l.get(..73).unwrap_or(l).to_string();
+    let cyc_part = |l: &str| l.split(" CYC:").nth(1).unwrap_or("").to_string();
+    for (i, (line, expected)) in lines.borrow().iter().zip(&golden).enumerate() {
+        assert_eq!(
+            (cpu_part(line), cyc_part(line)),
+            (cpu_part(expected), cyc_part(expected)),
+            "nestest.log line {}",
+            i + 1
+        );
+    }
+    assert_eq!(lines.borrow().len(), golden.len());
+
+    // result codes: 0 = all tests passed
+    assert_eq!((system.read(0x0002), system.read(0x0003)), (0x00, 0x00));
+}
+
+/// Klaus Dormann's 6502 functional test (built with `disable_decimal = 1`).
+/// The test loops on itself when done or on failure, the test case number
+/// at $0200 is $f0 after the last test.
+#[test]
+#[ignore = "needs fixtures"]
+fn functional_test() {
+    let bin = fixture("6502_functional_test.bin");
+    let image = fs::read(bin).unwrap();
+
+    let mut system = SystemDebugCpu::new();
+    system.load(&image, 0x0000);
+    system.cpu.pc = 0x0400;
+
+    let mut pc_prev = None;
+    for _ in 0..100_000_000 {
+        system.cpu_step();
+        if pc_prev == Some(system.cpu.pc) {
+            break;
+        }
+        pc_prev = Some(system.cpu.pc);
+    }
+
+    assert_eq!(
+        pc_prev,
+        Some(system.cpu.pc),
+        "no trap reached, PC ${:04x}",
+        system.cpu.pc
+    );
+    assert_eq!(
+        system.read(0x0200),
+        0xf0,
+        "trap at ${:04x} in test case ${:02x}",
+        system.cpu.pc,
+        system.read(0x0200)
+    );
+}
+
+/// Run a blargg test ROM until it reports a result: status at $6000 ($80:
+/// running, $81: reset requested, otherwise the result code), valid once
+/// the signature $de $b0 $61 is at $6001, text from $6004
+fn run_blargg(rom: &Path, max_frames: usize) -> (u8, String) {
+    let cart = Cartridge::new(rom.to_str().unwrap(), None).unwrap();
+    let mut system = System::new(cart, 44100, TvStandard::Ntsc);
+    let text = |system: &System| {
+        (0x6004..0x7000)
+            .map(|addr| system.read(addr))
+            .take_while(|&c| c != 0)
+            .map(char::from)
+            .collect::<String>()
+    };
+
+    let mut reset_frame = None;
+    for frame in 0..max_frames {
+        while !system.clock().frame_complete {}
+        if [0x6001, 0x6002, 0x6003].map(|addr| system.read(addr)) != [0xde, 0xb0, 0x61] {
+            continue;
+        }
+        match system.read(0x6000) {
+            0x80 => {}
+            0x81 => {
+                // press reset at least 100 ms after the request
+                let at = *reset_frame.get_or_insert(frame + 10);
+                if frame >= at {
+                    system.reset();
+                    reset_frame = None;
+                }
+            }
+            status => return (status, text(&system)),
+        }
+    }
+    panic!("no result after {max_frames} frames: {}", text(&system));
+}
+
+/// blargg's cpu_interrupts_v2: CLI / SEI / PLP latency, NMI / BRK / IRQ
+/// hijacking, IRQ / NMI timing and branch delays
+#[test]
+#[ignore = "needs fixtures"]
+fn cpu_interrupts() {
+    let rom = fixture("cpu_interrupts_v2/cpu_interrupts.nes");
+    let (status, text) = run_blargg(&rom, 60 * 60);
+    assert_eq!(status, 0, "cpu_interrupts_v2 failed:\n{text}");
+}
+
+#[derive(Deserialize)]
+struct SingleStepTest {
+    name: String,
+    initial: SingleStepState,
+    #[serde(rename = "final")]
+    final_state: SingleStepState,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct SingleStepState {
+    pc: u16,
+    s: u8,
+    a: u8,
+    x: u8,
+    y: u8,
+    p: u8,
+    ram: Vec<(u16, u8)>,
+}
+
+/// sparse RAM, logging every bus cycle
+struct LogBus {
+    ram: HashMap<u16, u8>,
+    cycles: Vec<(u16, u8, String)>,
+}
+
+impl CpuBus for LogBus {
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        self.ram.insert(addr, data);
+        self.cycles.push((addr, data, String::from("write")));
+    }
+
+    fn cpu_read(&mut self, addr: u16) -> u8 {
+        let data = self.cpu_read_ro(addr);
+        self.cycles.push((addr, data, String::from("read")));
+        data
+    }
+
+    fn cpu_read_ro(&self, addr: u16) -> u8 {
+        self.ram.get(&addr).copied().unwrap_or(0)
+    }
+}
+
+/// JAM and the unstable opcodes with chip dependent results
+const SINGLE_STEP_SKIP: [u8; 19] = [
+    0x02, 0x12, 0x22, 0x32, 0x42, 0x52, 0x62, 0x72, 0x92, 0xb2, 0xd2, 0xf2, // JAM
+    0x8b, 0xab, // XAA, LXA
+    0x93, 0x9f, 0x9b, 0x9c, 0x9e, // AHX, TAS, SHY, SHX
+];
+
+/// Run SingleStepTests vectors: registers, memory and every bus cycle of a
+/// single instruction
+fn run_single_step(tests: Vec<SingleStepTest>) {
+    for test in tests {
+        let mut bus = LogBus {
+            ram: test.initial.ram.iter().copied().collect(),
+            cycles: Vec::new(),
+        };
+        let mut cpu = Cpu::new();
+        cpu.pc = test.initial.pc;
+        cpu.stkp = test.initial.s;
+        cpu.a = test.initial.a;
+        cpu.x = test.initial.x;
+        cpu.y = test.initial.y;
+        cpu.status = test.initial.p;
+
+        loop {
+            cpu.clock(&mut bus);
+            if cpu.complete() {
+                break;
+            }
+        }
+
+        let state = SingleStepState {
+            pc: cpu.pc,
+            s: cpu.stkp,
+            a: cpu.a,
+            x: cpu.x,
+            y: cpu.y,
+            p: cpu.status,
+            ram: test
+                .final_state
+                .ram
+                .iter()
+                .map(|&(addr, _)| (addr, bus.cpu_read_ro(addr)))
+                .collect(),
+        };
+        assert_eq!(state, test.final_state, "test {}", test.name);
+        assert_eq!(bus.cycles, test.cycles, "bus cycles, test {}", test.name);
+    }
+}
+
+/// Vectors in the SingleStepTests format, derived by hand from the 6502 bus
+/// timing (dummy reads / writes, page crossing, stack and vector accesses)
+#[test]
+fn single_step_vectors() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/vectors/single_step.json");
+    let tests: Vec<SingleStepTest> =
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    assert!(!tests.is_empty());
+    run_single_step(tests);
+}
+
+/// SingleStepTests per-opcode vectors (`nes6502/v1/xx.json`), all opcodes
+/// except `SINGLE_STEP_SKIP`
+#[test]
+#[ignore = "needs fixtures"]
+fn single_step() {
+    let dir = fixture("nes6502/v1");
+    for opcode in 0..=0xffu8 {
+        if SINGLE_STEP_SKIP.contains(&opcode) {
+            continue;
+        }
+        let path = dir.join(format!("{opcode:02x}.json"));
+        let json =
+            fs::read_to_string(&path).unwrap_or_else(|e| panic!("fixture {}: {e}", path.display()));
+        run_single_step(serde_json::from_str(&json).unwrap());
+    }
+}
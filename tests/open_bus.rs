@@ -0,0 +1,65 @@
+//! CPU data bus open-bus behaviour, with a small NROM program.
+
+use nessuno::asm::assemble;
+use nessuno::cartridge::Cartridge;
+use nessuno::system::{System, TvStandard};
+use std::fs;
+
+const PROGRAM: &str = "
+        .org $e000
+reset:  lda $4016       ; D5-D7 from the operand high byte $40
+        sta $00
+        lda $5000       ; unmapped: $50
+        sta $01
+        lda $4015       ; bit 5 of $40 is clear
+        sta $02
+        jmp loop
+
+        .org $e0e0
+loop:   jmp loop        ; leaves $e0 on the bus
+
+        .org $fffa
+        .word reset, reset, reset
+";
+
+/// NROM-128 image with `PROGRAM` in PRG ROM ($c000-$ffff)
+fn rom_image() -> Vec<u8> {
+    let program = assemble(PROGRAM, 0xe000).unwrap();
+    let mut prg = vec![0xea; 0x4000];
+    for segment in &program.segments {
+        let offset = segment.addr as usize - 0xc000;
+        prg[offset..offset + segment.data.len()].copy_from_slice(&segment.data);
+    }
+
+    let mut image = vec![b'N', b'E', b'S', 0x1a, 1, 1, 0, 0];
+    image.resize(16, 0);
+    image.extend(prg);
+    image.extend(vec![0; 0x2000]);
+    image
+}
+
+#[test]
+fn open_bus() {
+    let path = std::env::temp_dir().join(format!("nessuno_open_bus_{}.nes", std::process::id()));
+    fs::write(&path, rom_image()).unwrap();
+    let cart = Cartridge::new(path.to_str().unwrap(), None).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let mut system = System::new(cart, 44100, TvStandard::Ntsc);
+    for _ in 0..3 * 1000 {
+        let res = system.clock();
+        if res.cpu_complete && system.cpu.pc == 0xe0e0 {
+            break;
+        }
+    }
+    assert_eq!(system.cpu.pc, 0xe0e0);
+
+    assert_eq!(system.read(0x0000), 0x40);
+    assert_eq!(system.read(0x0001), 0x50);
+    assert_eq!(system.read(0x0002), 0x00);
+
+    // after JMP $e0e0
+    assert_eq!(system.read(0x5000), 0xe0);
+    assert_eq!(system.read(0x4017), 0xe0);
+    assert_eq!(system.read(0x4015), 0x20);
+}